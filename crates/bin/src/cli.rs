@@ -0,0 +1,137 @@
+//! Command-line argument parsing
+
+use crate::command::Command;
+use crate::stats::StatsOptions;
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: launcher [--replace]
+       launcher <command>
+
+Options:
+  --replace              Take over from an already running launcher
+
+Commands (forwarded to the running launcher):
+  show [<x> <y>]         Open the popup (at the cursor by default)
+  hide                   Close the popup
+  launch <path>          Launch a program or document
+  add-clipboard <text>   Add text to the clipboard history
+  reload-config          Re-read the config file
+  quit                   Stop the running launcher
+
+Diagnostics:
+  trigger record <file>  Record mouse button events until Ctrl-C
+  trigger replay <file>  Replay a recording against the trigger config
+
+Statistics:
+  stats [options]        Launches per app per day, top documents, trends
+                         and clipboard paste counts
+    --weekly             Count per week (Monday to Sunday) instead of per day
+    --periods <n>        Number of days/weeks up to today (default 7/8)
+    --top <n>            Documents and clipboard entries listed (default 10)
+    --csv | --json       Export instead of printing tables
+    --output <file>      Write to a file instead of stdout";
+
+/// What the process was asked to do
+#[derive(Debug, PartialEq)]
+pub enum Cli {
+    /// Run the launcher in the foreground
+    Run { replace: bool },
+    /// Forward a command to the running launcher
+    Send(Command),
+    /// Record trigger input to a file
+    TriggerRecord(PathBuf),
+    /// Replay recorded trigger input against the config
+    TriggerReplay(PathBuf),
+    /// Print or export usage statistics
+    Stats(StatsOptions),
+    /// Print usage
+    Help,
+}
+
+/// Parse arguments (without the program name)
+pub fn parse_args<I>(args: I) -> Result<Cli>
+where
+    I: IntoIterator<Item = String>,
+{
+    let args: Vec<String> = args.into_iter().collect();
+    let Some(first) = args.first() else {
+        return Ok(Cli::Run { replace: false });
+    };
+    let rest = &args[1..];
+
+    let command = match first.as_str() {
+        "-h" | "--help" | "help" => return Ok(Cli::Help),
+        "--replace" if rest.is_empty() => return Ok(Cli::Run { replace: true }),
+        "trigger" => {
+            return match rest {
+                [mode, path] if mode == "record" => Ok(Cli::TriggerRecord(path.into())),
+                [mode, path] if mode == "replay" => Ok(Cli::TriggerReplay(path.into())),
+                _ => bail!("Usage: launcher trigger record|replay <file>"),
+            }
+        }
+        "stats" => return Ok(Cli::Stats(StatsOptions::parse(rest)?)),
+        "show" => match rest {
+            [] => Command::Show { position: None },
+            [x, y] => Command::Show {
+                position: Some((
+                    x.parse().context("Invalid x coordinate")?,
+                    y.parse().context("Invalid y coordinate")?,
+                )),
+            },
+            _ => bail!("show takes either no arguments or <x> <y>"),
+        },
+        "hide" if rest.is_empty() => Command::Hide,
+        "launch" if rest.len() == 1 => Command::Launch {
+            path: rest[0].clone(),
+        },
+        "add-clipboard" if !rest.is_empty() => Command::AddClipboard {
+            text: rest.join(" "),
+        },
+        "reload-config" if rest.is_empty() => Command::ReloadConfig,
+        "quit" if rest.is_empty() => Command::Quit,
+        _ => bail!("Invalid arguments: {}\n\n{}", args.join(" "), USAGE),
+    };
+
+    Ok(Cli::Send(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]).unwrap(), Cli::Run { replace: false });
+        assert_eq!(parse(&["--replace"]).unwrap(), Cli::Run { replace: true });
+        assert_eq!(
+            parse(&["show", "10", "20"]).unwrap(),
+            Cli::Send(Command::Show {
+                position: Some((10.0, 20.0))
+            })
+        );
+        assert_eq!(
+            parse(&["add-clipboard", "hello", "world"]).unwrap(),
+            Cli::Send(Command::AddClipboard {
+                text: "hello world".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["trigger", "replay", "clicks.jsonl"]).unwrap(),
+            Cli::TriggerReplay(PathBuf::from("clicks.jsonl"))
+        );
+        assert!(matches!(
+            parse(&["stats", "--json"]).unwrap(),
+            Cli::Stats(_)
+        ));
+        assert!(parse(&["trigger", "record"]).is_err());
+        assert!(parse(&["show", "10"]).is_err());
+        assert!(parse(&["launch"]).is_err());
+        assert!(parse(&["bogus"]).is_err());
+    }
+}
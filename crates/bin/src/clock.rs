@@ -0,0 +1,70 @@
+//! The clock gesture timing runs on
+//!
+//! Gesture times are offsets on `CLOCK_MONOTONIC`. libinput stamps its
+//! events with it, and evdev does too once [`use_monotonic`] switched the
+//! device over, so kernel event timestamps and [`now`] compare directly and
+//! the recognizer never reads the clock itself.
+
+use evdev::InputEvent;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `EVIOCSCLOCKID`: `_IOW('E', 0xa0, int)`
+const EVIOCSCLOCKID: libc::c_ulong = 0x4004_45a0;
+
+/// Current time on the monotonic clock
+pub fn now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Ask evdev to stamp a device's events with the monotonic clock
+///
+/// Returns false if the kernel refused; those events keep wall-clock
+/// timestamps and [`event_time`] converts them.
+pub fn use_monotonic(fd: RawFd) -> bool {
+    let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+    unsafe { libc::ioctl(fd, EVIOCSCLOCKID as _, &clock) == 0 }
+}
+
+/// Monotonic time of an evdev event (its `input_event.time`)
+pub fn event_time(event: &InputEvent, monotonic: bool) -> Duration {
+    if monotonic {
+        return from_timestamp(event.timestamp(), None);
+    }
+    from_timestamp(event.timestamp(), Some((SystemTime::now(), now())))
+}
+
+/// Monotonic time of a kernel timestamp, given the current wall-clock and
+/// monotonic times if the stamp is a wall-clock one
+fn from_timestamp(stamp: SystemTime, wall_clock: Option<(SystemTime, Duration)>) -> Duration {
+    let Some((wall_now, monotonic_now)) = wall_clock else {
+        return stamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    };
+    // Keep the event's age, so spacing between events is preserved
+    let age = wall_now.duration_since(stamp).unwrap_or_default();
+    monotonic_now.saturating_sub(age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_timestamps() {
+        let ms = Duration::from_millis;
+        let monotonic = UNIX_EPOCH + Duration::from_secs(42);
+        assert_eq!(from_timestamp(monotonic, None), Duration::from_secs(42));
+
+        // A wall-clock stamp 200 ms old
+        let wall_now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let now = Some((wall_now, ms(5_000)));
+        assert_eq!(from_timestamp(wall_now - ms(200), now), ms(4_800));
+        // Stamps from the future (clock adjusted) count as current
+        assert_eq!(from_timestamp(wall_now + ms(200), now), ms(5_000));
+    }
+}
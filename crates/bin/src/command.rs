@@ -0,0 +1,194 @@
+//! Commands that external clients (D-Bus, CLI) can send to the running launcher
+
+use crate::input::TriggerEvent;
+use launcher_core::platform::{get_data_source, PlatformDataSource};
+use launcher_core::{Config, ConfigManager, ItemType, LaunchItem, UsageTracker};
+use launcher_ui::{add_clipboard_entry, PopupControl};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// A request from an external client
+///
+/// Serialized as one JSON object per line on the IPC socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    /// Open the popup at the given screen position, or at the cursor if `None`
+    Show { position: Option<(f64, f64)> },
+    /// Close the popup if it is open
+    Hide,
+    /// Launch a program or document by path
+    Launch { path: String },
+    /// Add text to the clipboard history
+    AddClipboard { text: String },
+    /// Re-read the config file
+    ReloadConfig,
+    /// Save state and exit (used by `--replace`)
+    Quit,
+}
+
+/// Executes commands on a background thread
+///
+/// `Show` is forwarded to the main loop as a trigger, since the popup must run
+/// on the main thread; everything else is handled here so it works while the
+/// popup is open.
+pub struct Dispatcher {
+    config_manager: Arc<ConfigManager>,
+    usage_tracker: Arc<Mutex<UsageTracker>>,
+    popup: PopupControl,
+    trigger_tx: Sender<TriggerEvent>,
+}
+
+impl Dispatcher {
+    pub fn new(
+        config_manager: Arc<ConfigManager>,
+        usage_tracker: Arc<Mutex<UsageTracker>>,
+        popup: PopupControl,
+        trigger_tx: Sender<TriggerEvent>,
+    ) -> Self {
+        Self {
+            config_manager,
+            usage_tracker,
+            popup,
+            trigger_tx,
+        }
+    }
+
+    /// Start the dispatcher thread and return the sender for submitting commands
+    pub fn start(self) -> Sender<Command> {
+        let (command_tx, command_rx) = channel();
+        thread::spawn(move || self.run(command_rx));
+        command_tx
+    }
+
+    fn run(self, command_rx: Receiver<Command>) {
+        for command in command_rx {
+            log::debug!("Command: {:?}", command);
+            self.handle(command);
+        }
+    }
+
+    fn handle(&self, command: Command) {
+        match command {
+            Command::Show { position } => {
+                let _ = self.trigger_tx.send(TriggerEvent {
+                    position,
+                    timestamp: Instant::now(),
+                    profile: None,
+                });
+            }
+            Command::Hide => self.popup.request_hide(),
+            Command::Launch { path } => {
+                let item = resolve_item(&self.config_manager.get(), &path);
+                self.launch(&item);
+            }
+            Command::AddClipboard { text } => {
+                let max_history = self.config_manager.get().max_clipboard_history;
+                self.popup
+                    .add_clipboard(text, |text| add_clipboard_entry(text, max_history));
+            }
+            Command::ReloadConfig => {
+                if let Err(e) = self.config_manager.reload() {
+                    log::error!("Failed to reload config: {}", e);
+                }
+            }
+            // The main loop exits once the popup (if open) has closed
+            Command::Quit => self.popup.request_quit(),
+        }
+    }
+
+    /// Launch an item and record usage, mirroring a click in the popup
+    fn launch(&self, item: &LaunchItem) {
+        if let Err(e) = get_data_source().launch(item) {
+            log::error!("Failed to launch {}: {}", item.name, e);
+            return;
+        }
+
+        if let Ok(mut tracker) = self.usage_tracker.lock() {
+            match item.item_type {
+                ItemType::Program | ItemType::Shortcut => {
+                    tracker.record_program(item);
+                }
+                ItemType::Document => {
+                    tracker.record_document(&item.path, &item.name);
+                }
+            }
+            let _ = tracker.save_if_dirty();
+        }
+
+        self.popup.notify_launched(item);
+    }
+}
+
+/// Find a configured item by path, or build one from the path itself
+fn resolve_item(config: &Config, path: &str) -> LaunchItem {
+    if let Some(item) = config
+        .pinned_programs
+        .iter()
+        .chain(&config.pinned_documents)
+        .chain(&config.shortcuts)
+        .find(|item| item.path == path)
+    {
+        return item.clone();
+    }
+
+    let file = Path::new(path);
+    let is_document = file
+        .metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 == 0);
+    let name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+
+    LaunchItem {
+        name,
+        path: path.to_string(),
+        icon: None,
+        args: vec![],
+        item_type: if is_document {
+            ItemType::Document
+        } else {
+            ItemType::Program
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_wire_format() {
+        let command = Command::Show {
+            position: Some((10.0, 20.0)),
+        };
+        let json = serde_json::to_string(&command).unwrap();
+        assert_eq!(json, r#"{"command":"show","position":[10.0,20.0]}"#);
+        assert_eq!(serde_json::from_str::<Command>(&json).unwrap(), command);
+
+        let json = r#"{"command":"add-clipboard","text":"a\nb"}"#;
+        assert_eq!(
+            serde_json::from_str::<Command>(json).unwrap(),
+            Command::AddClipboard {
+                text: "a\nb".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_item_prefers_config() {
+        let config = Config::default();
+        let shortcut = config.shortcuts[0].clone();
+        assert_eq!(resolve_item(&config, &shortcut.path), shortcut);
+
+        let item = resolve_item(&config, "/usr/bin/some-tool");
+        assert_eq!(item.name, "some-tool");
+        assert_eq!(item.item_type, ItemType::Program);
+    }
+}
@@ -0,0 +1,453 @@
+//! Cursor position sources
+//!
+//! evdev only reports motion, not where the cursor is, so the position at
+//! trigger time comes from the display server when possible:
+//!
+//! - X11: `QueryPointer` on the root window (also XWayland, but there it only
+//!   updates while the pointer is over X11 windows)
+//! - Wayland: compositor IPC (Hyprland socket, GNOME Shell `Eval`, a KWin
+//!   script calling back `ReportPosition`), or a position pushed over D-Bus
+//! - evdev: relative motion accumulated from the last known position, or
+//!   absolute devices (VM tablets) scaled to the screen
+//!
+//! [`query`] returns the position together with the [`Source`] it came from.
+
+use crate::dbus;
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::ConnectionExt as _;
+
+/// Position used when no source is available (visible on any screen)
+const DEFAULT_POSITION: (f64, f64) = (400.0, 300.0);
+
+/// How long to wait for a KWin script to report back
+pub const KWIN_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// How long a position pushed over D-Bus stays valid
+const REPORT_MAX_AGE: Duration = Duration::from_secs(1);
+
+/// Where a cursor position came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    X11,
+    Hyprland,
+    GnomeShell,
+    KWin,
+    /// Pushed by an external client via D-Bus `ReportPosition`
+    Reported,
+    /// Estimated from evdev motion
+    Evdev,
+    /// Nothing available
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::X11 => "X11",
+            Source::Hyprland => "Hyprland IPC",
+            Source::GnomeShell => "GNOME Shell",
+            Source::KWin => "KWin script",
+            Source::Reported => "D-Bus report",
+            Source::Evdev => "evdev estimate",
+            Source::Default => "default",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A cursor position and the source it came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    pub source: Source,
+}
+
+impl Position {
+    pub fn xy(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+}
+
+/// Pointer position estimated from evdev motion, clamped to the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Estimate {
+    x: f64,
+    y: f64,
+    screen: (f64, f64),
+}
+
+impl Estimate {
+    fn apply_relative(&mut self, dx: f64, dy: f64) {
+        self.x = (self.x + dx).clamp(0.0, self.screen.0 - 1.0);
+        self.y = (self.y + dy).clamp(0.0, self.screen.1 - 1.0);
+    }
+
+    /// Move to a fraction (0.0-1.0) of the screen on either axis
+    fn apply_absolute(&mut self, fx: Option<f64>, fy: Option<f64>) {
+        if let Some(fx) = fx {
+            self.x = (fx.clamp(0.0, 1.0) * (self.screen.0 - 1.0)).round();
+        }
+        if let Some(fy) = fy {
+            self.y = (fy.clamp(0.0, 1.0) * (self.screen.1 - 1.0)).round();
+        }
+    }
+}
+
+struct State {
+    estimate: Option<Estimate>,
+    /// Last position pushed over D-Bus, and when
+    reported: Option<((f64, f64), Instant)>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    estimate: None,
+    reported: None,
+});
+
+/// Start estimating the position from evdev motion
+///
+/// Returns the screen size, or `None` if it is unknown (no estimate then).
+pub fn init_estimate() -> Option<(f64, f64)> {
+    let screen = screen_size()?;
+    let (x, y) = query().xy();
+    if let Ok(mut state) = STATE.lock() {
+        state.estimate = Some(Estimate { x, y, screen });
+    }
+    Some(screen)
+}
+
+/// Apply relative motion; returns the new estimate and screen size
+pub fn move_relative(dx: f64, dy: f64) -> Option<(f64, f64, (f64, f64))> {
+    let mut state = STATE.lock().ok()?;
+    let estimate = state.estimate.as_mut()?;
+    estimate.apply_relative(dx, dy);
+    Some((estimate.x, estimate.y, estimate.screen))
+}
+
+/// Apply an absolute position as a fraction of the device range
+pub fn move_absolute(fx: Option<f64>, fy: Option<f64>) -> Option<(f64, f64, (f64, f64))> {
+    let mut state = STATE.lock().ok()?;
+    let estimate = state.estimate.as_mut()?;
+    estimate.apply_absolute(fx, fy);
+    Some((estimate.x, estimate.y, estimate.screen))
+}
+
+/// Record a cursor position reported by an external client
+pub fn report(x: f64, y: f64) {
+    if let Ok(mut state) = STATE.lock() {
+        state.reported = Some(((x, y), Instant::now()));
+    }
+}
+
+/// Query the current cursor position from the best available source
+pub fn query() -> Position {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let hyprland = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some();
+
+    for source in sources(wayland, &desktop, hyprland) {
+        let result = match source {
+            Source::X11 => query_x11(),
+            Source::Hyprland => query_hyprland(),
+            Source::GnomeShell => query_gnome_shell(),
+            Source::KWin => query_kwin(),
+            Source::Reported => reported(),
+            Source::Evdev => estimate(),
+            Source::Default => Ok(DEFAULT_POSITION),
+        };
+        match result {
+            Ok((x, y)) => {
+                log::debug!("Cursor position ({}, {}) from {}", x, y, source);
+                if !matches!(source, Source::Evdev | Source::Default) {
+                    sync_estimate(x, y);
+                }
+                return Position { x, y, source };
+            }
+            Err(e) => log::debug!("Cursor source {} unavailable: {:#}", source, e),
+        }
+    }
+    unreachable!("the default source always succeeds")
+}
+
+/// Sources to try, in order, for the current session
+fn sources(wayland: bool, desktop: &str, hyprland: bool) -> Vec<Source> {
+    let mut sources = Vec::new();
+    if wayland {
+        if hyprland {
+            sources.push(Source::Hyprland);
+        }
+        let desktop = desktop.to_ascii_lowercase();
+        if desktop.contains("gnome") {
+            sources.push(Source::GnomeShell);
+        }
+        if desktop.contains("kde") {
+            sources.push(Source::KWin);
+        }
+        sources.push(Source::Reported);
+        // XWayland is stale while the pointer is over native Wayland windows
+        sources.push(Source::Evdev);
+        sources.push(Source::X11);
+    } else {
+        sources.push(Source::X11);
+        sources.push(Source::Reported);
+        sources.push(Source::Evdev);
+    }
+    sources.push(Source::Default);
+    sources
+}
+
+/// Correct accumulated drift with a real cursor position
+fn sync_estimate(x: f64, y: f64) {
+    if let Ok(mut state) = STATE.lock() {
+        if let Some(estimate) = state.estimate.as_mut() {
+            estimate.x = x;
+            estimate.y = y;
+        }
+    }
+}
+
+fn estimate() -> Result<(f64, f64)> {
+    let state = STATE
+        .lock()
+        .map_err(|_| anyhow::anyhow!("state poisoned"))?;
+    match state.estimate {
+        Some(estimate) => Ok((estimate.x, estimate.y)),
+        None => bail!("no motion estimate (screen size unknown)"),
+    }
+}
+
+fn reported() -> Result<(f64, f64)> {
+    let state = STATE
+        .lock()
+        .map_err(|_| anyhow::anyhow!("state poisoned"))?;
+    fresh_report(state.reported, Instant::now())
+}
+
+/// A reported position, unless it's older than [`REPORT_MAX_AGE`] at `now`
+fn fresh_report(reported: Option<((f64, f64), Instant)>, now: Instant) -> Result<(f64, f64)> {
+    match reported {
+        Some((position, at)) if now.saturating_duration_since(at) <= REPORT_MAX_AGE => Ok(position),
+        Some((_, at)) => bail!("last report is {:?} old", now.saturating_duration_since(at)),
+        None => bail!("nothing reported"),
+    }
+}
+
+/// Query the pointer on the X11 root window
+fn query_x11() -> Result<(f64, f64)> {
+    let (conn, screen) = x11rb::connect(None).context("No X11 display")?;
+    let root = conn.setup().roots[screen].root;
+    let reply = conn.query_pointer(root)?.reply()?;
+    Ok((reply.root_x as f64, reply.root_y as f64))
+}
+
+/// Query the size of the X11 root window (all monitors)
+pub fn screen_size() -> Option<(f64, f64)> {
+    let (conn, screen) = x11rb::connect(None).ok()?;
+    let root = &conn.setup().roots[screen];
+    Some((root.width_in_pixels as f64, root.height_in_pixels as f64))
+}
+
+/// Ask Hyprland over its IPC socket
+fn query_hyprland() -> Result<(f64, f64)> {
+    let reply = hyprland_request("cursorpos")?;
+    parse_hyprland(&reply).with_context(|| format!("Unexpected reply {:?}", reply))
+}
+
+/// Send a request (as for `hyprctl`) to Hyprland's IPC socket
+pub fn hyprland_request(request: &str) -> Result<String> {
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
+    let runtime = std::env::var("XDG_RUNTIME_DIR")?;
+    let socket = PathBuf::from(runtime)
+        .join("hypr")
+        .join(signature)
+        .join(".socket.sock");
+
+    let mut stream = UnixStream::connect(&socket)
+        .with_context(|| format!("Failed to connect to {:?}", socket))?;
+    stream.set_read_timeout(Some(Duration::from_millis(200)))?;
+    stream.write_all(request.as_bytes())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+/// Parse `hyprctl cursorpos` output: `"1234, 567"`
+fn parse_hyprland(reply: &str) -> Option<(f64, f64)> {
+    let (x, y) = reply.trim().split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+/// Ask GNOME Shell (needs unsafe mode or the `Eval` API to be enabled)
+fn query_gnome_shell() -> Result<(f64, f64)> {
+    let conn = zbus::blocking::Connection::session()?;
+    let reply = conn.call_method(
+        Some("org.gnome.Shell"),
+        "/org/gnome/Shell",
+        Some("org.gnome.Shell"),
+        "Eval",
+        &("global.get_pointer()",),
+    )?;
+    let (ok, result): (bool, String) = reply.body().deserialize()?;
+    if !ok {
+        bail!("Eval refused: {}", result);
+    }
+    parse_gnome_shell(&result).with_context(|| format!("Unexpected result {:?}", result))
+}
+
+/// Parse `global.get_pointer()` output: `"[x,y,modifiers]"`
+fn parse_gnome_shell(result: &str) -> Option<(f64, f64)> {
+    let mut parts = result.trim().trim_matches(['[', ']']).split(',');
+    let x = parts.next()?.trim().parse().ok()?;
+    let y = parts.next()?.trim().parse().ok()?;
+    Some((x, y))
+}
+
+/// Run a one-off KWin script that reports `workspace.cursorPos` back to us
+fn query_kwin() -> Result<(f64, f64)> {
+    let script = format!(
+        "var p = workspace.cursorPos;\
+         callDBus(\"{0}\", \"{1}\", \"{0}\", \"ReportPosition\", p.x, p.y);",
+        dbus::SERVICE_NAME,
+        dbus::OBJECT_PATH
+    );
+    run_kwin_script("cursor", &script, |requested| {
+        wait_for_report(requested, KWIN_TIMEOUT)
+    })?
+    .context("KWin script did not report a position")
+}
+
+/// Load and start a KWin script, wait for its D-Bus callback, then unload it
+///
+/// `wait` gets the time the script was requested and polls for the result.
+pub fn run_kwin_script<T>(
+    name: &str,
+    source: &str,
+    wait: impl FnOnce(Instant) -> Option<T>,
+) -> Result<Option<T>> {
+    let script = crate::instance::runtime_dir()?.join(format!("kwin_{}.js", name));
+    std::fs::write(&script, source)?;
+
+    let conn = zbus::blocking::Connection::session()?;
+    let plugin = format!("launcher_{}_{}", name, std::process::id());
+    let requested = Instant::now();
+
+    conn.call_method(
+        Some("org.kde.KWin"),
+        "/Scripting",
+        Some("org.kde.kwin.Scripting"),
+        "loadScript",
+        &(script.to_string_lossy().as_ref(), plugin.as_str()),
+    )
+    .context("KWin loadScript failed")?;
+    conn.call_method(
+        Some("org.kde.KWin"),
+        "/Scripting",
+        Some("org.kde.kwin.Scripting"),
+        "start",
+        &(),
+    )
+    .context("KWin start failed")?;
+
+    let result = wait(requested);
+
+    let _ = conn.call_method(
+        Some("org.kde.KWin"),
+        "/Scripting",
+        Some("org.kde.kwin.Scripting"),
+        "unloadScript",
+        &(plugin.as_str(),),
+    );
+
+    Ok(result)
+}
+
+/// Wait for a `ReportPosition` call made after `since`
+fn wait_for_report(since: Instant, timeout: Duration) -> Option<(f64, f64)> {
+    while since.elapsed() < timeout {
+        let fresh = STATE
+            .lock()
+            .ok()
+            .and_then(|state| state.reported)
+            .filter(|(_, at)| *at >= since);
+        if let Some((position, _)) = fresh {
+            return Some(position);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_order() {
+        assert_eq!(
+            sources(false, "", false),
+            vec![
+                Source::X11,
+                Source::Reported,
+                Source::Evdev,
+                Source::Default
+            ]
+        );
+        assert_eq!(
+            sources(true, "KDE", false),
+            vec![
+                Source::KWin,
+                Source::Reported,
+                Source::Evdev,
+                Source::X11,
+                Source::Default
+            ]
+        );
+        assert_eq!(
+            sources(true, "ubuntu:GNOME", true)[..2],
+            [Source::Hyprland, Source::GnomeShell]
+        );
+    }
+
+    #[test]
+    fn test_parse_compositor_replies() {
+        assert_eq!(parse_hyprland("1234, 567\n"), Some((1234.0, 567.0)));
+        assert_eq!(parse_hyprland("ok"), None);
+        assert_eq!(parse_gnome_shell("[812,440,16]"), Some((812.0, 440.0)));
+        assert_eq!(parse_gnome_shell(""), None);
+    }
+
+    #[test]
+    fn test_stale_report() {
+        let at = Instant::now();
+        let report = Some(((10.0, 20.0), at));
+        assert_eq!(fresh_report(report, at).unwrap(), (10.0, 20.0));
+        assert!(fresh_report(report, at + Duration::from_millis(900)).is_ok());
+        assert!(fresh_report(report, at + Duration::from_secs(5)).is_err());
+        assert!(fresh_report(None, at).is_err());
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut estimate = Estimate {
+            x: 100.0,
+            y: 100.0,
+            screen: (1920.0, 1080.0),
+        };
+        estimate.apply_relative(-500.0, 30.0);
+        assert_eq!((estimate.x, estimate.y), (0.0, 130.0));
+        estimate.apply_relative(5000.0, 5000.0);
+        assert_eq!((estimate.x, estimate.y), (1919.0, 1079.0));
+
+        estimate.apply_absolute(Some(0.5), None);
+        assert_eq!((estimate.x, estimate.y), (960.0, 1079.0));
+    }
+}
@@ -0,0 +1,209 @@
+//! D-Bus service for desktop integration (GNOME/KDE)
+//!
+//! Exposes `org.rmanov.Launcher` on the session bus at `/org/rmanov/Launcher`.
+
+use crate::command::Command;
+use crate::cursor;
+use crate::focus;
+use anyhow::{Context, Result};
+use launcher_ui::PopupControl;
+use std::sync::mpsc::Sender;
+use std::thread;
+use zbus::blocking::{connection, Connection};
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+/// Well-known bus name and interface name
+pub const SERVICE_NAME: &str = "org.rmanov.Launcher";
+
+/// Object path the service is registered at
+pub const OBJECT_PATH: &str = "/org/rmanov/Launcher";
+
+/// D-Bus object forwarding method calls to the command dispatcher
+struct LauncherService {
+    command_tx: Sender<Command>,
+}
+
+impl LauncherService {
+    fn send(&self, command: Command) -> zbus::fdo::Result<()> {
+        self.command_tx
+            .send(command)
+            .map_err(|_| zbus::fdo::Error::Failed("Launcher is shutting down".to_string()))
+    }
+}
+
+#[interface(name = "org.rmanov.Launcher")]
+impl LauncherService {
+    /// Open the popup at the given screen position
+    fn show(&self, x: f64, y: f64) -> zbus::fdo::Result<()> {
+        self.send(Command::Show {
+            position: Some((x, y)),
+        })
+    }
+
+    /// Close the popup if it is open
+    fn hide(&self) -> zbus::fdo::Result<()> {
+        self.send(Command::Hide)
+    }
+
+    /// Launch a program or document by path
+    fn launch(&self, path: String) -> zbus::fdo::Result<()> {
+        self.send(Command::Launch { path })
+    }
+
+    /// Add text to the clipboard history
+    fn add_clipboard(&self, text: String) -> zbus::fdo::Result<()> {
+        self.send(Command::AddClipboard { text })
+    }
+
+    /// Re-read the config file
+    fn reload_config(&self) -> zbus::fdo::Result<()> {
+        self.send(Command::ReloadConfig)
+    }
+
+    /// Cursor position pushed by a compositor script (same as the Python `Svc.ReportPosition`)
+    fn report_position(&self, x: i32, y: i32) {
+        cursor::report(x as f64, y as f64);
+    }
+
+    /// Focused app id pushed by a compositor script
+    fn report_focus(&self, app_id: String) {
+        focus::report(&app_id);
+    }
+
+    /// Emitted after an item is launched from the popup or via `Launch`
+    #[zbus(signal)]
+    async fn launched(emitter: &SignalEmitter<'_>, name: &str, path: &str) -> zbus::Result<()>;
+}
+
+/// Register the service on the session bus
+///
+/// The returned connection must be kept alive for the service to stay registered.
+pub fn serve(command_tx: Sender<Command>, popup: &PopupControl) -> Result<Connection> {
+    let builder = connection::Builder::session().context("Failed to connect to session bus")?;
+    serve_on(builder, command_tx, popup)
+}
+
+fn serve_on(
+    builder: connection::Builder<'_>,
+    command_tx: Sender<Command>,
+    popup: &PopupControl,
+) -> Result<Connection> {
+    let connection = builder
+        .name(SERVICE_NAME)?
+        .serve_at(OBJECT_PATH, LauncherService { command_tx })?
+        .build()
+        .with_context(|| format!("Failed to register D-Bus service {}", SERVICE_NAME))?;
+
+    // Forward launches to the `Launched` signal
+    let iface = connection
+        .object_server()
+        .interface::<_, LauncherService>(OBJECT_PATH)?;
+    let launches = popup.subscribe_launches();
+    thread::spawn(move || {
+        for item in launches {
+            let signal = LauncherService::launched(iface.signal_emitter(), &item.name, &item.path);
+            if let Err(e) = zbus::block_on(signal) {
+                log::warn!("Failed to emit Launched signal: {}", e);
+            }
+        }
+    });
+
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launcher_core::{ItemType, LaunchItem};
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command as ProcCommand, Stdio};
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    /// A private session bus, killed on drop
+    struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        fn start() -> Option<Self> {
+            let mut daemon = ProcCommand::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    fn test_methods_and_launched_signal() {
+        let Some(bus) = TestBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+
+        let (command_tx, command_rx) = channel();
+        let popup = PopupControl::new();
+        let builder = connection::Builder::address(bus.address.as_str()).unwrap();
+        let _service = serve_on(builder, command_tx, &popup).unwrap();
+
+        let client = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let proxy =
+            zbus::blocking::Proxy::new(&client, SERVICE_NAME, OBJECT_PATH, SERVICE_NAME).unwrap();
+
+        proxy.call_method("Show", &(10.0f64, 20.0f64)).unwrap();
+        proxy.call_method("Launch", &("/usr/bin/foot",)).unwrap();
+        proxy.call_method("ReloadConfig", &()).unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            command_rx.recv_timeout(timeout).unwrap(),
+            Command::Show {
+                position: Some((10.0, 20.0))
+            }
+        );
+        assert_eq!(
+            command_rx.recv_timeout(timeout).unwrap(),
+            Command::Launch {
+                path: "/usr/bin/foot".to_string()
+            }
+        );
+        assert_eq!(
+            command_rx.recv_timeout(timeout).unwrap(),
+            Command::ReloadConfig
+        );
+
+        let mut signals = proxy.receive_signal("Launched").unwrap();
+        popup.notify_launched(&LaunchItem {
+            name: "Foot".to_string(),
+            path: "/usr/bin/foot".to_string(),
+            icon: None,
+            args: vec![],
+            item_type: ItemType::Program,
+        });
+        let message = signals.next().unwrap();
+        let (name, path): (String, String) = message.body().deserialize().unwrap();
+        assert_eq!(name, "Foot");
+        assert_eq!(path, "/usr/bin/foot");
+    }
+}
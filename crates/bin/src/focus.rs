@@ -0,0 +1,265 @@
+//! Focused application at trigger time, for context-aware suggestions
+//!
+//! The popup learns which programs and snippets are used over which app, so
+//! the app that had focus when the popup was triggered is looked up from:
+//!
+//! - X11: `_NET_ACTIVE_WINDOW` on the root window, then its `WM_CLASS`
+//! - Sway: the focused node of `get_tree` over the i3 IPC socket (`SWAYSOCK`)
+//! - Hyprland: `activewindow` over its IPC socket
+//! - KWin: a one-off script calling back `ReportFocus`
+//!
+//! [`query`] returns a lowercase app id (Wayland app id or X11 class name).
+
+use crate::cursor;
+use crate::dbus;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fmt;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+
+/// i3/Sway IPC message header magic
+const I3_MAGIC: &[u8] = b"i3-ipc";
+
+/// i3/Sway IPC `GET_TREE` message type
+const I3_GET_TREE: u32 = 4;
+
+/// Where the focused app came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    X11,
+    Sway,
+    Hyprland,
+    KWin,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::X11 => "X11",
+            Source::Sway => "Sway IPC",
+            Source::Hyprland => "Hyprland IPC",
+            Source::KWin => "KWin script",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Last app id reported by the KWin script, and when
+static REPORTED: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+
+/// Record the focused app reported by a KWin script
+pub fn report(app_id: &str) {
+    if let Ok(mut reported) = REPORTED.lock() {
+        *reported = Some((app_id.to_string(), Instant::now()));
+    }
+}
+
+/// Query the focused app from the best available source
+pub fn query() -> Option<String> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let hyprland = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some();
+    let sway = std::env::var_os("SWAYSOCK").is_some();
+
+    for source in sources(wayland, &desktop, hyprland, sway) {
+        let result = match source {
+            Source::X11 => query_x11(),
+            Source::Sway => query_sway(),
+            Source::Hyprland => query_hyprland(),
+            Source::KWin => query_kwin(),
+        };
+        match result.map(|app| normalize(&app)) {
+            Ok(app) if !app.is_empty() => {
+                log::debug!("Focused app {:?} from {}", app, source);
+                return Some(app);
+            }
+            Ok(_) => log::debug!("No focused app from {}", source),
+            Err(e) => log::debug!("Focus source {} unavailable: {:#}", source, e),
+        }
+    }
+    None
+}
+
+/// Sources to try, in order, for the current session
+fn sources(wayland: bool, desktop: &str, hyprland: bool, sway: bool) -> Vec<Source> {
+    let mut sources = Vec::new();
+    if wayland {
+        if hyprland {
+            sources.push(Source::Hyprland);
+        }
+        if sway {
+            sources.push(Source::Sway);
+        }
+        if desktop.to_ascii_lowercase().contains("kde") {
+            sources.push(Source::KWin);
+        }
+    }
+    // Also covers XWayland windows on Wayland
+    sources.push(Source::X11);
+    sources
+}
+
+/// App ids are compared case-insensitively (`Firefox` and `firefox`)
+fn normalize(app: &str) -> String {
+    app.trim().to_lowercase()
+}
+
+/// Read `WM_CLASS` of the window in `_NET_ACTIVE_WINDOW`
+fn query_x11() -> Result<String> {
+    let (conn, screen) = x11rb::connect(None).context("No X11 display")?;
+    let root = conn.setup().roots[screen].root;
+    let active = conn.intern_atom(true, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+
+    let reply = conn
+        .get_property(false, root, active, AtomEnum::WINDOW, 0, 1)?
+        .reply()?;
+    let window = reply
+        .value32()
+        .and_then(|mut values| values.next())
+        .filter(|&window| window != 0)
+        .context("No active window")?;
+
+    let reply = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+        .reply()?;
+    parse_wm_class(&reply.value).context("Window has no WM_CLASS")
+}
+
+/// `WM_CLASS` is `instance\0class\0`; the class names the application
+fn parse_wm_class(value: &[u8]) -> Option<String> {
+    let mut parts = value.split(|&b| b == 0).filter(|part| !part.is_empty());
+    let instance = parts.next()?;
+    let class = parts.next().unwrap_or(instance);
+    Some(String::from_utf8_lossy(class).into_owned())
+}
+
+/// Ask Sway for its window tree and find the focused window
+fn query_sway() -> Result<String> {
+    let socket = std::env::var("SWAYSOCK")?;
+    let mut stream =
+        UnixStream::connect(&socket).with_context(|| format!("Failed to connect to {}", socket))?;
+    stream.set_read_timeout(Some(Duration::from_millis(200)))?;
+
+    let mut request = I3_MAGIC.to_vec();
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(&I3_GET_TREE.to_ne_bytes());
+    stream.write_all(&request)?;
+
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != I3_MAGIC {
+        bail!("Unexpected reply from {}", socket);
+    }
+    let length = u32::from_ne_bytes(header[6..10].try_into()?) as usize;
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload)?;
+
+    let tree: Value = serde_json::from_slice(&payload)?;
+    focused_sway_app(&tree).context("No focused window")
+}
+
+/// App id (Wayland) or class (XWayland) of the focused node in a Sway tree
+fn focused_sway_app(node: &Value) -> Option<String> {
+    if node["focused"].as_bool() == Some(true) {
+        let app = node["app_id"]
+            .as_str()
+            .or_else(|| node["window_properties"]["class"].as_str())?;
+        return Some(app.to_string());
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(focused_sway_app)
+}
+
+/// Ask Hyprland over its IPC socket
+fn query_hyprland() -> Result<String> {
+    let reply = cursor::hyprland_request("j/activewindow")?;
+    let window: Value =
+        serde_json::from_str(&reply).with_context(|| format!("Unexpected reply {:?}", reply))?;
+    window["class"]
+        .as_str()
+        .map(str::to_string)
+        .context("No active window")
+}
+
+/// Run a one-off KWin script that reports the active window's class back to us
+fn query_kwin() -> Result<String> {
+    // `activeWindow` in KWin 6, `activeClient` in KWin 5
+    let script = format!(
+        "var w = workspace.activeWindow || workspace.activeClient;\
+         callDBus(\"{0}\", \"{1}\", \"{0}\", \"ReportFocus\", w ? String(w.resourceClass) : \"\");",
+        dbus::SERVICE_NAME,
+        dbus::OBJECT_PATH
+    );
+    cursor::run_kwin_script("focus", &script, |requested| {
+        wait_for_report(requested, cursor::KWIN_TIMEOUT)
+    })?
+    .context("KWin script did not report the focused window")
+}
+
+/// Wait for a `ReportFocus` call made after `since`
+fn wait_for_report(since: Instant, timeout: Duration) -> Option<String> {
+    while since.elapsed() < timeout {
+        let fresh = REPORTED
+            .lock()
+            .ok()
+            .and_then(|reported| reported.clone())
+            .filter(|(_, at)| *at >= since);
+        if let Some((app, _)) = fresh {
+            return Some(app);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_order() {
+        assert_eq!(sources(false, "", false, false), vec![Source::X11]);
+        assert_eq!(
+            sources(true, "KDE", false, false),
+            vec![Source::KWin, Source::X11]
+        );
+        assert_eq!(
+            sources(true, "sway", false, true),
+            vec![Source::Sway, Source::X11]
+        );
+    }
+
+    #[test]
+    fn test_parse_window_info() {
+        assert_eq!(
+            parse_wm_class(b"navigator\0Firefox\0").as_deref(),
+            Some("Firefox")
+        );
+        assert_eq!(parse_wm_class(b"xterm\0").as_deref(), Some("xterm"));
+        assert_eq!(parse_wm_class(b""), None);
+
+        let tree = serde_json::json!({
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [
+                    {"focused": false, "app_id": "firefox"},
+                    {"focused": false, "nodes": [], "floating_nodes": [
+                        {"focused": true, "app_id": null, "window_properties": {"class": "Gimp"}}
+                    ]}
+                ]
+            }]
+        });
+        assert_eq!(focused_sway_app(&tree).as_deref(), Some("Gimp"));
+        assert_eq!(normalize(" Gimp "), "gimp");
+    }
+}
@@ -0,0 +1,1169 @@
+//! Gesture recognition for configurable popup triggers
+//!
+//! Device-agnostic: the input listener translates raw events into
+//! [`GestureInput`] and periodically calls [`GestureRecognizer::tick`] so
+//! time-based gestures (long press, hot corner dwell, touch hold) can fire.
+//!
+//! The recognizer is a pure state machine: it never reads the clock. Every
+//! input carries its event timestamp (see [`crate::clock`]), so replays and
+//! tests are deterministic. Timestamps from different devices may arrive
+//! slightly out of order; intervals saturate at zero instead of panicking.
+
+use launcher_core::{Corner, Direction, Gesture, GestureConfig, Modifier, MouseButton};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Distance from a screen corner (px) that counts as "in the corner"
+const HOT_CORNER_SIZE: f64 = 2.0;
+
+/// Finger movement (fraction of the device size) still counted as a tap or hold
+const TOUCH_SLOP: f64 = 0.03;
+
+/// A normalized input event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GestureInput {
+    Button {
+        button: MouseButton,
+        pressed: bool,
+    },
+    Modifier {
+        modifier: Modifier,
+        pressed: bool,
+    },
+    /// Any other keyboard key, by Linux key code
+    Key {
+        code: u16,
+        pressed: bool,
+    },
+    /// Pointer position and the size of the screen it moves on
+    PointerMoved {
+        x: f64,
+        y: f64,
+        screen: (f64, f64),
+    },
+    /// Fingers on a touch device and their centroid (0.0 to 1.0)
+    Touch {
+        fingers: u8,
+        position: (f64, f64),
+    },
+    /// The device turned the touch into something else (scrolling, pointer
+    /// motion); ends it without a tap
+    TouchCancel,
+}
+
+/// Fingers on a touch device, from the first touch until all are lifted
+#[derive(Debug, Clone, Copy)]
+struct TouchSession {
+    started: Duration,
+    max_fingers: u8,
+    /// Current finger count, and where and when it was reached
+    fingers: u8,
+    origin: (f64, f64),
+    since: Duration,
+    /// Fingers haven't moved since the count last changed
+    still: bool,
+    /// Fingers haven't moved at all (a tap is still possible)
+    tap: bool,
+    /// A gesture already fired; wait for all fingers to lift
+    fired: bool,
+}
+
+/// Chord and debounce windows, overridable per device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub simultaneous: Duration,
+    pub debounce: Duration,
+}
+
+/// Why the last input did not fire a gesture, for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rejection {
+    /// All chord buttons were down, but pressed too far apart
+    ChordTooSlow { gap: Duration },
+    /// A gesture matched too soon after the previous trigger
+    Debounced { since_last: Duration },
+}
+
+/// Detects configured gestures from a stream of input events
+pub struct GestureRecognizer {
+    gestures: Vec<GestureConfig>,
+    defaults: Thresholds,
+    /// Thresholds of the device that produced the current input
+    thresholds: Thresholds,
+    /// Buttons currently held, with their press time
+    buttons_down: HashMap<MouseButton, Duration>,
+    /// Buttons whose current press already fired a long press
+    long_press_fired: HashSet<MouseButton>,
+    /// Time of the last (unconsumed) press per button, for double clicks
+    last_press: HashMap<MouseButton, Duration>,
+    modifiers_down: HashSet<Modifier>,
+    /// Last modifier pressed with no other key since, for double taps
+    modifier_tap: Option<(Modifier, Duration)>,
+    /// Corner the pointer is in, when it entered, and whether it already fired
+    corner: Option<(Corner, Duration, bool)>,
+    touch: Option<TouchSession>,
+    last_trigger: Option<Duration>,
+    /// Press spread of the chord completed by the last input, if any
+    last_chord_gap: Option<Duration>,
+    last_rejection: Option<Rejection>,
+}
+
+impl GestureRecognizer {
+    pub fn new(
+        gestures: Vec<GestureConfig>,
+        simultaneous_threshold: Duration,
+        debounce_duration: Duration,
+    ) -> Self {
+        for config in &gestures {
+            if let Gesture::Hotkey { key, .. } = &config.gesture {
+                if key_code(key).is_none() {
+                    log::warn!("Unknown hotkey key {:?}, gesture disabled", key);
+                }
+            }
+        }
+        Self {
+            gestures,
+            defaults: Thresholds {
+                simultaneous: simultaneous_threshold,
+                debounce: debounce_duration,
+            },
+            thresholds: Thresholds {
+                simultaneous: simultaneous_threshold,
+                debounce: debounce_duration,
+            },
+            buttons_down: HashMap::new(),
+            long_press_fired: HashSet::new(),
+            last_press: HashMap::new(),
+            modifiers_down: HashSet::new(),
+            modifier_tap: None,
+            corner: None,
+            touch: None,
+            last_trigger: None,
+            last_chord_gap: None,
+            last_rejection: None,
+        }
+    }
+
+    /// Whether any gesture needs keyboard input
+    pub fn needs_keyboard(&self) -> bool {
+        self.gestures.iter().any(|g| {
+            matches!(
+                g.gesture,
+                Gesture::ModifierClick { .. } | Gesture::Hotkey { .. } | Gesture::DoubleTap { .. }
+            )
+        })
+    }
+
+    /// Whether any gesture needs pointer position updates
+    pub fn needs_pointer(&self) -> bool {
+        self.gestures
+            .iter()
+            .any(|g| matches!(g.gesture, Gesture::HotCorner { .. }))
+    }
+
+    /// Whether any gesture needs multitouch input
+    pub fn needs_touch(&self) -> bool {
+        self.gestures.iter().any(|g| {
+            matches!(
+                g.gesture,
+                Gesture::TouchTap { .. } | Gesture::TouchSwipe { .. } | Gesture::TouchHold { .. }
+            )
+        })
+    }
+
+    /// Whether any gesture fires from [`GestureRecognizer::tick`]
+    pub fn needs_ticks(&self) -> bool {
+        self.gestures.iter().any(|g| {
+            matches!(
+                g.gesture,
+                Gesture::LongPress { .. }
+                    | Gesture::HotCorner { dwell_ms: 1.., .. }
+                    | Gesture::TouchHold { .. }
+            )
+        })
+    }
+
+    /// Buttons that take part in any chord gesture
+    pub fn chord_buttons(&self) -> HashSet<MouseButton> {
+        self.gestures
+            .iter()
+            .filter_map(|g| match &g.gesture {
+                Gesture::Chord { buttons } => Some(buttons.iter().copied()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Time between the first and last press of the chord the last input
+    /// completed, whether or not it was within the threshold
+    pub fn last_chord_gap(&self) -> Option<Duration> {
+        self.last_chord_gap
+    }
+
+    /// Why the last input or tick did not fire, if a gesture almost matched
+    pub fn last_rejection(&self) -> Option<Rejection> {
+        self.last_rejection
+    }
+
+    /// Feed an input event from a device without its own thresholds
+    #[cfg(test)]
+    pub fn handle(&mut self, input: GestureInput, now: Duration) -> Option<&GestureConfig> {
+        self.handle_with(input, now, None)
+    }
+
+    /// Feed an input event, returning the gesture it completed (if any)
+    ///
+    /// `thresholds` are the source device's, `None` for the defaults.
+    pub fn handle_with(
+        &mut self,
+        input: GestureInput,
+        now: Duration,
+        thresholds: Option<Thresholds>,
+    ) -> Option<&GestureConfig> {
+        self.thresholds = thresholds.unwrap_or(self.defaults);
+        self.last_chord_gap = None;
+        self.last_rejection = None;
+        let index = match input {
+            GestureInput::Button { button, pressed } => self.handle_button(button, pressed, now),
+            GestureInput::Modifier { modifier, pressed } => {
+                self.handle_modifier(modifier, pressed, now)
+            }
+            GestureInput::Key { code, pressed } => self.handle_key(code, pressed),
+            GestureInput::PointerMoved { x, y, screen } => {
+                self.corner = match (corner_at(x, y, screen), self.corner) {
+                    (Some(c), Some(current)) if c == current.0 => Some(current),
+                    (Some(c), _) => Some((c, now, false)),
+                    (None, _) => None,
+                };
+                self.check_corner(now)
+            }
+            GestureInput::Touch { fingers, position } => self.handle_touch(fingers, position, now),
+            GestureInput::TouchCancel => {
+                self.touch = None;
+                None
+            }
+        };
+        self.fire(index, now)
+    }
+
+    /// Check time-based gestures; call this regularly (e.g. on every poll timeout)
+    pub fn tick(&mut self, now: Duration) -> Option<&GestureConfig> {
+        self.thresholds = self.defaults;
+        self.last_rejection = None;
+        let index = self
+            .check_long_press(now)
+            .or_else(|| self.check_corner(now))
+            .or_else(|| self.check_touch_hold(now));
+        self.fire(index, now)
+    }
+
+    fn handle_modifier(
+        &mut self,
+        modifier: Modifier,
+        pressed: bool,
+        now: Duration,
+    ) -> Option<usize> {
+        if !pressed {
+            self.modifiers_down.remove(&modifier);
+            return None;
+        }
+        self.modifiers_down.insert(modifier);
+
+        let previous_tap = self.modifier_tap.replace((modifier, now));
+        let (previous, at) = previous_tap.filter(|(m, _)| *m == modifier)?;
+        let index = self
+            .gestures
+            .iter()
+            .position(|config| match config.gesture {
+                Gesture::DoubleTap {
+                    modifier: m,
+                    interval_ms,
+                } => m == previous && now.saturating_sub(at) <= Duration::from_millis(interval_ms),
+                _ => false,
+            })?;
+        // A third tap starts over
+        self.modifier_tap = None;
+        Some(index)
+    }
+
+    fn handle_key(&mut self, code: u16, pressed: bool) -> Option<usize> {
+        if !pressed {
+            return None;
+        }
+        // Ctrl+C and the like are not double taps
+        self.modifier_tap = None;
+
+        self.gestures
+            .iter()
+            .position(|config| match &config.gesture {
+                Gesture::Hotkey { modifiers, key } => {
+                    key_code(key) == Some(code)
+                        && modifiers.iter().all(|m| self.modifiers_down.contains(m))
+                }
+                _ => false,
+            })
+    }
+
+    fn handle_button(
+        &mut self,
+        button: MouseButton,
+        pressed: bool,
+        now: Duration,
+    ) -> Option<usize> {
+        if !pressed {
+            self.buttons_down.remove(&button);
+            self.long_press_fired.remove(&button);
+            return None;
+        }
+        self.modifier_tap = None;
+        self.buttons_down.insert(button, now);
+
+        let previous_press = self.last_press.insert(button, now);
+
+        self.last_chord_gap = self
+            .gestures
+            .iter()
+            .filter_map(|config| match &config.gesture {
+                Gesture::Chord { buttons } if buttons.contains(&button) => self.chord_gap(buttons),
+                _ => None,
+            })
+            .min();
+
+        let index = self
+            .gestures
+            .iter()
+            .position(|config| match &config.gesture {
+                Gesture::Chord { buttons } => {
+                    buttons.contains(&button) && self.chord_complete(buttons)
+                }
+                Gesture::ModifierClick {
+                    modifiers,
+                    button: b,
+                } => *b == button && modifiers.iter().all(|m| self.modifiers_down.contains(m)),
+                Gesture::DoubleClick {
+                    button: b,
+                    interval_ms,
+                } => {
+                    *b == button
+                        && previous_press.is_some_and(|prev| {
+                            now.saturating_sub(prev) <= Duration::from_millis(*interval_ms)
+                        })
+                }
+                _ => false,
+            });
+        let Some(index) = index else {
+            self.last_rejection = self
+                .last_chord_gap
+                .map(|gap| Rejection::ChordTooSlow { gap });
+            return None;
+        };
+
+        log::debug!("Gesture matched: {:?}", self.gestures[index].gesture);
+
+        // Consume the presses so they can't complete another gesture
+        if let Gesture::Chord { buttons } = &self.gestures[index].gesture {
+            for b in buttons {
+                self.buttons_down.remove(b);
+            }
+        }
+        self.last_press.remove(&button);
+
+        Some(index)
+    }
+
+    /// Time between the first and last press, if all chord buttons are down
+    fn chord_gap(&self, buttons: &[MouseButton]) -> Option<Duration> {
+        let times: Option<Vec<Duration>> = buttons
+            .iter()
+            .map(|b| self.buttons_down.get(b).copied())
+            .collect();
+        let times = times?;
+        let (first, last) = (times.iter().min()?, times.iter().max()?);
+        Some(last.saturating_sub(*first))
+    }
+
+    /// Check if all chord buttons are down and were pressed within the threshold
+    fn chord_complete(&self, buttons: &[MouseButton]) -> bool {
+        let Some(diff) = self.chord_gap(buttons) else {
+            return false;
+        };
+        log::debug!(
+            "chord: diff={:?} threshold={:?}",
+            diff,
+            self.thresholds.simultaneous
+        );
+        if diff > self.thresholds.simultaneous {
+            log::debug!("chord: REJECTED - diff too large");
+            return false;
+        }
+        true
+    }
+
+    fn check_long_press(&mut self, now: Duration) -> Option<usize> {
+        for (index, config) in self.gestures.iter().enumerate() {
+            if let Gesture::LongPress {
+                button,
+                duration_ms,
+            } = config.gesture
+            {
+                let held = self
+                    .buttons_down
+                    .get(&button)
+                    .is_some_and(|t| now.saturating_sub(*t) >= Duration::from_millis(duration_ms));
+                if held && self.long_press_fired.insert(button) {
+                    return Some(index);
+                }
+            }
+        }
+        None
+    }
+
+    fn check_corner(&mut self, now: Duration) -> Option<usize> {
+        let (corner, entered, fired) = self.corner?;
+        if fired {
+            return None;
+        }
+
+        let index = self
+            .gestures
+            .iter()
+            .position(|config| match config.gesture {
+                Gesture::HotCorner {
+                    corner: c,
+                    dwell_ms,
+                } => c == corner && now.saturating_sub(entered) >= Duration::from_millis(dwell_ms),
+                _ => false,
+            })?;
+
+        // Fire once per visit to the corner
+        self.corner = Some((corner, entered, true));
+        Some(index)
+    }
+
+    fn handle_touch(&mut self, fingers: u8, position: (f64, f64), now: Duration) -> Option<usize> {
+        if fingers == 0 {
+            // Lifting the fingers completes a tap
+            let session = self.touch.take()?;
+            if session.fired || !session.tap {
+                return None;
+            }
+            let duration = now.saturating_sub(session.started);
+            return self
+                .gestures
+                .iter()
+                .position(|config| match config.gesture {
+                    Gesture::TouchTap {
+                        fingers,
+                        max_duration_ms,
+                    } => {
+                        fingers == session.max_fingers
+                            && duration <= Duration::from_millis(max_duration_ms)
+                    }
+                    _ => false,
+                });
+        }
+
+        let session = self.touch.get_or_insert(TouchSession {
+            started: now,
+            max_fingers: fingers,
+            fingers,
+            origin: position,
+            since: now,
+            still: true,
+            tap: true,
+            fired: false,
+        });
+        if fingers != session.fingers {
+            // The centroid jumps when fingers land or lift
+            session.max_fingers = session.max_fingers.max(fingers);
+            session.fingers = fingers;
+            session.origin = position;
+            session.since = now;
+            session.still = true;
+            return None;
+        }
+
+        let (dx, dy) = (position.0 - session.origin.0, position.1 - session.origin.1);
+        if dx.abs().max(dy.abs()) > TOUCH_SLOP {
+            session.still = false;
+            session.tap = false;
+        }
+        if session.fired {
+            return None;
+        }
+        let session = *session;
+
+        let index = self
+            .gestures
+            .iter()
+            .position(|config| match config.gesture {
+                Gesture::TouchSwipe {
+                    fingers,
+                    direction,
+                    distance,
+                } => {
+                    let (along, across) = match direction {
+                        Direction::Up => (-dy, dx),
+                        Direction::Down => (dy, dx),
+                        Direction::Left => (-dx, dy),
+                        Direction::Right => (dx, dy),
+                    };
+                    fingers == session.fingers && along >= distance && along > across.abs()
+                }
+                _ => false,
+            })?;
+        self.touch = Some(TouchSession {
+            fired: true,
+            ..session
+        });
+        Some(index)
+    }
+
+    fn check_touch_hold(&mut self, now: Duration) -> Option<usize> {
+        let session = self.touch.as_mut()?;
+        if session.fired || !session.still {
+            return None;
+        }
+        let held = now.saturating_sub(session.since);
+        let index = self
+            .gestures
+            .iter()
+            .position(|config| match config.gesture {
+                Gesture::TouchHold {
+                    fingers,
+                    duration_ms,
+                } => fingers == session.fingers && held >= Duration::from_millis(duration_ms),
+                _ => false,
+            })?;
+        session.fired = true;
+        Some(index)
+    }
+
+    /// Apply debounce to a matched gesture
+    fn fire(&mut self, index: Option<usize>, now: Duration) -> Option<&GestureConfig> {
+        let index = index?;
+
+        if let Some(last) = self.last_trigger {
+            let since_last = now.saturating_sub(last);
+            if since_last < self.thresholds.debounce {
+                log::debug!("Gesture debounced");
+                self.last_rejection = Some(Rejection::Debounced { since_last });
+                return None;
+            }
+        }
+        self.last_trigger = Some(now);
+
+        self.gestures.get(index)
+    }
+}
+
+/// Linux key code for a key name like `space` or `f12`
+fn key_code(name: &str) -> Option<u16> {
+    let key = evdev::Key::from_str(&format!("KEY_{}", name.to_uppercase())).ok()?;
+    Some(key.code())
+}
+
+/// Which corner (if any) a position is in
+fn corner_at(x: f64, y: f64, screen: (f64, f64)) -> Option<Corner> {
+    let left = x <= HOT_CORNER_SIZE;
+    let right = x >= screen.0 - 1.0 - HOT_CORNER_SIZE;
+    let top = y <= HOT_CORNER_SIZE;
+    let bottom = y >= screen.1 - 1.0 - HOT_CORNER_SIZE;
+
+    match (left, right, top, bottom) {
+        (true, _, true, _) => Some(Corner::TopLeft),
+        (_, true, true, _) => Some(Corner::TopRight),
+        (true, _, _, true) => Some(Corner::BottomLeft),
+        (_, true, _, true) => Some(Corner::BottomRight),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recognizer(gestures: Vec<Gesture>) -> GestureRecognizer {
+        let gestures = gestures
+            .into_iter()
+            .enumerate()
+            .map(|(i, gesture)| GestureConfig {
+                gesture,
+                profile: Some(format!("profile{}", i)),
+            })
+            .collect();
+        GestureRecognizer::new(
+            gestures,
+            Duration::from_millis(50),
+            Duration::from_millis(500),
+        )
+    }
+
+    fn press(button: MouseButton) -> GestureInput {
+        GestureInput::Button {
+            button,
+            pressed: true,
+        }
+    }
+
+    fn release(button: MouseButton) -> GestureInput {
+        GestureInput::Button {
+            button,
+            pressed: false,
+        }
+    }
+
+    fn profile(config: Option<&GestureConfig>) -> Option<String> {
+        config.and_then(|c| c.profile.clone())
+    }
+
+    #[test]
+    fn test_chord_maps_to_profile() {
+        let mut r = recognizer(vec![
+            Gesture::Chord {
+                buttons: vec![MouseButton::Left, MouseButton::Right],
+            },
+            Gesture::Chord {
+                buttons: vec![MouseButton::Middle, MouseButton::Right],
+            },
+        ]);
+        let t = Duration::ZERO;
+
+        assert!(r.handle(press(MouseButton::Middle), t).is_none());
+        let fired = r.handle(press(MouseButton::Right), t + Duration::from_millis(20));
+        assert_eq!(profile(fired), Some("profile1".to_string()));
+
+        // Too far apart
+        let t = t + Duration::from_secs(2);
+        r.handle(release(MouseButton::Right), t);
+        r.handle(press(MouseButton::Left), t);
+        assert!(r
+            .handle(press(MouseButton::Right), t + Duration::from_millis(100))
+            .is_none());
+    }
+
+    #[test]
+    fn test_device_thresholds() {
+        let mut r = recognizer(vec![Gesture::Chord {
+            buttons: vec![MouseButton::Left, MouseButton::Right],
+        }]);
+        let trackball = Some(Thresholds {
+            simultaneous: Duration::from_millis(300),
+            debounce: Duration::from_millis(500),
+        });
+        let t = Duration::ZERO;
+
+        // 100 ms apart: too slow by default, fine for the trackball
+        r.handle(press(MouseButton::Left), t);
+        let late = t + Duration::from_millis(100);
+        assert!(r.handle(press(MouseButton::Right), late).is_none());
+        r.handle(release(MouseButton::Right), late);
+        assert!(r
+            .handle_with(press(MouseButton::Right), late, trackball)
+            .is_some());
+    }
+
+    #[test]
+    fn test_modifier_click_and_double_click() {
+        let mut r = recognizer(vec![
+            Gesture::ModifierClick {
+                modifiers: vec![Modifier::Super],
+                button: MouseButton::Left,
+            },
+            Gesture::DoubleClick {
+                button: MouseButton::Extra,
+                interval_ms: 300,
+            },
+        ]);
+        let t = Duration::ZERO;
+
+        assert!(r.handle(press(MouseButton::Left), t).is_none());
+        r.handle(
+            GestureInput::Modifier {
+                modifier: Modifier::Super,
+                pressed: true,
+            },
+            t,
+        );
+        assert!(r.handle(press(MouseButton::Left), t).is_some());
+
+        let t = t + Duration::from_secs(1);
+        assert!(r.handle(press(MouseButton::Extra), t).is_none());
+        r.handle(release(MouseButton::Extra), t);
+        let fired = r.handle(press(MouseButton::Extra), t + Duration::from_millis(200));
+        assert_eq!(profile(fired), Some("profile1".to_string()));
+    }
+
+    #[test]
+    fn test_long_press_and_hot_corner() {
+        let mut r = recognizer(vec![
+            Gesture::LongPress {
+                button: MouseButton::Side,
+                duration_ms: 600,
+            },
+            Gesture::HotCorner {
+                corner: Corner::BottomRight,
+                dwell_ms: 200,
+            },
+        ]);
+        let t = Duration::ZERO;
+
+        r.handle(press(MouseButton::Side), t);
+        assert!(r.tick(t + Duration::from_millis(300)).is_none());
+        assert!(r.tick(t + Duration::from_millis(700)).is_some());
+        // Fires only once per press
+        assert!(r.tick(t + Duration::from_secs(5)).is_none());
+
+        let t = t + Duration::from_secs(10);
+        let moved = GestureInput::PointerMoved {
+            x: 1919.0,
+            y: 1079.0,
+            screen: (1920.0, 1080.0),
+        };
+        assert!(r.handle(moved, t).is_none());
+        assert!(r.tick(t + Duration::from_millis(100)).is_none());
+        assert!(r.tick(t + Duration::from_millis(250)).is_some());
+        assert!(r.tick(t + Duration::from_secs(2)).is_none());
+    }
+
+    fn modifier(modifier: Modifier, pressed: bool) -> GestureInput {
+        GestureInput::Modifier { modifier, pressed }
+    }
+
+    fn key(name: &str, pressed: bool) -> GestureInput {
+        GestureInput::Key {
+            code: key_code(name).unwrap(),
+            pressed,
+        }
+    }
+
+    #[test]
+    fn test_hotkey_and_double_tap() {
+        let mut r = recognizer(vec![
+            Gesture::Hotkey {
+                modifiers: vec![Modifier::Super],
+                key: "space".to_string(),
+            },
+            Gesture::DoubleTap {
+                modifier: Modifier::Ctrl,
+                interval_ms: 300,
+            },
+        ]);
+        let ms = Duration::from_millis;
+        let t = Duration::ZERO;
+
+        assert!(r.handle(key("space", true), t).is_none());
+        r.handle(key("space", false), t);
+        r.handle(modifier(Modifier::Super, true), t);
+        let fired = r.handle(key("space", true), t + ms(50));
+        assert_eq!(profile(fired), Some("profile0".to_string()));
+        r.handle(modifier(Modifier::Super, false), t + ms(60));
+
+        // Ctrl, Ctrl
+        let t = t + Duration::from_secs(1);
+        r.handle(modifier(Modifier::Ctrl, true), t);
+        r.handle(modifier(Modifier::Ctrl, false), t + ms(50));
+        let fired = r.handle(modifier(Modifier::Ctrl, true), t + ms(150));
+        assert_eq!(profile(fired), Some("profile1".to_string()));
+
+        // Ctrl+C, Ctrl is not a double tap
+        let t = t + Duration::from_secs(1);
+        r.handle(modifier(Modifier::Ctrl, true), t);
+        r.handle(key("c", true), t + ms(30));
+        r.handle(modifier(Modifier::Ctrl, false), t + ms(60));
+        assert!(r
+            .handle(modifier(Modifier::Ctrl, true), t + ms(150))
+            .is_none());
+
+        // Too slow
+        let t = t + Duration::from_secs(1);
+        r.handle(modifier(Modifier::Ctrl, false), t);
+        assert!(r
+            .handle(modifier(Modifier::Ctrl, true), t + ms(400))
+            .is_none());
+    }
+
+    fn touch(fingers: u8, x: f64, y: f64) -> GestureInput {
+        GestureInput::Touch {
+            fingers,
+            position: (x, y),
+        }
+    }
+
+    #[test]
+    fn test_touch_gestures() {
+        let mut r = recognizer(vec![
+            Gesture::TouchTap {
+                fingers: 3,
+                max_duration_ms: 250,
+            },
+            Gesture::TouchSwipe {
+                fingers: 4,
+                direction: Direction::Up,
+                distance: 0.15,
+            },
+            Gesture::TouchHold {
+                fingers: 2,
+                duration_ms: 600,
+            },
+        ]);
+        let ms = Duration::from_millis;
+        let t = Duration::ZERO;
+
+        // Three-finger tap, fingers landing one after another
+        r.handle(touch(1, 0.5, 0.5), t);
+        r.handle(touch(3, 0.52, 0.5), t + ms(20));
+        assert!(r.handle(touch(2, 0.51, 0.5), t + ms(100)).is_none());
+        let fired = r.handle(touch(0, 0.5, 0.5), t + ms(120));
+        assert_eq!(profile(fired), Some("profile0".to_string()));
+
+        // Too slow for a tap
+        let t = t + Duration::from_secs(1);
+        r.handle(touch(3, 0.5, 0.5), t);
+        assert!(r.handle(touch(0, 0.5, 0.5), t + ms(400)).is_none());
+
+        // Four-finger swipe up fires once, mid-swipe
+        let t = t + Duration::from_secs(1);
+        r.handle(touch(4, 0.5, 0.8), t);
+        assert!(r.handle(touch(4, 0.52, 0.7), t + ms(50)).is_none());
+        let fired = r.handle(touch(4, 0.53, 0.6), t + ms(100));
+        assert_eq!(profile(fired), Some("profile1".to_string()));
+        assert!(r.handle(touch(4, 0.53, 0.3), t + ms(150)).is_none());
+        // Swiped, so lifting is not a tap
+        assert!(r.handle(touch(0, 0.53, 0.3), t + ms(200)).is_none());
+
+        // Two-finger hold, but not while scrolling
+        let t = t + Duration::from_secs(1);
+        r.handle(touch(2, 0.5, 0.5), t);
+        r.handle(touch(2, 0.5, 0.7), t + ms(100));
+        assert!(r.tick(t + ms(650)).is_none());
+        r.handle(touch(0, 0.5, 0.7), t + ms(700));
+
+        let t = t + Duration::from_secs(1);
+        r.handle(touch(2, 0.5, 0.5), t);
+        assert!(r.tick(t + ms(300)).is_none());
+        let fired = r.tick(t + ms(650));
+        assert_eq!(profile(fired), Some("profile2".to_string()));
+        assert!(r.tick(t + ms(900)).is_none());
+
+        // A touch the device turned into scrolling is neither a tap nor a hold
+        let t = t + Duration::from_secs(1);
+        r.handle(touch(3, 0.5, 0.5), t);
+        r.handle(GestureInput::TouchCancel, t + ms(50));
+        assert!(r.handle(touch(0, 0.5, 0.5), t + ms(100)).is_none());
+    }
+
+    /// Property-based tests over generated event sequences
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        const THRESHOLD: Duration = Duration::from_millis(50);
+        const DEBOUNCE: Duration = Duration::from_millis(500);
+
+        fn chord() -> GestureRecognizer {
+            recognizer(vec![Gesture::Chord {
+                buttons: vec![MouseButton::Left, MouseButton::Right],
+            }])
+        }
+
+        /// One of each gesture type
+        fn every_gesture() -> GestureRecognizer {
+            recognizer(vec![
+                Gesture::Chord {
+                    buttons: vec![MouseButton::Left, MouseButton::Right],
+                },
+                Gesture::ModifierClick {
+                    modifiers: vec![Modifier::Super],
+                    button: MouseButton::Left,
+                },
+                Gesture::LongPress {
+                    button: MouseButton::Side,
+                    duration_ms: 600,
+                },
+                Gesture::DoubleClick {
+                    button: MouseButton::Extra,
+                    interval_ms: 300,
+                },
+                Gesture::HotCorner {
+                    corner: Corner::BottomRight,
+                    dwell_ms: 100,
+                },
+                Gesture::Hotkey {
+                    modifiers: vec![Modifier::Super],
+                    key: "space".to_string(),
+                },
+                Gesture::DoubleTap {
+                    modifier: Modifier::Ctrl,
+                    interval_ms: 300,
+                },
+                Gesture::TouchTap {
+                    fingers: 3,
+                    max_duration_ms: 250,
+                },
+                Gesture::TouchSwipe {
+                    fingers: 4,
+                    direction: Direction::Up,
+                    distance: 0.15,
+                },
+                Gesture::TouchHold {
+                    fingers: 2,
+                    duration_ms: 600,
+                },
+            ])
+        }
+
+        fn any_button() -> impl Strategy<Value = MouseButton> {
+            prop_oneof![
+                Just(MouseButton::Left),
+                Just(MouseButton::Right),
+                Just(MouseButton::Middle),
+                Just(MouseButton::Side),
+                Just(MouseButton::Extra),
+            ]
+        }
+
+        fn any_input() -> impl Strategy<Value = GestureInput> {
+            let modifier = prop_oneof![
+                Just(Modifier::Ctrl),
+                Just(Modifier::Shift),
+                Just(Modifier::Alt),
+                Just(Modifier::Super),
+            ];
+            // Mostly the corners, so dwell gestures get exercised
+            let position = prop_oneof![
+                Just((0.0, 0.0)),
+                Just((1919.0, 1079.0)),
+                (0.0..1920.0, 0.0..1080.0),
+            ];
+            prop_oneof![
+                (any_button(), any::<bool>())
+                    .prop_map(|(button, pressed)| GestureInput::Button { button, pressed }),
+                (modifier, any::<bool>())
+                    .prop_map(|(modifier, pressed)| GestureInput::Modifier { modifier, pressed }),
+                (prop_oneof![Just("space"), Just("c")], any::<bool>())
+                    .prop_map(|(name, pressed)| key(name, pressed)),
+                position.prop_map(|(x, y)| GestureInput::PointerMoved {
+                    x,
+                    y,
+                    screen: (1920.0, 1080.0),
+                }),
+                (0u8..5, 0.0..1.0, 0.0..1.0).prop_map(|(f, x, y)| touch(f, x, y)),
+                Just(GestureInput::TouchCancel),
+            ]
+        }
+
+        /// An input, or a tick (`None`), after a delay in ms
+        fn any_steps() -> impl Strategy<Value = Vec<(Option<GestureInput>, u64)>> {
+            prop::collection::vec(
+                (prop::option::weighted(0.8, any_input()), 0u64..400),
+                0..200,
+            )
+        }
+
+        /// Run timestamped steps, returning when each trigger fired and its profile
+        fn run(
+            r: &mut GestureRecognizer,
+            steps: &[(Option<GestureInput>, Duration)],
+        ) -> Vec<(Duration, String)> {
+            steps
+                .iter()
+                .filter_map(|&(input, now)| {
+                    let fired = match input {
+                        Some(input) => r.handle(input, now),
+                        None => r.tick(now),
+                    };
+                    profile(fired).map(|p| (now, p))
+                })
+                .collect()
+        }
+
+        /// Turn delays into timestamps
+        fn timestamps(
+            steps: Vec<(Option<GestureInput>, u64)>,
+        ) -> Vec<(Option<GestureInput>, Duration)> {
+            let mut now = Duration::from_secs(1);
+            steps
+                .into_iter()
+                .map(|(input, delay)| {
+                    now += Duration::from_millis(delay);
+                    (input, now)
+                })
+                .collect()
+        }
+
+        /// Reference chord model: both buttons down within the threshold,
+        /// presses consumed on a match, matches debounced
+        fn chord_model(events: &[(bool, bool, Duration)]) -> Vec<Duration> {
+            let mut down: HashMap<bool, Duration> = HashMap::new();
+            let mut last: Option<Duration> = None;
+            let mut fired = Vec::new();
+            for &(left, pressed, now) in events {
+                if !pressed {
+                    down.remove(&left);
+                    continue;
+                }
+                down.insert(left, now);
+                let (Some(&l), Some(&r)) = (down.get(&true), down.get(&false)) else {
+                    continue;
+                };
+                if l.max(r) - l.min(r) > THRESHOLD {
+                    continue;
+                }
+                down.clear();
+                if last.is_none_or(|last| now - last >= DEBOUNCE) {
+                    last = Some(now);
+                    fired.push(now);
+                }
+            }
+            fired
+        }
+
+        proptest! {
+            #[test]
+            fn prop_triggers_respect_debounce(steps in any_steps()) {
+                let steps = timestamps(steps);
+                let fired = run(&mut every_gesture(), &steps);
+                for pair in fired.windows(2) {
+                    prop_assert!(pair[1].0 - pair[0].0 >= DEBOUNCE);
+                }
+                // Pure: the same events always give the same triggers
+                prop_assert_eq!(run(&mut every_gesture(), &steps), fired);
+            }
+
+            #[test]
+            fn prop_out_of_order_timestamps(
+                steps in prop::collection::vec((prop::option::of(any_input()), 0u64..10_000), 0..200)
+            ) {
+                // Arbitrary, unordered timestamps must not panic or underflow
+                let steps: Vec<_> = steps
+                    .into_iter()
+                    .map(|(input, ms)| (input, Duration::from_millis(ms)))
+                    .collect();
+                let fired = run(&mut every_gesture(), &steps);
+                prop_assert_eq!(run(&mut every_gesture(), &steps), fired);
+            }
+
+            #[test]
+            fn prop_chord_matches_model(
+                events in prop::collection::vec((any::<bool>(), any::<bool>(), 0u64..120), 0..100)
+            ) {
+                let mut now = Duration::from_secs(1);
+                let events: Vec<_> = events
+                    .into_iter()
+                    .map(|(left, pressed, delay)| {
+                        now += Duration::from_millis(delay);
+                        (left, pressed, now)
+                    })
+                    .collect();
+
+                let mut r = chord();
+                let fired: Vec<_> = events
+                    .iter()
+                    .filter_map(|&(left, pressed, now)| {
+                        let button = if left { MouseButton::Left } else { MouseButton::Right };
+                        let input = GestureInput::Button { button, pressed };
+                        r.handle(input, now).map(|_| now)
+                    })
+                    .collect();
+                prop_assert_eq!(fired, chord_model(&events));
+            }
+
+            #[test]
+            fn prop_release_between_presses(held in 0u64..=25, wait in 0u64..=25) {
+                // Within the threshold, but Left is already up
+                let mut r = chord();
+                let t = Duration::from_secs(1);
+                r.handle(press(MouseButton::Left), t);
+                r.handle(release(MouseButton::Left), t + Duration::from_millis(held));
+                let late = t + Duration::from_millis(held + wait);
+                prop_assert!(r.handle(press(MouseButton::Right), late).is_none());
+            }
+
+            #[test]
+            fn prop_triple_press(gap in 0u64..=50, third in 0u64..5_000, left in any::<bool>()) {
+                let mut r = chord();
+                let t = Duration::from_secs(1);
+                r.handle(press(MouseButton::Left), t);
+                prop_assert!(r.handle(press(MouseButton::Right), t + Duration::from_millis(gap)).is_some());
+                // Pressing one chord button again, without releasing the other
+                let button = if left { MouseButton::Left } else { MouseButton::Right };
+                let again = t + Duration::from_millis(gap + third);
+                r.handle(release(button), again);
+                prop_assert!(r.handle(press(button), again).is_none());
+            }
+
+            #[test]
+            fn prop_triple_click_and_tap(gaps in prop::array::uniform2(0u64..=300)) {
+                let mut r = GestureRecognizer::new(
+                    every_gesture().gestures,
+                    THRESHOLD,
+                    Duration::ZERO,
+                );
+                let times = [
+                    Duration::from_secs(1),
+                    Duration::from_secs(1) + Duration::from_millis(gaps[0]),
+                    Duration::from_secs(1) + Duration::from_millis(gaps[0] + gaps[1]),
+                ];
+                // The second click or tap fires, the third starts over
+                let clicks: Vec<bool> = times
+                    .iter()
+                    .map(|&t| {
+                        r.handle(release(MouseButton::Extra), t);
+                        r.handle(press(MouseButton::Extra), t).is_some()
+                    })
+                    .collect();
+                prop_assert_eq!(clicks, vec![false, true, false]);
+                let taps: Vec<bool> = times
+                    .iter()
+                    .map(|&t| {
+                        let t = t + Duration::from_secs(10);
+                        r.handle(modifier(Modifier::Ctrl, false), t);
+                        r.handle(modifier(Modifier::Ctrl, true), t).is_some()
+                    })
+                    .collect();
+                prop_assert_eq!(taps, vec![false, true, false]);
+            }
+
+            #[test]
+            fn prop_press_during_debounce(since in 0u64..1_000, gap in 0u64..=50) {
+                let mut r = chord();
+                let t = Duration::from_secs(1);
+                let ms = Duration::from_millis;
+                r.handle(press(MouseButton::Left), t);
+                prop_assert!(r.handle(press(MouseButton::Right), t).is_some());
+                r.handle(release(MouseButton::Left), t + ms(since));
+                r.handle(release(MouseButton::Right), t + ms(since));
+
+                r.handle(press(MouseButton::Right), t + ms(since));
+                let fired = r.handle(press(MouseButton::Left), t + ms(since + gap)).is_some();
+                let since_last = ms(since + gap);
+                prop_assert_eq!(fired, since_last >= DEBOUNCE);
+                if !fired {
+                    prop_assert_eq!(r.last_rejection(), Some(Rejection::Debounced { since_last }));
+                }
+            }
+
+            #[test]
+            fn prop_chord_across_devices(left in 0u64..200, right in 0u64..200, left_first in any::<bool>()) {
+                // Two devices: events may be delivered in any order relative
+                // to their timestamps
+                let mut r = chord();
+                let t = Duration::from_secs(1);
+                let mut presses = [
+                    (MouseButton::Left, t + Duration::from_millis(left)),
+                    (MouseButton::Right, t + Duration::from_millis(right)),
+                ];
+                if !left_first {
+                    presses.reverse();
+                }
+                r.handle(press(presses[0].0), presses[0].1);
+                let fired = r.handle(press(presses[1].0), presses[1].1).is_some();
+                prop_assert_eq!(fired, left.abs_diff(right) <= 50);
+                prop_assert_eq!(r.last_chord_gap(), Some(Duration::from_millis(left.abs_diff(right))));
+            }
+        }
+    }
+}
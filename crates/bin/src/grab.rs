@@ -0,0 +1,218 @@
+//! Grab-and-reinject mode so trigger chords don't leak into applications
+//!
+//! A grabbed mouse (`EVIOCGRAB`) delivers events only to us. Everything is
+//! re-emitted through a uinput clone of the device, except the button
+//! presses (and matching releases) that formed a recognized chord. Presses of
+//! chord buttons are held back for up to the chord threshold until we know
+//! whether they are part of a chord.
+
+use anyhow::{Context, Result};
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{Device, InputEvent, InputEventKind, Key, Synchronization};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Name of the uinput device events are re-emitted through
+pub const VIRTUAL_DEVICE_NAME: &str = "Simple Program Launcher virtual mouse";
+
+/// Decides which events to forward, hold back or swallow
+pub struct ChordFilter {
+    /// Buttons that take part in a chord gesture
+    hold_keys: HashSet<Key>,
+    /// How long a press may be held back waiting for the rest of the chord
+    hold: Duration,
+    pending: Vec<(InputEvent, Instant)>,
+    /// Buttons whose press was swallowed; their release is swallowed too
+    swallowed: HashSet<Key>,
+}
+
+impl ChordFilter {
+    pub fn new(hold_keys: HashSet<Key>, hold: Duration) -> Self {
+        Self {
+            hold_keys,
+            hold,
+            pending: Vec::new(),
+            swallowed: HashSet::new(),
+        }
+    }
+
+    /// Filter one event; `triggered` is true if it completed a gesture
+    pub fn filter(&mut self, event: InputEvent, triggered: bool, now: Instant) -> Vec<InputEvent> {
+        let InputEventKind::Key(key) = event.kind() else {
+            return vec![event];
+        };
+
+        match event.value() {
+            1 if triggered => {
+                // The held presses and this one formed the chord: drop them all
+                for (held, _) in self.pending.drain(..) {
+                    if let InputEventKind::Key(k) = held.kind() {
+                        self.swallowed.insert(k);
+                    }
+                }
+                self.swallowed.insert(key);
+                vec![]
+            }
+            1 if self.hold_keys.contains(&key) => {
+                self.pending.push((event, now));
+                vec![]
+            }
+            _ if self.swallowed.contains(&key) => {
+                if event.value() == 0 {
+                    self.swallowed.remove(&key);
+                }
+                vec![]
+            }
+            0 if self.is_pending(key) => {
+                // Released before a chord completed: deliver the click as-is
+                let mut events = self.flush();
+                events.push(event);
+                events
+            }
+            _ => vec![event],
+        }
+    }
+
+    /// Release presses that have been held back longer than the chord threshold
+    pub fn expire(&mut self, now: Instant) -> Vec<InputEvent> {
+        let expired = self
+            .pending
+            .first()
+            .is_some_and(|(_, held_at)| now.duration_since(*held_at) > self.hold);
+        if expired {
+            self.flush()
+        } else {
+            vec![]
+        }
+    }
+
+    fn is_pending(&self, key: Key) -> bool {
+        self.pending
+            .iter()
+            .any(|(e, _)| e.kind() == InputEventKind::Key(key))
+    }
+
+    fn flush(&mut self) -> Vec<InputEvent> {
+        self.pending.drain(..).map(|(e, _)| e).collect()
+    }
+}
+
+/// A grabbed device and the virtual device its events are re-emitted through
+pub struct Reinjector {
+    virtual_device: VirtualDevice,
+    filter: ChordFilter,
+    /// Events of the current report, emitted at the next `SYN_REPORT`
+    frame: Vec<InputEvent>,
+}
+
+impl Reinjector {
+    /// Create a uinput clone of `device` and grab the original
+    pub fn new(device: &mut Device, filter: ChordFilter) -> Result<Self> {
+        let keys = device.supported_keys().context("Device has no buttons")?;
+        let axes = device
+            .supported_relative_axes()
+            .context("Only relative pointing devices can be grabbed")?;
+
+        let virtual_device = VirtualDeviceBuilder::new()
+            .context("Failed to open /dev/uinput")?
+            .name(VIRTUAL_DEVICE_NAME)
+            .input_id(device.input_id())
+            .with_keys(keys)?
+            .with_relative_axes(axes)?
+            .build()
+            .context("Failed to create uinput device")?;
+
+        device.grab().context("Failed to grab device")?;
+
+        Ok(Self {
+            virtual_device,
+            filter,
+            frame: Vec::new(),
+        })
+    }
+
+    /// Process one event read from the grabbed device
+    pub fn process(&mut self, event: InputEvent, triggered: bool, now: Instant) {
+        match event.kind() {
+            InputEventKind::Synchronization(Synchronization::SYN_REPORT)
+                if !self.frame.is_empty() =>
+            {
+                let frame = std::mem::take(&mut self.frame);
+                self.emit(&frame);
+            }
+            InputEventKind::Key(_) | InputEventKind::RelAxis(_) => {
+                let forwarded = self.filter.filter(event, triggered, now);
+                self.frame.extend(forwarded);
+            }
+            // MSC_SCAN and friends are not needed by consumers
+            _ => {}
+        }
+    }
+
+    /// Emit presses whose chord window has passed
+    pub fn expire(&mut self, now: Instant) {
+        let events = self.filter.expire(now);
+        if !events.is_empty() {
+            self.emit(&events);
+        }
+    }
+
+    fn emit(&mut self, events: &[InputEvent]) {
+        // `emit` appends the SYN_REPORT itself
+        if let Err(e) = self.virtual_device.emit(events) {
+            log::warn!("Failed to re-emit events: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::EventType;
+
+    fn key_event(key: Key, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY, key.code(), value)
+    }
+
+    fn filter() -> ChordFilter {
+        ChordFilter::new(
+            HashSet::from([Key::BTN_LEFT, Key::BTN_RIGHT]),
+            Duration::from_millis(50),
+        )
+    }
+
+    #[test]
+    fn test_chord_is_swallowed() {
+        let mut f = filter();
+        let t = Instant::now();
+
+        assert!(f.filter(key_event(Key::BTN_LEFT, 1), false, t).is_empty());
+        assert!(f.filter(key_event(Key::BTN_RIGHT, 1), true, t).is_empty());
+        assert!(f.expire(t + Duration::from_secs(1)).is_empty());
+        assert!(f.filter(key_event(Key::BTN_LEFT, 0), false, t).is_empty());
+        assert!(f.filter(key_event(Key::BTN_RIGHT, 0), false, t).is_empty());
+
+        // Later clicks pass through again
+        let released = f.filter(key_event(Key::BTN_MIDDLE, 1), false, t);
+        assert_eq!(released.len(), 1);
+    }
+
+    #[test]
+    fn test_plain_click_is_delayed_not_lost() {
+        let mut f = filter();
+        let t = Instant::now();
+
+        // Quick click: press is released together with the release
+        assert!(f.filter(key_event(Key::BTN_LEFT, 1), false, t).is_empty());
+        let events = f.filter(key_event(Key::BTN_LEFT, 0), false, t);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].value(), 1);
+        assert_eq!(events[1].value(), 0);
+
+        // Held press (e.g. drag start) is released after the threshold
+        assert!(f.filter(key_event(Key::BTN_RIGHT, 1), false, t).is_empty());
+        assert!(f.expire(t + Duration::from_millis(20)).is_empty());
+        assert_eq!(f.expire(t + Duration::from_millis(60)).len(), 1);
+        assert_eq!(f.filter(key_event(Key::BTN_RIGHT, 0), false, t).len(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+//! Input device hotplug via inotify on `/dev/input`
+//!
+//! udev creates `eventN` nodes when a device appears and removes them when it
+//! goes away. Node permissions (the `input` group ACL) are often applied just
+//! after creation, so attribute changes are reported as additions too; the
+//! listener ignores paths it already tracks.
+
+use anyhow::{Context, Result};
+use inotify::{EventMask, Inotify, WatchMask};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+/// Directory with the evdev device nodes
+pub const INPUT_DIR: &str = "/dev/input";
+
+/// A device node appeared or disappeared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceChange {
+    Added(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Watches a directory for evdev device nodes coming and going
+pub struct DeviceWatcher {
+    inotify: Inotify,
+    dir: PathBuf,
+    buffer: Vec<u8>,
+}
+
+impl DeviceWatcher {
+    pub fn new(dir: &Path) -> Result<Self> {
+        let inotify = Inotify::init().context("Failed to initialize inotify")?;
+        inotify
+            .watches()
+            .add(
+                dir,
+                WatchMask::CREATE | WatchMask::DELETE | WatchMask::ATTRIB,
+            )
+            .with_context(|| format!("Failed to watch {:?}", dir))?;
+
+        Ok(Self {
+            inotify,
+            dir: dir.to_path_buf(),
+            buffer: vec![0; 4096],
+        })
+    }
+
+    /// Read pending changes without blocking
+    pub fn read_changes(&mut self) -> Vec<DeviceChange> {
+        let events = match self.inotify.read_events(&mut self.buffer) {
+            Ok(events) => events,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Vec::new(),
+            Err(e) => {
+                log::warn!("Failed to read device hotplug events: {}", e);
+                return Vec::new();
+            }
+        };
+
+        events
+            .filter_map(|event| {
+                let name = event.name?.to_str()?;
+                // Only evdev nodes; skip mouseN, js0, by-id/ etc.
+                if !name.starts_with("event") {
+                    return None;
+                }
+                let path = self.dir.join(name);
+                if event.mask.contains(EventMask::DELETE) {
+                    Some(DeviceChange::Removed(path))
+                } else {
+                    Some(DeviceChange::Added(path))
+                }
+            })
+            .collect()
+    }
+}
+
+impl AsRawFd for DeviceWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Poll the watcher until it reports something (or give up after ~2s)
+    fn wait_changes(watcher: &mut DeviceWatcher) -> Vec<DeviceChange> {
+        for _ in 0..100 {
+            let changes = watcher.read_changes();
+            if !changes.is_empty() {
+                return changes;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        Vec::new()
+    }
+
+    /// Wait for a specific change, ignoring unrelated devices
+    fn wait_for(watcher: &mut DeviceWatcher, expected: DeviceChange) {
+        loop {
+            let changes = wait_changes(watcher);
+            assert!(!changes.is_empty(), "timed out waiting for {:?}", expected);
+            if changes.contains(&expected) {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove_nodes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut watcher = DeviceWatcher::new(dir).unwrap();
+
+        // Non-evdev nodes are ignored
+        fs::write(dir.join("mouse0"), "").unwrap();
+        fs::write(dir.join("event7"), "").unwrap();
+        assert_eq!(
+            wait_changes(&mut watcher),
+            vec![DeviceChange::Added(dir.join("event7"))]
+        );
+
+        // Permissions applied by udev after creation count as an addition
+        let mut permissions = fs::metadata(dir.join("event7")).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(dir.join("event7"), permissions).unwrap();
+        assert_eq!(
+            wait_changes(&mut watcher),
+            vec![DeviceChange::Added(dir.join("event7"))]
+        );
+
+        fs::remove_file(dir.join("event7")).unwrap();
+        assert_eq!(
+            wait_changes(&mut watcher),
+            vec![DeviceChange::Removed(dir.join("event7"))]
+        );
+    }
+
+    #[test]
+    fn test_uinput_device_hotplug() {
+        use evdev::uinput::VirtualDeviceBuilder;
+        use evdev::{AttributeSet, Key};
+
+        let Ok(builder) = VirtualDeviceBuilder::new() else {
+            eprintln!("Skipping: /dev/uinput not available");
+            return;
+        };
+        let mut watcher = DeviceWatcher::new(Path::new(INPUT_DIR)).unwrap();
+
+        let mut keys = AttributeSet::<Key>::new();
+        keys.insert(Key::BTN_LEFT);
+        let mut device = builder
+            .name("launcher hotplug test")
+            .with_keys(&keys)
+            .unwrap()
+            .build()
+            .unwrap();
+        let node = device
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .find_map(|p| p.ok())
+            .unwrap();
+
+        wait_for(&mut watcher, DeviceChange::Added(node.clone()));
+        drop(device);
+        wait_for(&mut watcher, DeviceChange::Removed(node));
+    }
+}
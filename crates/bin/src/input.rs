@@ -1,286 +0,0 @@
-//! Mouse input listener using evdev for Linux (works on both X11 and Wayland)
-
-use evdev::{Device, InputEventKind, Key};
-use std::os::unix::io::AsRawFd;
-use std::process::Command as ProcCommand;
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::{Duration, Instant};
-
-/// Query the current cursor position via X11 (works on Wayland via XWayland).
-fn query_cursor_position() -> (f64, f64) {
-    if let Ok(output) = ProcCommand::new("python3")
-        .args([
-            "-c",
-            "from Xlib import display;d=display.Display();p=d.screen().root.query_pointer()._data;print(p['root_x'],p['root_y'])",
-        ])
-        .output()
-    {
-        if let Ok(s) = std::string::String::from_utf8(output.stdout) {
-            let parts: Vec<&str> = s.trim().split_whitespace().collect();
-            if parts.len() == 2 {
-                if let (Ok(x), Ok(y)) = (parts[0].parse(), parts[1].parse()) {
-                    return (x, y);
-                }
-            }
-        }
-    }
-    (400.0, 300.0) // fallback: visible on screen
-}
-
-/// Trigger event sent when L+R click is detected
-#[derive(Debug, Clone)]
-pub struct TriggerEvent {
-    /// Mouse position at trigger time (always 0,0 with evdev - use cursor position from GUI)
-    pub position: (f64, f64),
-    /// Timestamp
-    pub timestamp: Instant,
-}
-
-/// Mouse state tracker
-struct MouseState {
-    left_pressed: Option<Instant>,
-    right_pressed: Option<Instant>,
-    last_trigger: Option<Instant>,
-}
-
-impl Default for MouseState {
-    fn default() -> Self {
-        Self {
-            left_pressed: None,
-            right_pressed: None,
-            last_trigger: None,
-        }
-    }
-}
-
-/// Find all mouse devices (devices that support BTN_LEFT)
-fn find_mouse_devices() -> Vec<Device> {
-    evdev::enumerate()
-        .filter_map(|(_, device)| {
-            if let Some(keys) = device.supported_keys() {
-                if keys.contains(Key::BTN_LEFT) {
-                    log::info!("Found mouse device: {:?}", device.name());
-                    return Some(device);
-                }
-            }
-            None
-        })
-        .collect()
-}
-
-/// Input listener that detects simultaneous L+R mouse clicks
-pub struct InputListener {
-    state: Arc<Mutex<MouseState>>,
-    simultaneous_threshold: Duration,
-    debounce_duration: Duration,
-    trigger_tx: Sender<TriggerEvent>,
-}
-
-impl InputListener {
-    /// Create a new input listener
-    ///
-    /// # Arguments
-    /// * `simultaneous_threshold_ms` - Maximum time between L and R clicks to count as simultaneous
-    /// * `debounce_ms` - Minimum time between triggers to prevent accidental double-triggers
-    pub fn new(simultaneous_threshold_ms: u64, debounce_ms: u64) -> (Self, Receiver<TriggerEvent>) {
-        let (trigger_tx, trigger_rx) = channel();
-
-        let listener = Self {
-            state: Arc::new(Mutex::new(MouseState::default())),
-            simultaneous_threshold: Duration::from_millis(simultaneous_threshold_ms),
-            debounce_duration: Duration::from_millis(debounce_ms),
-            trigger_tx,
-        };
-
-        (listener, trigger_rx)
-    }
-
-    /// Check if both buttons are pressed within the threshold
-    fn check_trigger(&self) -> Option<TriggerEvent> {
-        let mut state = self.state.lock().ok()?;
-
-        let (left_time, right_time) = match (state.left_pressed, state.right_pressed) {
-            (Some(l), Some(r)) => (l, r),
-            _ => return None,
-        };
-
-        // Check if both buttons were pressed within the threshold
-        let diff = if left_time > right_time {
-            left_time.duration_since(right_time)
-        } else {
-            right_time.duration_since(left_time)
-        };
-
-        log::debug!(
-            "check_trigger: diff={:?} threshold={:?}",
-            diff,
-            self.simultaneous_threshold
-        );
-        if diff > self.simultaneous_threshold {
-            log::debug!("check_trigger: REJECTED - diff too large");
-            return None;
-        }
-        log::info!("L+R TRIGGER DETECTED!");
-
-        // Check debounce
-        let now = Instant::now();
-        if let Some(last) = state.last_trigger {
-            if now.duration_since(last) < self.debounce_duration {
-                return None;
-            }
-        }
-
-        // Trigger!
-        state.last_trigger = Some(now);
-
-        // Clear button states to prevent re-triggering
-        state.left_pressed = None;
-        state.right_pressed = None;
-
-        Some(TriggerEvent {
-            position: query_cursor_position(),
-            timestamp: now,
-        })
-    }
-
-    /// Handle a button event
-    fn handle_button(&self, key: Key, pressed: bool) {
-        log::debug!("Button event: {:?} pressed={}", key, pressed);
-        match key {
-            Key::BTN_LEFT => {
-                if let Ok(mut state) = self.state.lock() {
-                    state.left_pressed = if pressed { Some(Instant::now()) } else { None };
-                }
-                if pressed {
-                    if let Some(trigger) = self.check_trigger() {
-                        let _ = self.trigger_tx.send(trigger);
-                    }
-                }
-            }
-            Key::BTN_RIGHT => {
-                if let Ok(mut state) = self.state.lock() {
-                    state.right_pressed = if pressed { Some(Instant::now()) } else { None };
-                }
-                if pressed {
-                    if let Some(trigger) = self.check_trigger() {
-                        let _ = self.trigger_tx.send(trigger);
-                    }
-                }
-            }
-            _ => {}
-        }
-    }
-
-    /// Start listening for mouse events
-    ///
-    /// This spawns a background thread that processes events and returns immediately.
-    /// The thread will run until the process exits.
-    ///
-    /// Note: Requires read access to /dev/input/event* devices.
-    /// User typically needs to be in the 'input' group: sudo usermod -aG input $USER
-    pub fn start(self) -> thread::JoinHandle<()> {
-        thread::spawn(move || {
-            log::info!("Starting evdev mouse event listener...");
-
-            let mut devices = find_mouse_devices();
-
-            if devices.is_empty() {
-                log::error!(
-                    "No mouse devices found. Make sure you have read access to /dev/input/event*. \
-                     Try: sudo usermod -aG input $USER (then log out and back in)"
-                );
-                return;
-            }
-
-            log::info!("Monitoring {} mouse device(s)", devices.len());
-
-            // Create poll file descriptors
-            let mut pollfds: Vec<libc::pollfd> = devices
-                .iter()
-                .map(|d| libc::pollfd {
-                    fd: d.as_raw_fd(),
-                    events: libc::POLLIN,
-                    revents: 0,
-                })
-                .collect();
-
-            loop {
-                // Wait for events on any device (100ms timeout)
-                let ret =
-                    unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, 100) };
-
-                if ret <= 0 {
-                    continue; // Timeout or error, try again
-                }
-
-                log::debug!("poll() returned {} - events ready", ret);
-
-                // Check which devices have events
-                for (i, pollfd) in pollfds.iter_mut().enumerate() {
-                    if pollfd.revents != 0 {
-                        log::debug!("Device {} revents=0x{:x}", i, pollfd.revents);
-                    }
-                    if pollfd.revents & libc::POLLIN != 0 {
-                        match devices[i].fetch_events() {
-                            Ok(events) => {
-                                for event in events {
-                                    log::debug!("Raw event: {:?}", event);
-                                    if let InputEventKind::Key(key) = event.kind() {
-                                        let pressed = event.value() == 1;
-                                        self.handle_button(key, pressed);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                log::debug!("fetch_events() error on device {}: {:?}", i, e);
-                            }
-                        }
-                    }
-                    pollfd.revents = 0; // Reset for next poll
-                }
-            }
-        })
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_trigger_detection() {
-        let (listener, rx) = InputListener::new(50, 500);
-
-        // Simulate left press
-        listener.handle_button(Key::BTN_LEFT, true);
-
-        // Simulate right press within threshold
-        listener.handle_button(Key::BTN_RIGHT, true);
-
-        // Should receive trigger
-        assert!(rx.try_recv().is_ok());
-    }
-
-    #[test]
-    fn test_debounce() {
-        let (listener, rx) = InputListener::new(50, 1000);
-
-        // First trigger
-        listener.handle_button(Key::BTN_LEFT, true);
-        listener.handle_button(Key::BTN_RIGHT, true);
-
-        assert!(rx.try_recv().is_ok());
-
-        // Release buttons
-        listener.handle_button(Key::BTN_LEFT, false);
-        listener.handle_button(Key::BTN_RIGHT, false);
-
-        // Try to trigger again immediately (should be debounced)
-        listener.handle_button(Key::BTN_LEFT, true);
-        listener.handle_button(Key::BTN_RIGHT, true);
-
-        assert!(rx.try_recv().is_err()); // Should be debounced
-    }
-}
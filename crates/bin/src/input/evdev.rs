@@ -0,0 +1,449 @@
+//! evdev backend: reads `/dev/input/event*` directly (X11 and Wayland)
+//!
+//! Needs read access to the device nodes, usually through the `input` group.
+//! The only backend that supports grab mode and raw multitouch.
+
+use super::{device_thresholds, key_input, BackendOptions, InputBackend, InputSink, Needs};
+use crate::clock;
+use crate::cursor;
+use crate::gesture::{GestureInput, Thresholds};
+use crate::grab::{ChordFilter, Reinjector, VIRTUAL_DEVICE_NAME};
+use crate::hotplug::{DeviceChange, DeviceWatcher, INPUT_DIR};
+use crate::touch::TouchTracker;
+use anyhow::{bail, Result};
+use evdev::{AbsoluteAxisType, Device, InputEventKind, Key, RelativeAxisType};
+use launcher_core::{DeviceIdentity, TriggerConfig};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Axis ranges of an absolute pointing device (tablet, VM pointer)
+#[derive(Debug, Clone, Copy)]
+struct AbsRange {
+    x: (i32, i32),
+    y: (i32, i32),
+}
+
+impl AbsRange {
+    /// Read the ranges of devices that map ABS_X/ABS_Y to the screen
+    ///
+    /// Touchpads also report ABS_X/ABS_Y, but for the finger on the pad.
+    fn of(device: &Device) -> Option<Self> {
+        let axes = device.supported_absolute_axes()?;
+        if !axes.contains(AbsoluteAxisType::ABS_X) || !axes.contains(AbsoluteAxisType::ABS_Y) {
+            return None;
+        }
+        if device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(Key::BTN_TOOL_FINGER))
+        {
+            return None;
+        }
+        let state = device.get_abs_state().ok()?;
+        let range = |axis: AbsoluteAxisType| {
+            let info = state[axis.0 as usize];
+            (info.minimum, info.maximum)
+        };
+        Some(Self {
+            x: range(AbsoluteAxisType::ABS_X),
+            y: range(AbsoluteAxisType::ABS_Y),
+        })
+    }
+
+    /// Position of `value` within `range`, from 0.0 to 1.0
+    fn fraction(range: (i32, i32), value: i32) -> f64 {
+        if range.1 <= range.0 {
+            return 0.0;
+        }
+        (value - range.0) as f64 / (range.1 - range.0) as f64
+    }
+}
+
+/// Kind of input device the listener reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Mouse,
+    Keyboard,
+    Touchscreen,
+}
+
+/// Classify a device: mice support BTN_LEFT, touchscreens BTN_TOUCH,
+/// keyboards have modifier keys
+pub fn device_kind(device: &Device) -> Option<DeviceKind> {
+    // Never listen to our own re-injection device
+    if device.name() == Some(VIRTUAL_DEVICE_NAME) {
+        return None;
+    }
+    let keys = device.supported_keys()?;
+    if keys.contains(Key::BTN_LEFT) {
+        Some(DeviceKind::Mouse)
+    } else if keys.contains(Key::BTN_TOUCH) {
+        Some(DeviceKind::Touchscreen)
+    } else if keys.contains(Key::KEY_LEFTCTRL) {
+        Some(DeviceKind::Keyboard)
+    } else {
+        None
+    }
+}
+
+/// An open device the listener polls
+struct TrackedDevice {
+    path: PathBuf,
+    kind: DeviceKind,
+    device: Device,
+    /// Set for absolute pointing devices
+    abs_range: Option<AbsRange>,
+    /// Set when the device is grabbed (see [`crate::grab`])
+    reinjector: Option<Reinjector>,
+    /// Chord/debounce windows from the matching `trigger.devices` rule
+    thresholds: Thresholds,
+    /// Set for multitouch devices when touch gestures are configured
+    touch: Option<TouchTracker>,
+    /// Events are stamped with the monotonic clock (see [`clock::use_monotonic`])
+    monotonic: bool,
+}
+
+/// Identify a device for matching against `trigger.devices` rules
+pub fn device_identity(device: &Device) -> DeviceIdentity {
+    DeviceIdentity {
+        name: device.name().unwrap_or_default().to_string(),
+        vendor: device.input_id().vendor(),
+        product: device.input_id().product(),
+        phys: device.physical_path().unwrap_or_default().to_string(),
+    }
+}
+
+pub struct EvdevBackend {
+    needs: Needs,
+    rules: TriggerConfig,
+    grab: bool,
+    watcher: Option<DeviceWatcher>,
+    devices: Vec<TrackedDevice>,
+}
+
+impl EvdevBackend {
+    /// Open all readable input devices
+    ///
+    /// Unless `wait_for_devices` is set (and hotplug works), finding no
+    /// devices is an error so the next backend can be tried.
+    pub fn open(options: &BackendOptions, wait_for_devices: bool) -> Result<Self> {
+        // Pick up devices connected later (Bluetooth reconnects, docks);
+        // watch before enumerating so nothing slips through in between
+        let watcher = match DeviceWatcher::new(Path::new(INPUT_DIR)) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::warn!("Device hotplug disabled: {:#}", e);
+                None
+            }
+        };
+
+        let mut backend = Self {
+            needs: options.needs.clone(),
+            rules: options.rules.clone(),
+            grab: options.grab,
+            watcher,
+            devices: Vec::new(),
+        };
+
+        // Keyboards are only needed for keyboard gestures, touchscreens for
+        // touch gestures
+        backend.devices = evdev::enumerate()
+            .filter_map(|(path, device)| backend.track(path, device))
+            .collect();
+
+        let mice = backend
+            .devices
+            .iter()
+            .filter(|d| d.kind == DeviceKind::Mouse)
+            .count();
+        if backend.devices.is_empty() && !(wait_for_devices && backend.watcher.is_some()) {
+            bail!(
+                "No readable input devices. Make sure you have read access to /dev/input/event*. \
+                 Try: sudo usermod -aG input $USER (then log out and back in)"
+            );
+        }
+        if mice == 0 {
+            log::error!("No mouse devices found, waiting for one to be connected");
+        }
+        log::info!("Monitoring {} mouse device(s)", mice);
+
+        // Track the pointer from evdev motion, for hot corners and as a
+        // fallback cursor source
+        if cursor::init_estimate().is_none() && backend.needs.pointer {
+            log::warn!("Could not determine screen size, hot corners disabled");
+        }
+
+        Ok(backend)
+    }
+
+    /// Start tracking a device if it's one we listen to
+    ///
+    /// Keyboards and touchscreens are only tracked when a gesture needs them;
+    /// devices excluded by `trigger.devices` are skipped. Mice are grabbed in
+    /// grab mode; if that fails (no uinput access, not a relative pointer)
+    /// they are left in passive mode.
+    fn track(&self, path: PathBuf, mut device: Device) -> Option<TrackedDevice> {
+        let kind = device_kind(&device)?;
+        match kind {
+            DeviceKind::Keyboard if !self.needs.keyboard => return None,
+            DeviceKind::Touchscreen if !self.needs.touch => return None,
+            _ => {}
+        }
+        let kind_name = match kind {
+            DeviceKind::Mouse => "mouse",
+            DeviceKind::Keyboard => "keyboard",
+            DeviceKind::Touchscreen => "touchscreen",
+        };
+        let thresholds = device_thresholds(&self.rules, &device_identity(&device), kind_name)?;
+
+        let reinjector = if self.grab && kind == DeviceKind::Mouse {
+            let filter = ChordFilter::new(self.needs.hold_keys.clone(), thresholds.simultaneous);
+            match Reinjector::new(&mut device, filter) {
+                Ok(reinjector) => {
+                    log::info!("Grabbed mouse device: {:?}", device.name());
+                    Some(reinjector)
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Not grabbing {:?}, chord clicks will reach applications: {:#}",
+                        device.name(),
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Touchpads are mice that also report multitouch
+        let touch = if self.needs.touch {
+            TouchTracker::of(&device)
+        } else {
+            None
+        };
+
+        let monotonic = clock::use_monotonic(device.as_raw_fd());
+        if !monotonic {
+            log::debug!("{:?} keeps wall-clock event timestamps", device.name());
+        }
+
+        Some(TrackedDevice {
+            path,
+            kind,
+            monotonic,
+            abs_range: AbsRange::of(&device),
+            touch,
+            device,
+            reinjector,
+            thresholds,
+        })
+    }
+
+    /// Read and handle all pending events of a device
+    fn process_events(
+        tracked: &mut TrackedDevice,
+        needs_pointer: bool,
+        sink: &dyn InputSink,
+    ) -> io::Result<()> {
+        for event in tracked.device.fetch_events()? {
+            if tracked.kind != DeviceKind::Keyboard {
+                log::trace!("Raw event: {:?}", event);
+            }
+            let time = clock::event_time(&event, tracked.monotonic);
+            let mut triggered = false;
+            if let Some(frame) = tracked.touch.as_mut().and_then(|t| t.process(&event)) {
+                let input = GestureInput::Touch {
+                    fingers: frame.fingers,
+                    position: frame.position,
+                };
+                sink.input(input, time, Some(tracked.thresholds));
+            }
+            match event.kind() {
+                // Ignore key auto-repeat (value 2)
+                InputEventKind::Key(key) if event.value() != 2 => {
+                    let input = key_input(key, event.value() == 1);
+                    triggered = sink.input(input, time, Some(tracked.thresholds));
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_X) => {
+                    let moved = cursor::move_relative(event.value() as f64, 0.0);
+                    handle_motion(moved, time, needs_pointer, sink);
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_Y) => {
+                    let moved = cursor::move_relative(0.0, event.value() as f64);
+                    handle_motion(moved, time, needs_pointer, sink);
+                }
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_X) => {
+                    if let Some(range) = tracked.abs_range {
+                        let fx = AbsRange::fraction(range.x, event.value());
+                        handle_motion(
+                            cursor::move_absolute(Some(fx), None),
+                            time,
+                            needs_pointer,
+                            sink,
+                        );
+                    }
+                }
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_Y) => {
+                    if let Some(range) = tracked.abs_range {
+                        let fy = AbsRange::fraction(range.y, event.value());
+                        handle_motion(
+                            cursor::move_absolute(None, Some(fy)),
+                            time,
+                            needs_pointer,
+                            sink,
+                        );
+                    }
+                }
+                _ => {}
+            }
+            if let Some(reinjector) = tracked.reinjector.as_mut() {
+                reinjector.process(event, triggered, Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply device additions and removals reported by the watcher
+    fn handle_hotplug(&mut self) {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        for change in watcher.read_changes() {
+            match change {
+                DeviceChange::Added(path) => {
+                    if self.devices.iter().any(|d| d.path == path) {
+                        continue;
+                    }
+                    // Permissions may not be set yet; a later attribute
+                    // change retries the open
+                    match Device::open(&path) {
+                        Ok(device) => {
+                            let tracked = self.track(path, device);
+                            self.devices.extend(tracked);
+                        }
+                        Err(e) => log::debug!("Cannot open {:?} yet: {}", path, e),
+                    }
+                }
+                DeviceChange::Removed(path) => {
+                    self.devices.retain(|d| {
+                        if d.path != path {
+                            return true;
+                        }
+                        log::info!("Device disconnected: {:?}", d.device.name());
+                        false
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl InputBackend for EvdevBackend {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn supports_grab(&self) -> bool {
+        true
+    }
+
+    fn supports_touch(&self) -> bool {
+        true
+    }
+
+    fn run(&mut self, sink: &dyn InputSink) -> Result<()> {
+        loop {
+            // Time-based gestures need frequent ticks
+            // (held-back chord presses also need to be released on time)
+            let grabbing = self.devices.iter().any(|d| d.reinjector.is_some());
+            let timeout = if self.needs.ticks || grabbing {
+                20
+            } else {
+                100
+            };
+
+            // Devices first, then the hotplug watcher
+            let mut pollfds: Vec<libc::pollfd> = self
+                .devices
+                .iter()
+                .map(|d| d.device.as_raw_fd())
+                .chain(self.watcher.as_ref().map(|w| w.as_raw_fd()))
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+
+            // Wait for events on any device
+            let ret =
+                unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout) };
+
+            if self.needs.ticks {
+                sink.tick(clock::now());
+            }
+            if grabbing {
+                let now = Instant::now();
+                for reinjector in self
+                    .devices
+                    .iter_mut()
+                    .filter_map(|d| d.reinjector.as_mut())
+                {
+                    reinjector.expire(now);
+                }
+            }
+
+            if ret <= 0 {
+                continue; // Timeout or error, try again
+            }
+
+            log::debug!("poll() returned {} - events ready", ret);
+
+            // Check which devices have events, collecting unplugged ones
+            let mut dead = Vec::new();
+            for (i, tracked) in self.devices.iter_mut().enumerate() {
+                let revents = pollfds[i].revents;
+                if revents != 0 {
+                    log::debug!("Device {} revents=0x{:x}", i, revents);
+                }
+                if revents & libc::POLLIN != 0 {
+                    match Self::process_events(tracked, self.needs.pointer, sink) {
+                        Ok(()) => {}
+                        Err(e) if e.raw_os_error() == Some(libc::ENODEV) => dead.push(i),
+                        Err(e) => {
+                            log::debug!("fetch_events() error on device {}: {:?}", i, e);
+                        }
+                    }
+                } else if revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+                    dead.push(i);
+                }
+            }
+            for i in dead.into_iter().rev() {
+                let removed = self.devices.remove(i);
+                log::info!("Device disconnected: {:?}", removed.device.name());
+            }
+
+            let hotplug_ready = self.watcher.is_some()
+                && pollfds
+                    .last()
+                    .is_some_and(|p| p.revents & libc::POLLIN != 0);
+            if hotplug_ready {
+                self.handle_hotplug();
+            }
+        }
+    }
+}
+
+/// Feed the updated position estimate to hot corner detection
+fn handle_motion(
+    moved: Option<(f64, f64, (f64, f64))>,
+    time: Duration,
+    needs_pointer: bool,
+    sink: &dyn InputSink,
+) {
+    if let (Some((x, y, screen)), true) = (moved, needs_pointer) {
+        sink.input(GestureInput::PointerMoved { x, y, screen }, time, None);
+    }
+}
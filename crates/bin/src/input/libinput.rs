@@ -0,0 +1,553 @@
+//! libinput backend, loaded at runtime (`libinput.so.10`)
+//!
+//! Uses libinput's device handling on top of `/dev/input`, so it needs the
+//! same permissions as evdev, but gets libinput's touchpad processing: palm
+//! rejection, and swipe/hold gestures instead of raw multitouch frames.
+
+use super::{device_thresholds, key_input, BackendOptions, InputBackend, InputSink, Needs};
+use crate::clock;
+use crate::cursor;
+use crate::gesture::{GestureInput, Thresholds};
+use crate::hotplug::{DeviceChange, DeviceWatcher, INPUT_DIR};
+use anyhow::{bail, Context, Result};
+use evdev::Key;
+use launcher_core::{DeviceIdentity, TriggerConfig};
+use libloading::Library;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const LIBRARY: &str = "libinput.so.10";
+
+/// Nominal touchpad width in libinput's unaccelerated units (1000 DPI),
+/// used to express swipes as a fraction of the pad
+const TOUCHPAD_UNITS: f64 = 4000.0;
+
+/// Resolution for `*_transformed` coordinates, turned into fractions
+const TRANSFORM_UNITS: u32 = 10_000;
+
+// `enum libinput_event_type`
+const DEVICE_ADDED: u32 = 1;
+const DEVICE_REMOVED: u32 = 2;
+const KEYBOARD_KEY: u32 = 300;
+const POINTER_MOTION: u32 = 400;
+const POINTER_MOTION_ABSOLUTE: u32 = 401;
+const POINTER_BUTTON: u32 = 402;
+const TOUCH_DOWN: u32 = 500;
+const TOUCH_UP: u32 = 501;
+const TOUCH_MOTION: u32 = 502;
+const TOUCH_CANCEL: u32 = 503;
+const TOUCH_FRAME: u32 = 504;
+const GESTURE_SWIPE_BEGIN: u32 = 800;
+const GESTURE_SWIPE_UPDATE: u32 = 801;
+const GESTURE_SWIPE_END: u32 = 802;
+const GESTURE_PINCH_BEGIN: u32 = 803;
+const GESTURE_HOLD_BEGIN: u32 = 806;
+const GESTURE_HOLD_END: u32 = 807;
+
+// `enum libinput_device_capability`
+const CAP_KEYBOARD: c_int = 0;
+const CAP_POINTER: c_int = 1;
+const CAP_TOUCH: c_int = 2;
+
+type Ptr = *mut c_void;
+
+/// `struct libinput_interface`
+#[repr(C)]
+struct Interface {
+    open_restricted: extern "C" fn(*const c_char, c_int, Ptr) -> c_int,
+    close_restricted: extern "C" fn(c_int, Ptr),
+}
+
+extern "C" fn open_restricted(path: *const c_char, flags: c_int, _: Ptr) -> c_int {
+    let fd = unsafe { libc::open(path, flags | libc::O_CLOEXEC) };
+    if fd < 0 {
+        -std::io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or(libc::EIO)
+    } else {
+        fd
+    }
+}
+
+extern "C" fn close_restricted(fd: c_int, _: Ptr) {
+    unsafe { libc::close(fd) };
+}
+
+static INTERFACE: Interface = Interface {
+    open_restricted,
+    close_restricted,
+};
+
+/// The libinput functions we use, resolved from the shared library
+struct Api {
+    path_create_context: unsafe extern "C" fn(*const Interface, Ptr) -> Ptr,
+    path_add_device: unsafe extern "C" fn(Ptr, *const c_char) -> Ptr,
+    path_remove_device: unsafe extern "C" fn(Ptr),
+    unref: unsafe extern "C" fn(Ptr) -> Ptr,
+    get_fd: unsafe extern "C" fn(Ptr) -> c_int,
+    dispatch: unsafe extern "C" fn(Ptr) -> c_int,
+    get_event: unsafe extern "C" fn(Ptr) -> Ptr,
+    event_get_type: unsafe extern "C" fn(Ptr) -> u32,
+    event_get_device: unsafe extern "C" fn(Ptr) -> Ptr,
+    event_destroy: unsafe extern "C" fn(Ptr),
+    device_get_name: unsafe extern "C" fn(Ptr) -> *const c_char,
+    device_get_id_vendor: unsafe extern "C" fn(Ptr) -> u32,
+    device_get_id_product: unsafe extern "C" fn(Ptr) -> u32,
+    device_has_capability: unsafe extern "C" fn(Ptr, c_int) -> c_int,
+    event_get_keyboard_event: unsafe extern "C" fn(Ptr) -> Ptr,
+    keyboard_get_key: unsafe extern "C" fn(Ptr) -> u32,
+    keyboard_get_key_state: unsafe extern "C" fn(Ptr) -> u32,
+    keyboard_get_time_usec: unsafe extern "C" fn(Ptr) -> u64,
+    event_get_pointer_event: unsafe extern "C" fn(Ptr) -> Ptr,
+    pointer_get_button: unsafe extern "C" fn(Ptr) -> u32,
+    pointer_get_button_state: unsafe extern "C" fn(Ptr) -> u32,
+    pointer_get_time_usec: unsafe extern "C" fn(Ptr) -> u64,
+    pointer_get_dx: unsafe extern "C" fn(Ptr) -> f64,
+    pointer_get_dy: unsafe extern "C" fn(Ptr) -> f64,
+    pointer_get_absolute_x_transformed: unsafe extern "C" fn(Ptr, u32) -> f64,
+    pointer_get_absolute_y_transformed: unsafe extern "C" fn(Ptr, u32) -> f64,
+    event_get_touch_event: unsafe extern "C" fn(Ptr) -> Ptr,
+    touch_get_seat_slot: unsafe extern "C" fn(Ptr) -> i32,
+    touch_get_time_usec: unsafe extern "C" fn(Ptr) -> u64,
+    touch_get_x_transformed: unsafe extern "C" fn(Ptr, u32) -> f64,
+    touch_get_y_transformed: unsafe extern "C" fn(Ptr, u32) -> f64,
+    event_get_gesture_event: unsafe extern "C" fn(Ptr) -> Ptr,
+    gesture_get_finger_count: unsafe extern "C" fn(Ptr) -> c_int,
+    gesture_get_cancelled: unsafe extern "C" fn(Ptr) -> c_int,
+    gesture_get_time_usec: unsafe extern "C" fn(Ptr) -> u64,
+    gesture_get_dx_unaccelerated: unsafe extern "C" fn(Ptr) -> f64,
+    gesture_get_dy_unaccelerated: unsafe extern "C" fn(Ptr) -> f64,
+    /// Keeps the function pointers above valid
+    _library: Library,
+}
+
+impl Api {
+    fn load() -> Result<Self> {
+        let library =
+            unsafe { Library::new(LIBRARY) }.with_context(|| format!("Cannot load {}", LIBRARY))?;
+        macro_rules! sym {
+            ($name:literal) => {
+                *unsafe { library.get(concat!("libinput_", $name, "\0").as_bytes()) }
+                    .with_context(|| format!("{} lacks libinput_{}", LIBRARY, $name))?
+            };
+        }
+        Ok(Self {
+            path_create_context: sym!("path_create_context"),
+            path_add_device: sym!("path_add_device"),
+            path_remove_device: sym!("path_remove_device"),
+            unref: sym!("unref"),
+            get_fd: sym!("get_fd"),
+            dispatch: sym!("dispatch"),
+            get_event: sym!("get_event"),
+            event_get_type: sym!("event_get_type"),
+            event_get_device: sym!("event_get_device"),
+            event_destroy: sym!("event_destroy"),
+            device_get_name: sym!("device_get_name"),
+            device_get_id_vendor: sym!("device_get_id_vendor"),
+            device_get_id_product: sym!("device_get_id_product"),
+            device_has_capability: sym!("device_has_capability"),
+            event_get_keyboard_event: sym!("event_get_keyboard_event"),
+            keyboard_get_key: sym!("event_keyboard_get_key"),
+            keyboard_get_key_state: sym!("event_keyboard_get_key_state"),
+            keyboard_get_time_usec: sym!("event_keyboard_get_time_usec"),
+            event_get_pointer_event: sym!("event_get_pointer_event"),
+            pointer_get_button: sym!("event_pointer_get_button"),
+            pointer_get_button_state: sym!("event_pointer_get_button_state"),
+            pointer_get_time_usec: sym!("event_pointer_get_time_usec"),
+            pointer_get_dx: sym!("event_pointer_get_dx"),
+            pointer_get_dy: sym!("event_pointer_get_dy"),
+            pointer_get_absolute_x_transformed: sym!("event_pointer_get_absolute_x_transformed"),
+            pointer_get_absolute_y_transformed: sym!("event_pointer_get_absolute_y_transformed"),
+            event_get_touch_event: sym!("event_get_touch_event"),
+            touch_get_seat_slot: sym!("event_touch_get_seat_slot"),
+            touch_get_time_usec: sym!("event_touch_get_time_usec"),
+            touch_get_x_transformed: sym!("event_touch_get_x_transformed"),
+            touch_get_y_transformed: sym!("event_touch_get_y_transformed"),
+            event_get_gesture_event: sym!("event_get_gesture_event"),
+            gesture_get_finger_count: sym!("event_gesture_get_finger_count"),
+            gesture_get_cancelled: sym!("event_gesture_get_cancelled"),
+            gesture_get_time_usec: sym!("event_gesture_get_time_usec"),
+            gesture_get_dx_unaccelerated: sym!("event_gesture_get_dx_unaccelerated"),
+            gesture_get_dy_unaccelerated: sym!("event_gesture_get_dy_unaccelerated"),
+            _library: library,
+        })
+    }
+}
+
+pub struct LibinputBackend {
+    api: Api,
+    context: Ptr,
+    needs: Needs,
+    rules: TriggerConfig,
+    watcher: Option<DeviceWatcher>,
+    /// Device nodes handed to libinput
+    paths: HashSet<PathBuf>,
+    /// Thresholds per libinput device handle
+    devices: HashMap<usize, Thresholds>,
+    /// Touchscreen contacts by seat slot, as fractions of the screen
+    contacts: HashMap<i32, (f64, f64)>,
+    /// Touchpad gesture: finger count and accumulated motion
+    gesture: (u8, (f64, f64)),
+}
+
+impl LibinputBackend {
+    pub fn open(options: &BackendOptions) -> Result<Self> {
+        let api = Api::load()?;
+        let context = unsafe { (api.path_create_context)(&INTERFACE, std::ptr::null_mut()) };
+        if context.is_null() {
+            bail!("Failed to create a libinput context");
+        }
+
+        let watcher = match DeviceWatcher::new(Path::new(INPUT_DIR)) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::warn!("Device hotplug disabled: {:#}", e);
+                None
+            }
+        };
+        let mut backend = Self {
+            api,
+            context,
+            needs: options.needs.clone(),
+            rules: options.rules.clone(),
+            watcher,
+            paths: HashSet::new(),
+            devices: HashMap::new(),
+            contacts: HashMap::new(),
+            gesture: (0, (0.0, 0.0)),
+        };
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(INPUT_DIR)
+            .with_context(|| format!("Cannot read {}", INPUT_DIR))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.as_bytes().starts_with(b"event"))
+            })
+            .collect();
+        paths.sort();
+        let added = paths
+            .into_iter()
+            .filter(|path| backend.add_device(path.clone()))
+            .count();
+        if added == 0 {
+            bail!(
+                "No readable input devices. Make sure you have read access to /dev/input/event*. \
+                 Try: sudo usermod -aG input $USER (then log out and back in)"
+            );
+        }
+
+        if cursor::init_estimate().is_none() && backend.needs.pointer {
+            log::warn!("Could not determine screen size, hot corners disabled");
+        }
+        Ok(backend)
+    }
+
+    /// Hand a device node to libinput, once
+    fn add_device(&mut self, path: PathBuf) -> bool {
+        if self.paths.contains(&path) {
+            return false;
+        }
+        let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let device = unsafe { (self.api.path_add_device)(self.context, c_path.as_ptr()) };
+        if device.is_null() {
+            // Permissions may not be set yet; a later attribute change retries
+            return false;
+        }
+        self.paths.insert(path);
+        true
+    }
+
+    /// Apply `trigger.devices` rules to a newly added device
+    fn device_added(&mut self, device: Ptr) {
+        let api = &self.api;
+        let has = |cap| unsafe { (api.device_has_capability)(device, cap) } != 0;
+        let kind_name = if has(CAP_TOUCH) && !has(CAP_POINTER) {
+            "touchscreen"
+        } else if has(CAP_POINTER) {
+            "mouse"
+        } else if has(CAP_KEYBOARD) {
+            "keyboard"
+        } else {
+            ""
+        };
+        let wanted = match kind_name {
+            "mouse" => true,
+            "keyboard" => self.needs.keyboard,
+            "touchscreen" => self.needs.touch,
+            _ => false,
+        };
+
+        let identity = unsafe {
+            let name = (api.device_get_name)(device);
+            DeviceIdentity {
+                name: if name.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(name).to_string_lossy().into_owned()
+                },
+                vendor: (api.device_get_id_vendor)(device) as u16,
+                product: (api.device_get_id_product)(device) as u16,
+                // libinput doesn't expose the phys path
+                phys: String::new(),
+            }
+        };
+        let thresholds = if wanted {
+            device_thresholds(&self.rules, &identity, kind_name)
+        } else {
+            None
+        };
+        match thresholds {
+            Some(thresholds) => {
+                self.devices.insert(device as usize, thresholds);
+            }
+            None => unsafe { (api.path_remove_device)(device) },
+        }
+    }
+
+    fn handle_event(&mut self, event: Ptr, sink: &dyn InputSink) {
+        let api = &self.api;
+        let kind = unsafe { (api.event_get_type)(event) };
+        let device = unsafe { (api.event_get_device)(event) };
+        match kind {
+            DEVICE_ADDED => return self.device_added(device),
+            DEVICE_REMOVED => {
+                if self.devices.remove(&(device as usize)).is_some() {
+                    log::info!("Device disconnected");
+                }
+                return;
+            }
+            _ => {}
+        }
+        let Some(&thresholds) = self.devices.get(&(device as usize)) else {
+            return;
+        };
+        let thresholds = Some(thresholds);
+        let time = self.event_time(kind, event);
+
+        match kind {
+            KEYBOARD_KEY => {
+                let (key, state) = unsafe {
+                    let keyboard = (api.event_get_keyboard_event)(event);
+                    (
+                        (api.keyboard_get_key)(keyboard),
+                        (api.keyboard_get_key_state)(keyboard),
+                    )
+                };
+                sink.input(
+                    key_input(Key::new(key as u16), state == 1),
+                    time,
+                    thresholds,
+                );
+            }
+            POINTER_BUTTON => {
+                let (button, state) = unsafe {
+                    let pointer = (api.event_get_pointer_event)(event);
+                    (
+                        (api.pointer_get_button)(pointer),
+                        (api.pointer_get_button_state)(pointer),
+                    )
+                };
+                sink.input(
+                    key_input(Key::new(button as u16), state == 1),
+                    time,
+                    thresholds,
+                );
+            }
+            POINTER_MOTION if self.needs.pointer => {
+                let (dx, dy) = unsafe {
+                    let pointer = (api.event_get_pointer_event)(event);
+                    ((api.pointer_get_dx)(pointer), (api.pointer_get_dy)(pointer))
+                };
+                pointer_moved(cursor::move_relative(dx, dy), time, sink);
+            }
+            POINTER_MOTION_ABSOLUTE if self.needs.pointer => {
+                let (fx, fy) = unsafe {
+                    let pointer = (api.event_get_pointer_event)(event);
+                    (
+                        (api.pointer_get_absolute_x_transformed)(pointer, TRANSFORM_UNITS),
+                        (api.pointer_get_absolute_y_transformed)(pointer, TRANSFORM_UNITS),
+                    )
+                };
+                let units = TRANSFORM_UNITS as f64;
+                pointer_moved(
+                    cursor::move_absolute(Some(fx / units), Some(fy / units)),
+                    time,
+                    sink,
+                );
+            }
+            TOUCH_DOWN | TOUCH_MOTION | TOUCH_UP => {
+                let touch = unsafe { (api.event_get_touch_event)(event) };
+                let slot = unsafe { (api.touch_get_seat_slot)(touch) };
+                if kind == TOUCH_UP {
+                    self.contacts.remove(&slot);
+                } else {
+                    let units = TRANSFORM_UNITS as f64;
+                    let position = unsafe {
+                        (
+                            (api.touch_get_x_transformed)(touch, TRANSFORM_UNITS) / units,
+                            (api.touch_get_y_transformed)(touch, TRANSFORM_UNITS) / units,
+                        )
+                    };
+                    self.contacts.insert(slot, position);
+                }
+            }
+            TOUCH_FRAME => {
+                let n = self.contacts.len() as f64;
+                let position = if n > 0.0 {
+                    let (x, y) = self
+                        .contacts
+                        .values()
+                        .fold((0.0, 0.0), |(x, y), p| (x + p.0, y + p.1));
+                    (x / n, y / n)
+                } else {
+                    (0.0, 0.0)
+                };
+                let fingers = self.contacts.len() as u8;
+                sink.input(GestureInput::Touch { fingers, position }, time, thresholds);
+            }
+            TOUCH_CANCEL => {
+                self.contacts.clear();
+                sink.input(GestureInput::TouchCancel, time, thresholds);
+            }
+            GESTURE_SWIPE_BEGIN | GESTURE_HOLD_BEGIN => {
+                let gesture = unsafe { (api.event_get_gesture_event)(event) };
+                let fingers = unsafe { (api.gesture_get_finger_count)(gesture) };
+                self.gesture = (fingers.clamp(0, 10) as u8, (0.0, 0.0));
+                sink.input(self.touchpad_frame(self.gesture.0), time, thresholds);
+            }
+            GESTURE_SWIPE_UPDATE => {
+                let (dx, dy) = unsafe {
+                    let gesture = (api.event_get_gesture_event)(event);
+                    (
+                        (api.gesture_get_dx_unaccelerated)(gesture),
+                        (api.gesture_get_dy_unaccelerated)(gesture),
+                    )
+                };
+                let (fingers, (x, y)) = self.gesture;
+                self.gesture = (fingers, (x + dx, y + dy));
+                sink.input(self.touchpad_frame(fingers), time, thresholds);
+            }
+            GESTURE_SWIPE_END | GESTURE_HOLD_END => {
+                let gesture = unsafe { (api.event_get_gesture_event)(event) };
+                // A cancelled hold turns into a swipe, scroll or pointer motion
+                if unsafe { (api.gesture_get_cancelled)(gesture) } != 0 {
+                    sink.input(GestureInput::TouchCancel, time, thresholds);
+                } else {
+                    sink.input(self.touchpad_frame(0), time, thresholds);
+                }
+            }
+            GESTURE_PINCH_BEGIN => {
+                sink.input(GestureInput::TouchCancel, time, thresholds);
+            }
+            _ => {}
+        }
+    }
+
+    /// Event timestamp (libinput uses the monotonic clock)
+    fn event_time(&self, kind: u32, event: Ptr) -> Duration {
+        let api = &self.api;
+        let usec = unsafe {
+            match kind {
+                KEYBOARD_KEY => (api.keyboard_get_time_usec)((api.event_get_keyboard_event)(event)),
+                POINTER_MOTION..=POINTER_BUTTON => {
+                    (api.pointer_get_time_usec)((api.event_get_pointer_event)(event))
+                }
+                TOUCH_DOWN..=TOUCH_FRAME => {
+                    (api.touch_get_time_usec)((api.event_get_touch_event)(event))
+                }
+                GESTURE_SWIPE_BEGIN..=GESTURE_HOLD_END => {
+                    (api.gesture_get_time_usec)((api.event_get_gesture_event)(event))
+                }
+                _ => return clock::now(),
+            }
+        };
+        Duration::from_micros(usec)
+    }
+
+    /// Touchpad gesture state as a touch frame centered on the pad
+    fn touchpad_frame(&self, fingers: u8) -> GestureInput {
+        let (dx, dy) = self.gesture.1;
+        GestureInput::Touch {
+            fingers,
+            position: (0.5 + dx / TOUCHPAD_UNITS, 0.5 + dy / TOUCHPAD_UNITS),
+        }
+    }
+}
+
+impl InputBackend for LibinputBackend {
+    fn name(&self) -> &'static str {
+        "libinput"
+    }
+
+    fn supports_touch(&self) -> bool {
+        true
+    }
+
+    fn run(&mut self, sink: &dyn InputSink) -> Result<()> {
+        let timeout = if self.needs.ticks { 20 } else { 100 };
+        let fd = unsafe { (self.api.get_fd)(self.context) };
+        loop {
+            let mut pollfds: Vec<libc::pollfd> = std::iter::once(fd)
+                .chain(self.watcher.as_ref().map(|w| w.as_raw_fd()))
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout) };
+
+            if unsafe { (self.api.dispatch)(self.context) } < 0 {
+                bail!("libinput_dispatch failed");
+            }
+            loop {
+                let event = unsafe { (self.api.get_event)(self.context) };
+                if event.is_null() {
+                    break;
+                }
+                self.handle_event(event, sink);
+                unsafe { (self.api.event_destroy)(event) };
+            }
+
+            if self.needs.ticks {
+                sink.tick(clock::now());
+            }
+
+            // libinput reports removed devices itself
+            let hotplug_ready = pollfds
+                .get(1)
+                .is_some_and(|p| p.revents & libc::POLLIN != 0);
+            if let Some(watcher) = self.watcher.as_mut().filter(|_| hotplug_ready) {
+                for change in watcher.read_changes() {
+                    match change {
+                        DeviceChange::Added(path) => {
+                            self.add_device(path);
+                        }
+                        DeviceChange::Removed(path) => {
+                            self.paths.remove(&path);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LibinputBackend {
+    fn drop(&mut self) {
+        unsafe { (self.api.unref)(self.context) };
+    }
+}
+
+fn pointer_moved(moved: Option<(f64, f64, (f64, f64))>, time: Duration, sink: &dyn InputSink) {
+    if let Some((x, y, screen)) = moved {
+        sink.input(GestureInput::PointerMoved { x, y, screen }, time, None);
+    }
+}
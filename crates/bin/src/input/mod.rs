@@ -0,0 +1,547 @@
+//! Input listener with pluggable backends (evdev, libinput, X11 XInput2)
+//!
+//! Backends turn device events into [`GestureInput`] and feed them to an
+//! [`InputSink`]; the [`InputListener`] recognizes gestures and sends
+//! triggers. With `trigger.backend` set to `auto`, backends are tried in the
+//! order of [`FALLBACK_CHAIN`].
+
+mod evdev;
+mod libinput;
+mod x11;
+
+pub use self::evdev::{device_identity, device_kind};
+
+use crate::gesture::{GestureInput, GestureRecognizer, Thresholds};
+use ::evdev::Key;
+use anyhow::Result;
+use launcher_core::{
+    DeviceIdentity, DeviceRule, GestureConfig, InputBackendKind, Modifier, MouseButton,
+    TriggerConfig,
+};
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Backends tried by `auto`: evdev and libinput need the `input` group,
+/// X11 works without it but only sees X11/XWayland input
+pub const FALLBACK_CHAIN: [InputBackendKind; 3] = [
+    InputBackendKind::Evdev,
+    InputBackendKind::Libinput,
+    InputBackendKind::X11,
+];
+
+/// Trigger event sent when a configured gesture is detected
+#[derive(Debug, Clone)]
+pub struct TriggerEvent {
+    /// Where to open the popup; `None` for the cursor position, looked up by
+    /// the receiver (off the input thread, which must never wait on it)
+    pub position: Option<(f64, f64)>,
+    /// Timestamp
+    pub timestamp: Instant,
+    /// Popup profile requested by the gesture
+    pub profile: Option<String>,
+}
+
+/// What the configured gestures need from a backend
+#[derive(Debug, Clone, Default)]
+pub struct Needs {
+    pub keyboard: bool,
+    pub pointer: bool,
+    pub touch: bool,
+    /// Call [`InputSink::tick`] frequently
+    pub ticks: bool,
+    /// Chord buttons, held back in grab mode
+    pub hold_keys: HashSet<Key>,
+}
+
+/// Settings shared by all backends
+pub struct BackendOptions {
+    pub needs: Needs,
+    /// Default thresholds plus per-device rules
+    pub rules: TriggerConfig,
+    /// Grab mice and re-emit their events minus chord clicks
+    pub grab: bool,
+}
+
+/// Receives normalized input from a backend
+pub trait InputSink {
+    /// Feed an event, returning whether it triggered the popup
+    ///
+    /// `time` is the event's own timestamp on the [`crate::clock`] (for
+    /// evdev, `input_event.time`), not the time it was read. `thresholds`
+    /// overrides the defaults for a device with its own rule.
+    fn input(&self, input: GestureInput, time: Duration, thresholds: Option<Thresholds>) -> bool;
+
+    /// Check time-based gestures (long press, hot corner dwell) at `now`
+    fn tick(&self, now: Duration);
+}
+
+/// A source of input events
+pub trait InputBackend {
+    fn name(&self) -> &'static str;
+
+    /// Whether chord clicks can be kept from reaching applications
+    fn supports_grab(&self) -> bool {
+        false
+    }
+
+    /// Whether touch gestures are recognized
+    fn supports_touch(&self) -> bool {
+        false
+    }
+
+    /// Read events and feed them to `sink` until the backend fails
+    fn run(&mut self, sink: &dyn InputSink) -> Result<()>;
+}
+
+/// Open a specific backend
+///
+/// `explicit` is set when the user picked the backend, so evdev waits for
+/// devices to be plugged in instead of failing.
+fn open_backend(
+    kind: InputBackendKind,
+    options: &BackendOptions,
+    explicit: bool,
+) -> Result<Box<dyn InputBackend>> {
+    Ok(match kind {
+        InputBackendKind::Auto | InputBackendKind::Evdev => {
+            Box::new(evdev::EvdevBackend::open(options, explicit)?)
+        }
+        InputBackendKind::Libinput => Box::new(libinput::LibinputBackend::open(options)?),
+        InputBackendKind::X11 => Box::new(x11::X11Backend::open(options)?),
+    })
+}
+
+/// Open the configured backend, or the first working one of the chain
+pub fn select_backend(
+    kind: InputBackendKind,
+    options: &BackendOptions,
+) -> Option<Box<dyn InputBackend>> {
+    let (candidates, explicit) = match kind {
+        InputBackendKind::Auto => (&FALLBACK_CHAIN[..], false),
+        _ => (std::slice::from_ref(&kind), true),
+    };
+    for &candidate in candidates {
+        match open_backend(candidate, options, explicit) {
+            Ok(backend) => {
+                log::info!("Using {} input backend", backend.name());
+                if options.grab && !backend.supports_grab() {
+                    log::warn!(
+                        "grab_devices needs the evdev backend, chord clicks will reach applications"
+                    );
+                }
+                if options.needs.touch && !backend.supports_touch() {
+                    log::warn!(
+                        "Touch gestures are not supported by the {} backend",
+                        backend.name()
+                    );
+                }
+                return Some(backend);
+            }
+            Err(e) => log::warn!("{:?} input backend unavailable: {:#}", candidate, e),
+        }
+    }
+    None
+}
+
+/// Apply `trigger.devices` rules to a device, logging the outcome
+///
+/// Returns `None` for excluded devices.
+fn device_thresholds(
+    rules: &TriggerConfig,
+    identity: &DeviceIdentity,
+    kind_name: &str,
+) -> Option<Thresholds> {
+    let Some((simultaneous_ms, debounce_ms)) = rules.device_thresholds(identity) else {
+        log::info!(
+            "Ignoring {} device {:?} (excluded by trigger.devices)",
+            kind_name,
+            identity.name
+        );
+        return None;
+    };
+    log::info!(
+        "Found {} device: {:?} ({:04x}:{:04x}, phys {:?})",
+        kind_name,
+        identity.name,
+        identity.vendor,
+        identity.product,
+        identity.phys
+    );
+    Some(Thresholds {
+        simultaneous: Duration::from_millis(simultaneous_ms),
+        debounce: Duration::from_millis(debounce_ms),
+    })
+}
+
+/// Normalize a button or key event (evdev codes) for the recognizer
+pub fn key_input(key: Key, pressed: bool) -> GestureInput {
+    if let Some(button) = button_from_key(key) {
+        log::debug!("Button event: {:?} pressed={}", key, pressed);
+        GestureInput::Button { button, pressed }
+    } else if let Some(modifier) = modifier_from_key(key) {
+        GestureInput::Modifier { modifier, pressed }
+    } else {
+        // Not logged: keyboards may be read for hotkeys
+        GestureInput::Key {
+            code: key.code(),
+            pressed,
+        }
+    }
+}
+
+/// Map an evdev button code to a configurable mouse button
+pub fn button_from_key(key: Key) -> Option<MouseButton> {
+    match key {
+        Key::BTN_LEFT => Some(MouseButton::Left),
+        Key::BTN_RIGHT => Some(MouseButton::Right),
+        Key::BTN_MIDDLE => Some(MouseButton::Middle),
+        Key::BTN_SIDE => Some(MouseButton::Side),
+        Key::BTN_EXTRA => Some(MouseButton::Extra),
+        Key::BTN_FORWARD => Some(MouseButton::Forward),
+        Key::BTN_BACK => Some(MouseButton::Back),
+        _ => None,
+    }
+}
+
+/// Map a configurable mouse button to its evdev button code
+fn key_from_button(button: MouseButton) -> Key {
+    match button {
+        MouseButton::Left => Key::BTN_LEFT,
+        MouseButton::Right => Key::BTN_RIGHT,
+        MouseButton::Middle => Key::BTN_MIDDLE,
+        MouseButton::Side => Key::BTN_SIDE,
+        MouseButton::Extra => Key::BTN_EXTRA,
+        MouseButton::Forward => Key::BTN_FORWARD,
+        MouseButton::Back => Key::BTN_BACK,
+    }
+}
+
+/// Map an evdev key code to a keyboard modifier
+pub fn modifier_from_key(key: Key) -> Option<Modifier> {
+    match key {
+        Key::KEY_LEFTCTRL | Key::KEY_RIGHTCTRL => Some(Modifier::Ctrl),
+        Key::KEY_LEFTSHIFT | Key::KEY_RIGHTSHIFT => Some(Modifier::Shift),
+        Key::KEY_LEFTALT | Key::KEY_RIGHTALT => Some(Modifier::Alt),
+        Key::KEY_LEFTMETA | Key::KEY_RIGHTMETA => Some(Modifier::Super),
+        _ => None,
+    }
+}
+
+/// Input listener that detects configured trigger gestures (L+R click by default)
+pub struct InputListener {
+    recognizer: Mutex<GestureRecognizer>,
+    simultaneous_threshold: Duration,
+    debounce_duration: Duration,
+    /// Grab mice and re-emit their events minus chord clicks
+    grab: bool,
+    /// Default thresholds plus per-device rules
+    devices: TriggerConfig,
+    backend: InputBackendKind,
+    trigger_tx: Sender<TriggerEvent>,
+}
+
+impl InputListener {
+    /// Create a new input listener for the default L+R chord
+    ///
+    /// # Arguments
+    /// * `simultaneous_threshold_ms` - Maximum time between L and R clicks to count as simultaneous
+    /// * `debounce_ms` - Minimum time between triggers to prevent accidental double-triggers
+    pub fn new(simultaneous_threshold_ms: u64, debounce_ms: u64) -> (Self, Receiver<TriggerEvent>) {
+        let (trigger_tx, trigger_rx) = channel();
+        let simultaneous_threshold = Duration::from_millis(simultaneous_threshold_ms);
+        let debounce_duration = Duration::from_millis(debounce_ms);
+
+        let listener = Self {
+            recognizer: Mutex::new(GestureRecognizer::new(
+                TriggerConfig::default().gestures,
+                simultaneous_threshold,
+                debounce_duration,
+            )),
+            simultaneous_threshold,
+            debounce_duration,
+            grab: false,
+            devices: TriggerConfig {
+                simultaneous_threshold_ms,
+                debounce_ms,
+                ..TriggerConfig::default()
+            },
+            backend: InputBackendKind::Auto,
+            trigger_tx,
+        };
+
+        (listener, trigger_rx)
+    }
+
+    /// Replace the default L+R chord with the configured gestures
+    pub fn with_gestures(self, gestures: Vec<GestureConfig>) -> Self {
+        Self {
+            recognizer: Mutex::new(GestureRecognizer::new(
+                gestures,
+                self.simultaneous_threshold,
+                self.debounce_duration,
+            )),
+            ..self
+        }
+    }
+
+    /// Enable grab-and-reinject mode (see [`crate::grab`])
+    pub fn with_grab(self, grab: bool) -> Self {
+        Self { grab, ..self }
+    }
+
+    /// Apply per-device rules (include/exclude and threshold overrides)
+    pub fn with_devices(mut self, rules: Vec<DeviceRule>, only_listed_devices: bool) -> Self {
+        self.devices.devices = rules;
+        self.devices.only_listed_devices = only_listed_devices;
+        self
+    }
+
+    /// Pick the input backend (`auto` tries [`FALLBACK_CHAIN`])
+    pub fn with_backend(self, backend: InputBackendKind) -> Self {
+        Self { backend, ..self }
+    }
+
+    /// Get a sender for injecting triggers from other sources (e.g. D-Bus `Show`)
+    pub fn trigger_sender(&self) -> Sender<TriggerEvent> {
+        self.trigger_tx.clone()
+    }
+
+    /// Send a trigger event for the cursor position
+    ///
+    /// The position is left to the receiver: compositor queries can take
+    /// hundreds of milliseconds, and grabbed devices' events wait for this.
+    fn trigger(&self, profile: Option<String>) {
+        log::info!("TRIGGER DETECTED! (profile: {:?})", profile);
+        let _ = self.trigger_tx.send(TriggerEvent {
+            position: None,
+            timestamp: Instant::now(),
+            profile,
+        });
+    }
+
+    /// What the configured gestures need from a backend
+    fn needs(&self) -> Needs {
+        match self.recognizer.lock() {
+            Ok(r) => Needs {
+                keyboard: r.needs_keyboard(),
+                pointer: r.needs_pointer(),
+                touch: r.needs_touch(),
+                ticks: r.needs_ticks(),
+                hold_keys: r.chord_buttons().into_iter().map(key_from_button).collect(),
+            },
+            Err(_) => Needs::default(),
+        }
+    }
+
+    /// Start listening for input events
+    ///
+    /// This spawns a background thread that processes events and returns immediately.
+    /// The thread will run until the process exits.
+    ///
+    /// Note: the evdev and libinput backends need read access to
+    /// /dev/input/event* devices. The user typically needs to be in the
+    /// 'input' group: sudo usermod -aG input $USER
+    pub fn start(self) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            log::info!("Starting input listener...");
+
+            let options = BackendOptions {
+                needs: self.needs(),
+                rules: self.devices.clone(),
+                grab: self.grab,
+            };
+            let Some(mut backend) = select_backend(self.backend, &options) else {
+                log::error!("No input backend available, gestures are disabled");
+                return;
+            };
+            if let Err(e) = backend.run(&self) {
+                log::error!("{} input backend failed: {:#}", backend.name(), e);
+            }
+        })
+    }
+}
+
+impl InputSink for InputListener {
+    fn input(&self, input: GestureInput, time: Duration, thresholds: Option<Thresholds>) -> bool {
+        let profile = match self.recognizer.lock() {
+            Ok(mut recognizer) => recognizer
+                .handle_with(input, time, thresholds)
+                .map(|g| g.profile.clone()),
+            Err(_) => None,
+        };
+        match profile {
+            Some(profile) => {
+                self.trigger(profile);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn tick(&self, now: Duration) {
+        let profile = match self.recognizer.lock() {
+            Ok(mut recognizer) => recognizer.tick(now).map(|g| g.profile.clone()),
+            Err(_) => None,
+        };
+        if let Some(profile) = profile {
+            self.trigger(profile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::evdev::Key;
+
+    fn button(listener: &InputListener, key: Key, pressed: bool, at_ms: u64) -> bool {
+        listener.input(key_input(key, pressed), Duration::from_millis(at_ms), None)
+    }
+
+    #[test]
+    fn test_trigger_detection() {
+        let (listener, rx) = InputListener::new(50, 500);
+
+        // Simulate left press
+        button(&listener, Key::BTN_LEFT, true, 1000);
+
+        // Simulate right press within threshold
+        button(&listener, Key::BTN_RIGHT, true, 1030);
+
+        // Should receive trigger, with the cursor left to the receiver
+        assert_eq!(rx.try_recv().unwrap().position, None);
+    }
+
+    #[test]
+    fn test_debounce() {
+        let (listener, rx) = InputListener::new(50, 1000);
+
+        // First trigger
+        button(&listener, Key::BTN_LEFT, true, 1000);
+        button(&listener, Key::BTN_RIGHT, true, 1010);
+
+        assert!(rx.try_recv().is_ok());
+
+        // Release buttons
+        button(&listener, Key::BTN_LEFT, false, 1100);
+        button(&listener, Key::BTN_RIGHT, false, 1110);
+
+        // Try to trigger again soon after (should be debounced)
+        button(&listener, Key::BTN_LEFT, true, 1500);
+        button(&listener, Key::BTN_RIGHT, true, 1510);
+
+        assert!(rx.try_recv().is_err()); // Should be debounced
+
+        // Once the debounce window has passed
+        button(&listener, Key::BTN_LEFT, false, 1600);
+        button(&listener, Key::BTN_RIGHT, false, 1600);
+        button(&listener, Key::BTN_LEFT, true, 2010);
+        button(&listener, Key::BTN_RIGHT, true, 2020);
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_configured_gesture_profile() {
+        let (listener, rx) = InputListener::new(50, 500);
+        let listener = listener.with_gestures(vec![GestureConfig {
+            gesture: launcher_core::Gesture::Chord {
+                buttons: vec![MouseButton::Middle, MouseButton::Right],
+            },
+            profile: Some("work".to_string()),
+        }]);
+
+        // L+R is no longer a trigger
+        button(&listener, Key::BTN_LEFT, true, 1000);
+        button(&listener, Key::BTN_RIGHT, true, 1010);
+        assert!(rx.try_recv().is_err());
+
+        button(&listener, Key::BTN_RIGHT, false, 1100);
+        button(&listener, Key::BTN_MIDDLE, true, 1200);
+        button(&listener, Key::BTN_RIGHT, true, 1210);
+        assert_eq!(rx.try_recv().unwrap().profile.as_deref(), Some("work"));
+    }
+
+    /// Replays timestamped inputs, like a device that sent them
+    struct ScriptedBackend(Vec<(GestureInput, u64)>);
+
+    impl InputBackend for ScriptedBackend {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn run(&mut self, sink: &dyn InputSink) -> Result<()> {
+            for (input, at_ms) in self.0.drain(..) {
+                sink.input(input, Duration::from_millis(at_ms), None);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_backend_feeds_listener() {
+        let (listener, rx) = InputListener::new(50, 500);
+        let mut backend: Box<dyn InputBackend> = Box::new(ScriptedBackend(vec![
+            (key_input(Key::BTN_LEFT, true), 1000),
+            (key_input(Key::KEY_A, true), 1005),
+            (key_input(Key::BTN_RIGHT, true), 1010),
+        ]));
+        backend.run(&listener).unwrap();
+        assert!(rx.try_recv().is_ok());
+        assert!(!backend.supports_grab());
+    }
+
+    /// Clicks a virtual mouse in the live session and leaves a listener
+    /// thread running, so it only runs on request:
+    /// `cargo test -- --ignored test_hotplugged_uinput_mouse`
+    #[test]
+    #[ignore = "injects real clicks through /dev/uinput"]
+    fn test_hotplugged_uinput_mouse() {
+        use ::evdev::uinput::VirtualDeviceBuilder;
+        use ::evdev::{AttributeSet, EventType, InputEvent};
+
+        let Ok(builder) = VirtualDeviceBuilder::new() else {
+            eprintln!("Skipping: /dev/uinput not available");
+            return;
+        };
+        let (listener, rx) = InputListener::new(50, 0);
+        // Explicit evdev waits for devices instead of falling back
+        listener.with_backend(InputBackendKind::Evdev).start();
+
+        // Connected after the listener started, so only hotplug can find it
+        let mut keys = AttributeSet::<Key>::new();
+        keys.insert(Key::BTN_LEFT);
+        keys.insert(Key::BTN_RIGHT);
+        let mut mouse = builder
+            .name("launcher hotplug test mouse")
+            .with_keys(&keys)
+            .unwrap()
+            .build()
+            .unwrap();
+        let readable = mouse
+            .enumerate_dev_nodes_blocking()
+            .unwrap()
+            .filter_map(|node| node.ok())
+            .any(|node| std::fs::File::open(node).is_ok());
+        if !readable {
+            eprintln!("Skipping: the virtual mouse's node in /dev/input is not readable");
+            return;
+        }
+
+        let click = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        for _ in 0..20 {
+            mouse
+                .emit(&[click(Key::BTN_LEFT, 1), click(Key::BTN_RIGHT, 1)])
+                .unwrap();
+            mouse
+                .emit(&[click(Key::BTN_LEFT, 0), click(Key::BTN_RIGHT, 0)])
+                .unwrap();
+            if rx.recv_timeout(Duration::from_millis(100)).is_ok() {
+                return;
+            }
+        }
+        panic!("no trigger from hotplugged mouse");
+    }
+}
@@ -0,0 +1,318 @@
+//! X11 backend: XInput2 raw events on the root window
+//!
+//! Needs no special permissions. Since XInput 2.1, raw events are delivered
+//! for every physical device regardless of focus or grabs (2.0 withholds them
+//! during the implicit grab of a button press, so the second button of a
+//! chord would never arrive), but only while the X server receives the input
+//! (on Wayland, only for XWayland windows).
+
+use super::{device_thresholds, key_input, BackendOptions, InputBackend, InputSink, Needs};
+use crate::clock;
+use crate::gesture::{GestureInput, Thresholds};
+use anyhow::{bail, Context, Result};
+use evdev::Key;
+use launcher_core::{DeviceIdentity, TriggerConfig};
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{self, ConnectionExt as _, DeviceType, XIEventMask};
+use x11rb::protocol::xproto::{ConnectionExt as _, Window};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+/// `XIAllDevices` / `XIAllMasterDevices`
+const ALL_DEVICES: u16 = 0;
+const ALL_MASTER_DEVICES: u16 = 1;
+
+/// X keycodes are evdev codes offset by 8
+const KEYCODE_OFFSET: u32 = 8;
+
+pub struct X11Backend {
+    conn: RustConnection,
+    root: Window,
+    screen: (f64, f64),
+    needs: Needs,
+    rules: TriggerConfig,
+    /// Thresholds per physical (slave) device; `None` for excluded devices
+    devices: HashMap<u16, Option<Thresholds>>,
+}
+
+impl X11Backend {
+    pub fn open(options: &BackendOptions) -> Result<Self> {
+        let (conn, screen) = x11rb::connect(None).context("No X11 display")?;
+        let setup_screen = &conn.setup().roots[screen];
+        let root = setup_screen.root;
+        let size = (
+            setup_screen.width_in_pixels as f64,
+            setup_screen.height_in_pixels as f64,
+        );
+
+        let version = conn
+            .xinput_xi_query_version(2, 2)
+            .context("XInput extension missing")?
+            .reply()
+            .context("XInput extension missing")?;
+        if (version.major_version, version.minor_version) < (2, 1) {
+            bail!(
+                "XInput {}.{} is too old, 2.1 is needed",
+                version.major_version,
+                version.minor_version
+            );
+        }
+
+        let mut mask = XIEventMask::HIERARCHY
+            | XIEventMask::RAW_BUTTON_PRESS
+            | XIEventMask::RAW_BUTTON_RELEASE;
+        if options.needs.keyboard {
+            mask |= XIEventMask::RAW_KEY_PRESS | XIEventMask::RAW_KEY_RELEASE;
+        }
+        if options.needs.pointer {
+            mask |= XIEventMask::RAW_MOTION;
+        }
+        conn.xinput_xi_select_events(
+            root,
+            &[xinput::EventMask {
+                deviceid: ALL_MASTER_DEVICES,
+                mask: vec![mask],
+            }],
+        )?
+        .check()
+        .context("Failed to select XInput raw events")?;
+
+        let mut backend = Self {
+            conn,
+            root,
+            screen: size,
+            needs: options.needs.clone(),
+            rules: options.rules.clone(),
+            devices: HashMap::new(),
+        };
+        backend.refresh_devices()?;
+        Ok(backend)
+    }
+
+    /// Re-read the device list and apply `trigger.devices` rules
+    fn refresh_devices(&mut self) -> Result<()> {
+        let reply = self.conn.xinput_xi_query_device(ALL_DEVICES)?.reply()?;
+        let mut devices = HashMap::new();
+        for info in reply.infos {
+            let kind_name = match info.type_ {
+                DeviceType::SLAVE_POINTER => "mouse",
+                DeviceType::SLAVE_KEYBOARD if self.needs.keyboard => "keyboard",
+                _ => continue,
+            };
+            let thresholds = match self.devices.get(&info.deviceid) {
+                Some(known) => *known,
+                None => {
+                    // X11 doesn't expose USB ids or the phys path here
+                    let identity = DeviceIdentity {
+                        name: String::from_utf8_lossy(&info.name).into_owned(),
+                        ..DeviceIdentity::default()
+                    };
+                    device_thresholds(&self.rules, &identity, kind_name)
+                }
+            };
+            devices.insert(info.deviceid, thresholds);
+        }
+        self.devices = devices;
+        Ok(())
+    }
+
+    /// Thresholds for events from a device, or `None` if it's excluded
+    ///
+    /// A device that isn't known yet (plugged in since the last refresh) is
+    /// looked up again first.
+    fn thresholds(&mut self, sourceid: u16) -> Result<Option<Option<Thresholds>>> {
+        if !self.devices.contains_key(&sourceid) {
+            self.refresh_devices()?;
+        }
+        Ok(match self.devices.get(&sourceid) {
+            Some(thresholds) => thresholds.map(Some),
+            None if self.rules.only_listed_devices => None,
+            None => Some(None),
+        })
+    }
+
+    fn handle_event(&mut self, event: Event, sink: &dyn InputSink) -> Result<()> {
+        // Raw events carry X server milliseconds, which wrap and whose clock
+        // isn't guaranteed; stamp them on arrival instead
+        let time = clock::now();
+        match event {
+            Event::XinputRawButtonPress(e) | Event::XinputRawButtonRelease(e) => {
+                // Touchscreens also emulate pointer buttons
+                if e.flags
+                    .contains(xinput::PointerEventFlags::POINTER_EMULATED)
+                {
+                    return Ok(());
+                }
+                let (Some(thresholds), Some(key)) =
+                    (self.thresholds(e.sourceid)?, button_key(e.detail))
+                else {
+                    return Ok(());
+                };
+                let pressed = e.event_type == xinput::RAW_BUTTON_PRESS_EVENT;
+                sink.input(key_input(key, pressed), time, thresholds);
+            }
+            Event::XinputRawKeyPress(e) | Event::XinputRawKeyRelease(e) => {
+                if e.flags.contains(xinput::KeyEventFlags::KEY_REPEAT) {
+                    return Ok(());
+                }
+                let Some(thresholds) = self.thresholds(e.sourceid)? else {
+                    return Ok(());
+                };
+                let Some(code) = e.detail.checked_sub(KEYCODE_OFFSET) else {
+                    return Ok(());
+                };
+                let pressed = e.event_type == xinput::RAW_KEY_PRESS_EVENT;
+                sink.input(key_input(Key::new(code as u16), pressed), time, thresholds);
+            }
+            Event::XinputRawMotion(_) => {
+                let pointer = self.conn.query_pointer(self.root)?.reply()?;
+                let input = GestureInput::PointerMoved {
+                    x: pointer.root_x as f64,
+                    y: pointer.root_y as f64,
+                    screen: self.screen,
+                };
+                sink.input(input, time, None);
+            }
+            Event::XinputHierarchy(_) => self.refresh_devices()?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl InputBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn run(&mut self, sink: &dyn InputSink) -> Result<()> {
+        let timeout = if self.needs.ticks { 20 } else { 100 };
+        loop {
+            while let Some(event) = self.conn.poll_for_event()? {
+                self.handle_event(event, sink)?;
+            }
+
+            let mut pollfd = libc::pollfd {
+                fd: self.conn.stream().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            unsafe { libc::poll(&mut pollfd, 1, timeout) };
+
+            if self.needs.ticks {
+                sink.tick(clock::now());
+            }
+        }
+    }
+}
+
+/// Map an X button number to its evdev code (4-7 are scroll steps)
+fn button_key(button: u32) -> Option<Key> {
+    match button {
+        1 => Some(Key::BTN_LEFT),
+        2 => Some(Key::BTN_MIDDLE),
+        3 => Some(Key::BTN_RIGHT),
+        8 => Some(Key::BTN_SIDE),
+        9 => Some(Key::BTN_EXTRA),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launcher_core::MouseButton;
+    use std::cell::RefCell;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+    use x11rb::protocol::xproto::{Setup, GE_GENERIC_EVENT};
+    use x11rb::rust_connection::DefaultStream;
+
+    /// Records what a backend feeds it
+    #[derive(Default)]
+    struct RecordingSink(RefCell<Vec<GestureInput>>);
+
+    impl InputSink for RecordingSink {
+        fn input(&self, input: GestureInput, _: Duration, _: Option<Thresholds>) -> bool {
+            self.0.borrow_mut().push(input);
+            false
+        }
+
+        fn tick(&self, _: Duration) {}
+    }
+
+    fn raw_button(pressed: bool, button: u32, sourceid: u16) -> Event {
+        let event = xinput::RawButtonPressEvent {
+            response_type: GE_GENERIC_EVENT,
+            extension: 0,
+            sequence: 0,
+            length: 0,
+            event_type: if pressed {
+                xinput::RAW_BUTTON_PRESS_EVENT
+            } else {
+                xinput::RAW_BUTTON_RELEASE_EVENT
+            },
+            deviceid: 2,
+            time: 0,
+            detail: button,
+            sourceid,
+            flags: xinput::PointerEventFlags::from(0u32),
+            valuator_mask: vec![],
+            axisvalues: vec![],
+            axisvalues_raw: vec![],
+        };
+        if pressed {
+            Event::XinputRawButtonPress(event)
+        } else {
+            Event::XinputRawButtonRelease(event)
+        }
+    }
+
+    #[test]
+    fn test_overlapping_raw_presses() {
+        // A connection to nowhere; known devices need no requests
+        let (stream, _server) = UnixStream::pair().unwrap();
+        let (stream, _) = DefaultStream::from_unix_stream(stream).unwrap();
+        let setup = Setup {
+            resource_id_mask: 0xff,
+            ..Setup::default()
+        };
+        let conn = RustConnection::for_connected_stream(stream, setup).unwrap();
+        let thresholds = Thresholds {
+            simultaneous: Duration::from_millis(200),
+            debounce: Duration::from_millis(500),
+        };
+        let mut backend = X11Backend {
+            conn,
+            root: 0,
+            screen: (1920.0, 1080.0),
+            needs: Needs::default(),
+            rules: TriggerConfig::default(),
+            devices: HashMap::from([(10, Some(thresholds)), (11, None)]),
+        };
+        let sink = RecordingSink::default();
+
+        // Left held while right goes down: both presses arrive
+        for event in [
+            raw_button(true, 1, 10),
+            raw_button(true, 3, 10),
+            raw_button(true, 3, 11),
+            raw_button(false, 3, 10),
+            raw_button(false, 1, 10),
+        ] {
+            backend.handle_event(event, &sink).unwrap();
+        }
+        let button = |button, pressed| GestureInput::Button { button, pressed };
+        assert_eq!(
+            *sink.0.borrow(),
+            [
+                button(MouseButton::Left, true),
+                button(MouseButton::Right, true),
+                button(MouseButton::Right, false),
+                button(MouseButton::Left, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Single-instance enforcement
+//!
+//! The primary instance holds an exclusive `flock` on `launcher.lock` in the
+//! runtime directory and accepts commands on `launcher.sock` next to it.
+//! Later invocations forward their command over the socket instead of
+//! starting a second listener.
+
+use crate::command::Command;
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOCK_FILE: &str = "launcher.lock";
+const SOCKET_FILE: &str = "launcher.sock";
+
+/// How long a client may take to send its command
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Get the per-user runtime directory for the lock file and socket
+pub fn runtime_dir() -> Result<PathBuf> {
+    let dir = directories::ProjectDirs::from("com", "rmanov", "launcher")
+        .and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+        .unwrap_or_else(|| {
+            // No XDG_RUNTIME_DIR: fall back to a per-user directory in /tmp
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("launcher-{}", uid))
+        });
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("Failed to create runtime directory {:?}", dir))?;
+    Ok(dir)
+}
+
+/// Proof that this process is the primary instance
+///
+/// The lock is released when the value is dropped or the process exits.
+pub struct Instance {
+    _lock: File,
+    socket_path: PathBuf,
+}
+
+impl Instance {
+    /// Try to become the primary instance, returning `None` if another one is running
+    pub fn acquire(dir: &Path) -> Result<Option<Self>> {
+        let lock_path = dir.join(LOCK_FILE);
+        let mut lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .mode(0o600)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file {:?}", lock_path))?;
+
+        let ret = unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+                return Ok(None);
+            }
+            return Err(err).with_context(|| format!("Failed to lock {:?}", lock_path));
+        }
+
+        // Record our pid for diagnostics
+        lock.set_len(0)?;
+        writeln!(lock, "{}", std::process::id())?;
+
+        Ok(Some(Self {
+            _lock: lock,
+            socket_path: dir.join(SOCKET_FILE),
+        }))
+    }
+
+    /// Like [`Instance::acquire`], but keep retrying until `timeout` elapses
+    pub fn acquire_within(dir: &Path, timeout: Duration) -> Result<Option<Self>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(instance) = Self::acquire(dir)? {
+                return Ok(Some(instance));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Start accepting commands on the IPC socket
+    ///
+    /// This spawns a background thread and returns immediately.
+    pub fn listen(&self, command_tx: Sender<Command>) -> Result<thread::JoinHandle<()>> {
+        // We hold the lock, so any existing socket is left over from a crash
+        let _ = fs::remove_file(&self.socket_path);
+        let listener = UnixListener::bind(&self.socket_path)
+            .with_context(|| format!("Failed to bind {:?}", self.socket_path))?;
+
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_client(stream, &command_tx) {
+                            log::warn!("IPC client error: {:#}", e);
+                        }
+                    }
+                    Err(e) => log::warn!("IPC accept error: {}", e),
+                }
+            }
+        }))
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+/// Read one command from a client and reply with `ok` or `error: ...`
+///
+/// Clients are served one at a time, so a silent one times out rather than
+/// blocking all others.
+fn handle_client(stream: UnixStream, command_tx: &Sender<Command>) -> Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let reply = match serde_json::from_str::<Command>(&line) {
+        Ok(command) => match command_tx.send(command) {
+            Ok(()) => "ok".to_string(),
+            Err(_) => "error: launcher is shutting down".to_string(),
+        },
+        Err(e) => format!("error: invalid command: {}", e),
+    };
+    writeln!(&stream, "{}", reply)?;
+    Ok(())
+}
+
+/// Send a command to the primary instance
+pub fn send_command(dir: &Path, command: &Command) -> Result<()> {
+    let socket_path = dir.join(SOCKET_FILE);
+    let stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("Failed to connect to {:?}", socket_path))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    writeln!(&stream, "{}", serde_json::to_string(command)?)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        other => bail!("Launcher replied: {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_second_instance_is_rejected() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let first = Instance::acquire(dir).unwrap();
+        assert!(first.is_some());
+        assert!(Instance::acquire(dir).unwrap().is_none());
+
+        drop(first);
+        assert!(Instance::acquire(dir).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_forward_command() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let instance = Instance::acquire(dir).unwrap().unwrap();
+
+        let (command_tx, command_rx) = channel();
+        instance.listen(command_tx).unwrap();
+
+        let command = Command::Launch {
+            path: "/usr/bin/foot".to_string(),
+        };
+        send_command(dir, &command).unwrap();
+        assert_eq!(command_rx.recv().unwrap(), command);
+
+        // A client that never sends a line doesn't block the next one
+        let _silent = UnixStream::connect(dir.join(SOCKET_FILE)).unwrap();
+        send_command(dir, &Command::Quit).unwrap();
+        assert_eq!(command_rx.recv().unwrap(), Command::Quit);
+    }
+}
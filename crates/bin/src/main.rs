@@ -2,13 +2,36 @@
 //!
 //! Cross-platform program launcher triggered by simultaneous L+R mouse click.
 
+mod cli;
+mod clock;
+mod command;
+mod cursor;
+mod dbus;
+mod focus;
+mod gesture;
+mod grab;
+mod hotplug;
 mod input;
+mod instance;
+mod monitor;
+mod replay;
+mod stats;
+mod touch;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use cli::Cli;
+use command::{Command, Dispatcher};
 use input::InputListener;
-use launcher_core::{ConfigManager, UsageTracker};
-use launcher_ui::run_popup;
+use instance::Instance;
+use launcher_core::{Config, ConfigManager, UsageTracker};
+use launcher_ui::{run_popup, PopupControl};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often the idle main loop checks for a `quit` command
+const QUIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 fn main() -> Result<()> {
     // Initialize logging
@@ -16,58 +39,164 @@ fn main() -> Result<()> {
         .format_timestamp_secs()
         .init();
 
+    let replace = match cli::parse_args(std::env::args().skip(1))? {
+        Cli::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Cli::Send(command) => {
+            let runtime_dir = instance::runtime_dir()?;
+            return instance::send_command(&runtime_dir, &command)
+                .context("Failed to reach the running launcher (is it started?)");
+        }
+        Cli::TriggerRecord(path) => return replay::record(&path),
+        Cli::TriggerReplay(path) => {
+            let events = replay::load(&path)?;
+            let config = Config::load()?;
+            print!("{}", replay::replay(&events, &config.trigger));
+            return Ok(());
+        }
+        Cli::Stats(options) => return stats::run(&options),
+        Cli::Run { replace } => replace,
+    };
+
+    // Make sure only one launcher reads input and writes usage/clipboard files
+    let runtime_dir = instance::runtime_dir()?;
+    let instance = match Instance::acquire(&runtime_dir)? {
+        Some(instance) => instance,
+        None if replace => {
+            log::info!("Asking the running launcher to exit");
+            if let Err(e) = instance::send_command(&runtime_dir, &Command::Quit) {
+                log::warn!("Failed to send quit command: {:#}", e);
+            }
+            Instance::acquire_within(&runtime_dir, Duration::from_secs(5))?
+                .context("The running launcher did not exit in time")?
+        }
+        None => bail!("Launcher is already running (use --replace to take over)"),
+    };
+
     log::info!("Starting Simple Program Launcher");
 
     // Load configuration
     let config_manager =
         Arc::new(ConfigManager::new().context("Failed to initialize config manager")?);
 
+    // Pull pins and usage from other machines before they are first read
+    let _sync_watcher = launcher_core::sync::start(config_manager.clone()).unwrap_or_else(|e| {
+        log::warn!("Sync unavailable: {:#}", e);
+        None
+    });
+
     // Initialize usage tracker
     let usage_tracker = Arc::new(Mutex::new(
-        UsageTracker::new().context("Failed to initialize usage tracker")?,
+        UsageTracker::new(&config_manager.get().ranking)
+            .context("Failed to initialize usage tracker")?,
     ));
 
     // Get trigger settings
-    let (simultaneous_threshold, debounce) = {
-        let config = config_manager.get();
-        (
-            config.trigger.simultaneous_threshold_ms,
-            config.trigger.debounce_ms,
-        )
-    };
+    let trigger_config = config_manager.get().trigger.clone();
 
     // Create input listener
-    let (listener, trigger_rx) = InputListener::new(simultaneous_threshold, debounce);
+    let (listener, trigger_rx) = InputListener::new(
+        trigger_config.simultaneous_threshold_ms,
+        trigger_config.debounce_ms,
+    );
+    let listener = listener
+        .with_gestures(trigger_config.gestures.clone())
+        .with_grab(trigger_config.grab_devices)
+        .with_backend(trigger_config.backend)
+        .with_devices(
+            trigger_config.devices.clone(),
+            trigger_config.only_listed_devices,
+        );
+
+    // Commands from external clients share the trigger channel for `Show`
+    let popup_control = PopupControl::new();
+    let command_tx = Dispatcher::new(
+        config_manager.clone(),
+        usage_tracker.clone(),
+        popup_control.clone(),
+        listener.trigger_sender(),
+    )
+    .start();
+
+    // Accept commands forwarded by later invocations
+    instance
+        .listen(command_tx.clone())
+        .context("Failed to start IPC socket")?;
+
+    // Expose the D-Bus service (optional - no session bus on some setups)
+    let _dbus_connection = match dbus::serve(command_tx, &popup_control) {
+        Ok(connection) => {
+            log::info!("D-Bus service registered as {}", dbus::SERVICE_NAME);
+            Some(connection)
+        }
+        Err(e) => {
+            log::warn!("D-Bus service unavailable: {:#}", e);
+            None
+        }
+    };
 
     // Start listening for mouse events
     let _listener_handle = listener.start();
 
     log::info!(
-        "Listening for L+R click (threshold: {}ms, debounce: {}ms)",
-        simultaneous_threshold,
-        debounce
+        "Listening for {} gesture(s) (threshold: {}ms, debounce: {}ms)",
+        trigger_config.gestures.len(),
+        trigger_config.simultaneous_threshold_ms,
+        trigger_config.debounce_ms
     );
     log::info!("Press Ctrl+C to exit");
 
     // Main event loop - wait for triggers
     loop {
-        match trigger_rx.recv() {
+        if popup_control.quit_requested() {
+            log::info!("Quit requested, exiting");
+            break;
+        }
+        match trigger_rx.recv_timeout(QUIT_POLL_INTERVAL) {
             Ok(trigger) => {
+                // Both before the popup takes focus, side by side as either
+                // may wait on a compositor or X server
+                let focus = thread::spawn(focus::query);
+                let position = trigger.position.unwrap_or_else(|| {
+                    let cursor = cursor::query();
+                    log::info!(
+                        "Cursor at ({}, {}) via {}",
+                        cursor.x,
+                        cursor.y,
+                        cursor.source
+                    );
+                    cursor.xy()
+                });
                 log::info!(
-                    "Trigger detected at position ({:.0}, {:.0})",
-                    trigger.position.0,
-                    trigger.position.1
+                    "Trigger detected at position ({:.0}, {:.0}), profile: {}",
+                    position.0,
+                    position.1,
+                    trigger.profile.as_deref().unwrap_or("default")
                 );
+                log::debug!("Trigger latency: {:?}", trigger.timestamp.elapsed());
+
+                let context = focus.join().unwrap_or_else(|_| {
+                    log::warn!("Focused app query panicked");
+                    None
+                });
+                log::debug!("Focused app: {}", context.as_deref().unwrap_or("unknown"));
 
                 // Show the popup window on main thread (required by winit)
                 if let Err(e) = run_popup(
-                    trigger.position,
+                    position,
+                    monitor::detect(),
+                    trigger.profile,
+                    context,
                     config_manager.clone(),
                     usage_tracker.clone(),
+                    popup_control.clone(),
                 ) {
                     log::error!("Popup error: {}", e);
                 }
             }
+            Err(RecvTimeoutError::Timeout) => {}
             Err(e) => {
                 log::error!("Trigger channel error: {}", e);
                 break;
@@ -75,5 +204,8 @@ fn main() -> Result<()> {
         }
     }
 
+    if let Ok(mut tracker) = usage_tracker.lock() {
+        let _ = tracker.save_if_dirty();
+    }
     Ok(())
 }
@@ -0,0 +1,143 @@
+//! Monitor layout detection for popup placement
+//!
+//! Monitors are reported in the same coordinate space as [`crate::cursor`]
+//! positions from the same session: Hyprland uses logical layout coordinates
+//! (so scale is 1.0 there), X11 uses physical pixels with the global
+//! `Xft.dpi` scale.
+
+use crate::cursor;
+use anyhow::{Context, Result};
+use launcher_ui::Monitor;
+use serde::Deserialize;
+use x11rb::connection::Connection as _;
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+
+/// Detect monitors, or an empty list if the layout is unknown
+pub fn detect() -> Vec<Monitor> {
+    let hyprland = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some();
+    let result = if hyprland {
+        detect_hyprland()
+    } else {
+        detect_x11()
+    };
+    match result {
+        Ok(monitors) => {
+            log::debug!("Monitors: {:?}", monitors);
+            monitors
+        }
+        Err(e) => {
+            log::debug!("Monitor layout unavailable: {:#}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HyprlandMonitor {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    scale: f64,
+    #[serde(default)]
+    transform: u8,
+}
+
+fn detect_hyprland() -> Result<Vec<Monitor>> {
+    let reply = cursor::hyprland_request("j/monitors")?;
+    parse_hyprland(&reply)
+}
+
+/// Convert `hyprctl -j monitors` output to logical monitor rectangles
+fn parse_hyprland(json: &str) -> Result<Vec<Monitor>> {
+    let monitors: Vec<HyprlandMonitor> =
+        serde_json::from_str(json).context("Invalid monitor list")?;
+    Ok(monitors
+        .into_iter()
+        .map(|m| {
+            let scale = if m.scale > 0.0 { m.scale } else { 1.0 };
+            // Transforms 1, 3, 5, 7 rotate by 90°
+            let (width, height) = if m.transform % 2 == 1 {
+                (m.height, m.width)
+            } else {
+                (m.width, m.height)
+            };
+            Monitor {
+                x: m.x,
+                y: m.y,
+                width: width / scale,
+                height: height / scale,
+                scale: 1.0,
+            }
+        })
+        .collect())
+}
+
+fn detect_x11() -> Result<Vec<Monitor>> {
+    let (conn, screen) = x11rb::connect(None).context("No X11 display")?;
+    let root = conn.setup().roots[screen].root;
+
+    // Xft.dpi is the closest thing X11 has to a scale factor
+    let resources = conn
+        .get_property(
+            false,
+            root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            0,
+            u32::MAX / 4,
+        )?
+        .reply()?;
+    let scale = xft_scale(&String::from_utf8_lossy(&resources.value)).unwrap_or(1.0);
+
+    let reply = conn.randr_get_monitors(root, true)?.reply()?;
+    Ok(reply
+        .monitors
+        .iter()
+        .map(|m| Monitor {
+            x: m.x as f64,
+            y: m.y as f64,
+            width: m.width as f64,
+            height: m.height as f64,
+            scale,
+        })
+        .collect())
+}
+
+/// Scale factor from the `Xft.dpi` X resource (96 DPI = 1.0)
+fn xft_scale(resources: &str) -> Option<f64> {
+    let dpi: f64 = resources
+        .lines()
+        .find_map(|line| line.strip_prefix("Xft.dpi:"))?
+        .trim()
+        .parse()
+        .ok()?;
+    (dpi > 0.0).then_some(dpi / 96.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hyprland_monitors() {
+        let json = r#"[
+            {"id": 0, "name": "eDP-1", "x": 0, "y": 0, "width": 2880, "height": 1800,
+             "scale": 2.0, "transform": 0},
+            {"id": 1, "name": "DP-1", "x": 1440, "y": 0, "width": 1920, "height": 1080,
+             "scale": 1.0, "transform": 1}
+        ]"#;
+        let monitors = parse_hyprland(json).unwrap();
+        assert_eq!(monitors.len(), 2);
+        assert_eq!((monitors[0].width, monitors[0].height), (1440.0, 900.0));
+        assert_eq!((monitors[1].width, monitors[1].height), (1080.0, 1920.0));
+        assert_eq!(monitors[1].x, 1440.0);
+    }
+
+    #[test]
+    fn test_xft_scale() {
+        assert_eq!(xft_scale("Xft.antialias:\t1\nXft.dpi:\t144\n"), Some(1.5));
+        assert_eq!(xft_scale("Xcursor.size:\t24\n"), None);
+    }
+}
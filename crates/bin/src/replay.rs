@@ -0,0 +1,495 @@
+//! Recording and offline replay of trigger input, for tuning thresholds
+//!
+//! `launcher trigger record <file>` writes mouse button and modifier key
+//! events (never other keys) as JSON lines with kernel timestamps.
+//! `launcher trigger replay <file>` feeds them through the gesture
+//! recognizer with the configured thresholds, per-device rules included, and
+//! explains the outcome.
+
+use crate::gesture::{GestureInput, GestureRecognizer, Rejection, Thresholds};
+use crate::input::{button_from_key, device_identity, device_kind, modifier_from_key};
+use anyhow::{Context, Result};
+use evdev::{InputEventKind, Key};
+use launcher_core::{DeviceIdentity, TriggerConfig};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// Chord gaps above this are treated as separate clicks, not chord attempts
+const PLAUSIBLE_CHORD_GAP: Duration = Duration::from_millis(400);
+
+/// Debounced triggers this far apart were probably intentional
+const INTENTIONAL_RETRIGGER: Duration = Duration::from_millis(150);
+
+/// One recorded button or modifier event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the first recorded event
+    pub time_ms: f64,
+    pub device: String,
+    /// USB ids and physical path of the device, for `trigger.devices` rules
+    /// (missing in older recordings)
+    #[serde(default)]
+    pub vendor: u16,
+    #[serde(default)]
+    pub product: u16,
+    #[serde(default)]
+    pub phys: String,
+    /// evdev key name, e.g. `BTN_LEFT`
+    pub key: String,
+    pub pressed: bool,
+}
+
+impl RecordedEvent {
+    fn identity(&self) -> DeviceIdentity {
+        DeviceIdentity {
+            name: self.device.clone(),
+            vendor: self.vendor,
+            product: self.product,
+            phys: self.phys.clone(),
+        }
+    }
+}
+
+/// Record events until interrupted (Ctrl-C)
+pub fn record(path: &Path) -> Result<()> {
+    let mut devices: Vec<_> = evdev::enumerate()
+        .filter(|(_, device)| device_kind(device).is_some())
+        .map(|(_, device)| device)
+        .collect();
+    if devices.is_empty() {
+        anyhow::bail!("No input devices found (is your user in the 'input' group?)");
+    }
+
+    let mut file = File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+    eprintln!(
+        "Recording {} device(s) to {:?}, press Ctrl-C to stop",
+        devices.len(),
+        path
+    );
+
+    let mut pollfds: Vec<libc::pollfd> = devices
+        .iter()
+        .map(|d| libc::pollfd {
+            fd: d.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    let mut start: Option<SystemTime> = None;
+
+    loop {
+        let ret = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) };
+        if ret <= 0 {
+            continue;
+        }
+
+        // Unplugged devices report a hangup forever; stop polling them
+        for i in (0..pollfds.len()).rev() {
+            if pollfds[i].revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0 {
+                let device = devices.remove(i);
+                pollfds.remove(i);
+                eprintln!("{} disconnected", device.name().unwrap_or("Device"));
+            }
+        }
+        if devices.is_empty() {
+            anyhow::bail!("All devices disconnected");
+        }
+
+        for (i, pollfd) in pollfds.iter_mut().enumerate() {
+            if pollfd.revents & libc::POLLIN == 0 {
+                continue;
+            }
+            pollfd.revents = 0;
+
+            let device = &mut devices[i];
+            let identity = device_identity(device);
+            let Ok(events) = device.fetch_events() else {
+                continue;
+            };
+            for event in events {
+                let InputEventKind::Key(key) = event.kind() else {
+                    continue;
+                };
+                if event.value() == 2
+                    || (button_from_key(key).is_none() && modifier_from_key(key).is_none())
+                {
+                    continue;
+                }
+                let start = *start.get_or_insert(event.timestamp());
+                let elapsed = event.timestamp().duration_since(start).unwrap_or_default();
+                let recorded = RecordedEvent {
+                    time_ms: elapsed.as_secs_f64() * 1000.0,
+                    device: identity.name.clone(),
+                    vendor: identity.vendor,
+                    product: identity.product,
+                    phys: identity.phys.clone(),
+                    key: format!("{:?}", key),
+                    pressed: event.value() == 1,
+                };
+                writeln!(file, "{}", serde_json::to_string(&recorded)?)?;
+                file.flush()?;
+                eprintln!(
+                    "{:>10.1} ms  {:<12} {}",
+                    recorded.time_ms,
+                    recorded.key,
+                    if recorded.pressed { "down" } else { "up" }
+                );
+            }
+        }
+    }
+}
+
+/// Read a recording written by [`record`]
+pub fn load(path: &Path) -> Result<Vec<RecordedEvent>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|(n, line)| {
+            serde_json::from_str(&line?).with_context(|| format!("Invalid event on line {}", n + 1))
+        })
+        .collect()
+}
+
+/// What happened to a recorded press
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Triggered { profile: Option<String> },
+    Rejected(Rejection),
+}
+
+/// A press that triggered or almost triggered
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub time_ms: f64,
+    pub key: String,
+    pub verdict: Verdict,
+    /// Thresholds that applied to the device, `(simultaneous, debounce)` ms
+    pub thresholds_ms: (u64, u64),
+}
+
+/// Result of replaying a recording
+#[derive(Debug, Default)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+    /// Spread of every chord attempt, and whether it triggered
+    pub chord_gaps: Vec<(Duration, bool)>,
+    pub presses: usize,
+    /// Events from devices that `trigger.devices` excludes
+    pub excluded: usize,
+    pub current_threshold_ms: u64,
+    pub current_debounce_ms: u64,
+}
+
+/// Feed recorded events through the recognizer with the configured thresholds
+///
+/// Like the daemon, events from excluded devices are skipped and devices with
+/// their own rule use its thresholds.
+pub fn replay(events: &[RecordedEvent], trigger: &TriggerConfig) -> Report {
+    let mut recognizer = GestureRecognizer::new(
+        trigger.gestures.clone(),
+        Duration::from_millis(trigger.simultaneous_threshold_ms),
+        Duration::from_millis(trigger.debounce_ms),
+    );
+    let mut report = Report {
+        current_threshold_ms: trigger.simultaneous_threshold_ms,
+        current_debounce_ms: trigger.debounce_ms,
+        ..Default::default()
+    };
+    let mut last_tick = Duration::ZERO;
+    let defaults = (trigger.simultaneous_threshold_ms, trigger.debounce_ms);
+
+    for event in events {
+        let Ok(key) = Key::from_str(&event.key) else {
+            log::warn!("Skipping unknown key {:?}", event.key);
+            continue;
+        };
+        let offset = Duration::from_secs_f64(event.time_ms.max(0.0) / 1000.0);
+
+        // Let time-based gestures (long press) fire between events
+        while last_tick + Duration::from_millis(10) < offset {
+            last_tick += Duration::from_millis(10);
+            if let Some(fired) = recognizer.tick(last_tick) {
+                report.outcomes.push(Outcome {
+                    time_ms: last_tick.as_secs_f64() * 1000.0,
+                    key: "(held)".to_string(),
+                    verdict: Verdict::Triggered {
+                        profile: fired.profile.clone(),
+                    },
+                    thresholds_ms: defaults,
+                });
+            }
+        }
+
+        let Some(thresholds_ms) = trigger.device_thresholds(&event.identity()) else {
+            report.excluded += 1;
+            continue;
+        };
+        let thresholds = Thresholds {
+            simultaneous: Duration::from_millis(thresholds_ms.0),
+            debounce: Duration::from_millis(thresholds_ms.1),
+        };
+
+        let input = if let Some(button) = button_from_key(key) {
+            GestureInput::Button {
+                button,
+                pressed: event.pressed,
+            }
+        } else if let Some(modifier) = modifier_from_key(key) {
+            GestureInput::Modifier {
+                modifier,
+                pressed: event.pressed,
+            }
+        } else {
+            continue;
+        };
+        if event.pressed {
+            report.presses += 1;
+        }
+
+        let fired = recognizer
+            .handle_with(input, offset, Some(thresholds))
+            .map(|g| g.profile.clone());
+        if let Some(gap) = recognizer.last_chord_gap() {
+            report.chord_gaps.push((gap, fired.is_some()));
+        }
+        let verdict = match (fired, recognizer.last_rejection()) {
+            (Some(profile), _) => Verdict::Triggered { profile },
+            (None, Some(rejection)) => Verdict::Rejected(rejection),
+            (None, None) => continue,
+        };
+        report.outcomes.push(Outcome {
+            time_ms: event.time_ms,
+            key: event.key.clone(),
+            verdict,
+            thresholds_ms,
+        });
+    }
+    report
+}
+
+impl Report {
+    /// Suggested `simultaneous_threshold_ms`, from the slowest plausible chord
+    pub fn suggested_threshold_ms(&self) -> Option<u64> {
+        let slowest = self
+            .chord_gaps
+            .iter()
+            .map(|(gap, _)| *gap)
+            .filter(|gap| *gap <= PLAUSIBLE_CHORD_GAP)
+            .max()?;
+        // 25% headroom, rounded up to 10 ms
+        let ms = (slowest.as_millis() as f64 * 1.25 / 10.0).ceil() as u64 * 10;
+        Some(ms.max(20))
+    }
+
+    /// Suggested `debounce_ms`, if intentional re-triggers were debounced
+    pub fn suggested_debounce_ms(&self) -> Option<u64> {
+        let shortest = self
+            .outcomes
+            .iter()
+            .filter_map(|o| match o.verdict {
+                Verdict::Rejected(Rejection::Debounced { since_last }) => Some(since_last),
+                _ => None,
+            })
+            .filter(|since_last| *since_last >= INTENTIONAL_RETRIGGER)
+            .min()?;
+        // 20% below the fastest intentional re-trigger, rounded down to 10 ms
+        let ms = (shortest.as_millis() as f64 * 0.8 / 10.0).floor() as u64 * 10;
+        Some(ms.max(100))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for outcome in &self.outcomes {
+            write!(f, "{:>10.1} ms  {:<12} ", outcome.time_ms, outcome.key)?;
+            match &outcome.verdict {
+                Verdict::Triggered { profile } => match profile {
+                    Some(profile) => writeln!(f, "TRIGGER (profile {})", profile)?,
+                    None => writeln!(f, "TRIGGER")?,
+                },
+                Verdict::Rejected(Rejection::ChordTooSlow { gap }) => writeln!(
+                    f,
+                    "rejected: chord buttons {} ms apart (threshold {} ms)",
+                    gap.as_millis(),
+                    outcome.thresholds_ms.0
+                )?,
+                Verdict::Rejected(Rejection::Debounced { since_last }) => writeln!(
+                    f,
+                    "rejected: {} ms after previous trigger (debounce {} ms)",
+                    since_last.as_millis(),
+                    outcome.thresholds_ms.1
+                )?,
+            }
+        }
+
+        let triggers = self
+            .outcomes
+            .iter()
+            .filter(|o| matches!(o.verdict, Verdict::Triggered { .. }))
+            .count();
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{} presses, {} triggered, {} rejected",
+            self.presses,
+            triggers,
+            self.outcomes.len() - triggers
+        )?;
+        if self.excluded > 0 {
+            writeln!(
+                f,
+                "{} events skipped from devices excluded by trigger.devices",
+                self.excluded
+            )?;
+        }
+
+        match self.suggested_threshold_ms() {
+            Some(ms) => writeln!(
+                f,
+                "Suggested simultaneous_threshold_ms: {} (current {})",
+                ms, self.current_threshold_ms
+            )?,
+            None => writeln!(f, "No chord attempts recorded")?,
+        }
+        if let Some(ms) = self.suggested_debounce_ms() {
+            writeln!(
+                f,
+                "Suggested debounce_ms: {} (current {})",
+                ms, self.current_debounce_ms
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time_ms: f64, key: &str, pressed: bool) -> RecordedEvent {
+        RecordedEvent {
+            time_ms,
+            device: "test mouse".to_string(),
+            vendor: 0,
+            product: 0,
+            phys: String::new(),
+            key: key.to_string(),
+            pressed,
+        }
+    }
+
+    fn trigger(threshold_ms: u64, debounce_ms: u64) -> TriggerConfig {
+        TriggerConfig {
+            simultaneous_threshold_ms: threshold_ms,
+            debounce_ms,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_replay_explains_rejections() {
+        let events = vec![
+            // Chord 30 ms apart: triggers
+            event(0.0, "BTN_LEFT", true),
+            event(30.0, "BTN_RIGHT", true),
+            event(80.0, "BTN_LEFT", false),
+            event(90.0, "BTN_RIGHT", false),
+            // Again 300 ms later: debounced
+            event(300.0, "BTN_LEFT", true),
+            event(310.0, "BTN_RIGHT", true),
+            event(350.0, "BTN_LEFT", false),
+            event(360.0, "BTN_RIGHT", false),
+            // Chord 80 ms apart: too slow for a 50 ms threshold
+            event(2000.0, "BTN_RIGHT", true),
+            event(2080.0, "BTN_LEFT", true),
+        ];
+        let report = replay(&events, &trigger(50, 500));
+
+        let verdicts: Vec<_> = report.outcomes.iter().map(|o| &o.verdict).collect();
+        assert_eq!(verdicts.len(), 3);
+        assert_eq!(verdicts[0], &Verdict::Triggered { profile: None });
+        assert!(matches!(
+            verdicts[1],
+            Verdict::Rejected(Rejection::Debounced { .. })
+        ));
+        assert!(matches!(
+            verdicts[2],
+            Verdict::Rejected(Rejection::ChordTooSlow { .. })
+        ));
+
+        // Slowest chord was 80 ms, intentional re-trigger after 280 ms
+        assert_eq!(report.suggested_threshold_ms(), Some(100));
+        assert_eq!(report.suggested_debounce_ms(), Some(220));
+        assert!(report
+            .to_string()
+            .contains("6 presses, 1 triggered, 2 rejected"));
+    }
+
+    #[test]
+    fn test_replay_applies_device_rules() {
+        let on = |device: &str, time_ms: f64, key: &str| RecordedEvent {
+            device: device.to_string(),
+            ..event(time_ms, key, true)
+        };
+        let events = vec![
+            // 80 ms apart on a mouse with a 100 ms rule: triggers
+            on("Slow Mouse", 0.0, "BTN_LEFT"),
+            on("Slow Mouse", 80.0, "BTN_RIGHT"),
+            // Excluded touchpad
+            on("Touchpad", 2000.0, "BTN_LEFT"),
+            on("Touchpad", 2010.0, "BTN_RIGHT"),
+        ];
+        let mut config = trigger(50, 500);
+        config.devices = vec![
+            launcher_core::DeviceRule {
+                name: Some("slow".to_string()),
+                simultaneous_threshold_ms: Some(100),
+                ..Default::default()
+            },
+            launcher_core::DeviceRule {
+                name: Some("touchpad".to_string()),
+                exclude: true,
+                ..Default::default()
+            },
+        ];
+        let report = replay(&events, &config);
+        assert_eq!(report.outcomes.len(), 1);
+        assert_eq!(
+            report.outcomes[0].verdict,
+            Verdict::Triggered { profile: None }
+        );
+        assert_eq!(report.outcomes[0].thresholds_ms, (100, 500));
+        assert_eq!(report.excluded, 2);
+
+        // Only listed devices: the unlisted one is skipped too
+        config.only_listed_devices = true;
+        config.devices.truncate(1);
+        let events = [on("Other Mouse", 0.0, "BTN_LEFT")];
+        assert_eq!(replay(&events, &config).excluded, 1);
+    }
+
+    #[test]
+    fn test_recording_round_trip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path();
+        let events = vec![
+            event(0.0, "BTN_LEFT", true),
+            event(12.5, "KEY_LEFTMETA", false),
+        ];
+        let lines: Vec<String> = events
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap())
+            .collect();
+        std::fs::write(path, lines.join("\n") + "\n\n").unwrap();
+
+        assert_eq!(load(path).unwrap(), events);
+    }
+}
@@ -0,0 +1,127 @@
+//! `launcher stats`: usage statistics from `usage.json` and the clipboard
+//! history, printed as tables or exported as CSV/JSON
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use launcher_core::{Period, UsageData, UsageStats};
+use std::fs;
+use std::path::PathBuf;
+
+/// Output format of `launcher stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+/// Options of `launcher stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsOptions {
+    pub period: Period,
+    /// Number of periods, ending with the current one
+    pub periods: usize,
+    /// Documents and clipboard entries listed
+    pub top: usize,
+    pub format: Format,
+    /// Write here instead of stdout
+    pub output: Option<PathBuf>,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self {
+            period: Period::Day,
+            periods: 7,
+            top: 10,
+            format: Format::Text,
+            output: None,
+        }
+    }
+}
+
+impl StatsOptions {
+    /// Parse `stats` arguments
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Self::default();
+        let mut default_periods = true;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .with_context(|| format!("{} needs a value", name))
+            };
+            match arg.as_str() {
+                "--weekly" => options.period = Period::Week,
+                "--periods" => {
+                    options.periods = value(arg)?.parse().context("Invalid --periods")?;
+                    default_periods = false;
+                }
+                "--top" => options.top = value(arg)?.parse().context("Invalid --top")?,
+                "--csv" => options.format = Format::Csv,
+                "--json" => options.format = Format::Json,
+                "--output" => options.output = Some(value(arg)?.into()),
+                _ => bail!("Unknown stats option: {}", arg),
+            }
+        }
+        if options.period == Period::Week && default_periods {
+            options.periods = 8;
+        }
+        if options.periods == 0 {
+            bail!("--periods must be at least 1");
+        }
+        Ok(options)
+    }
+}
+
+/// Compute statistics and print or export them
+pub fn run(options: &StatsOptions) -> Result<()> {
+    let data = UsageData::load()?;
+    let pastes = launcher_ui::clipboard_paste_counts();
+    let stats = UsageStats::compute(
+        &data,
+        &pastes,
+        options.period,
+        options.periods,
+        &Local::now(),
+        options.top,
+    );
+
+    let rendered = match options.format {
+        Format::Text => stats.to_text(),
+        Format::Csv => stats.to_csv(),
+        Format::Json => serde_json::to_string_pretty(&stats)? + "\n",
+    };
+    match &options.output {
+        Some(path) => {
+            fs::write(path, rendered).with_context(|| format!("Failed to write {:?}", path))?;
+            println!("Wrote usage statistics to {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<StatsOptions> {
+        StatsOptions::parse(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(parse(&[]).unwrap(), StatsOptions::default());
+        let weekly = parse(&["--weekly", "--csv", "--output", "usage.csv"]).unwrap();
+        assert_eq!(weekly.period, Period::Week);
+        assert_eq!(weekly.periods, 8);
+        assert_eq!(weekly.format, Format::Csv);
+        assert_eq!(weekly.output, Some(PathBuf::from("usage.csv")));
+        let json = parse(&["--json", "--periods", "30", "--top", "3"]).unwrap();
+        assert_eq!((json.periods, json.top, json.format), (30, 3, Format::Json));
+        assert!(parse(&["--periods"]).is_err());
+        assert!(parse(&["--periods", "0"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Multitouch frame tracking for touchpads and touchscreens
+//!
+//! Follows the `ABS_MT_*` slot protocol (type B) and the `BTN_TOOL_*TAP`
+//! finger count keys, and reduces each `SYN_REPORT` frame to a finger count
+//! and the normalized centroid of the touching fingers. Touchpads often track
+//! fewer slots than they count fingers, so the larger count wins.
+
+use evdev::{AbsoluteAxisType, Device, InputEvent, InputEventKind, Key, Synchronization};
+
+/// Slots tracked when the device doesn't report its slot range
+const DEFAULT_SLOTS: usize = 10;
+
+/// The fingers on the device after a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchFrame {
+    pub fingers: u8,
+    /// Centroid of the touching fingers, 0.0 to 1.0 on each axis
+    pub position: (f64, f64),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    active: bool,
+    x: i32,
+    y: i32,
+}
+
+/// Reassembles multitouch frames from a device's events
+pub struct TouchTracker {
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+    slots: Vec<Slot>,
+    slot: usize,
+    /// Finger count from `BTN_TOOL_*`
+    tool_fingers: u8,
+    last: Option<TouchFrame>,
+}
+
+impl TouchTracker {
+    /// Track a device if it supports multitouch positions
+    pub fn of(device: &Device) -> Option<Self> {
+        let axes = device.supported_absolute_axes()?;
+        if !axes.contains(AbsoluteAxisType::ABS_MT_POSITION_X)
+            || !axes.contains(AbsoluteAxisType::ABS_MT_POSITION_Y)
+        {
+            return None;
+        }
+        let state = device.get_abs_state().ok()?;
+        let range = |axis: AbsoluteAxisType| {
+            let info = state[axis.0 as usize];
+            (info.minimum, info.maximum)
+        };
+        let slots = if axes.contains(AbsoluteAxisType::ABS_MT_SLOT) {
+            range(AbsoluteAxisType::ABS_MT_SLOT).1.max(0) as usize + 1
+        } else {
+            DEFAULT_SLOTS
+        };
+        Some(Self::new(
+            range(AbsoluteAxisType::ABS_MT_POSITION_X),
+            range(AbsoluteAxisType::ABS_MT_POSITION_Y),
+            slots,
+        ))
+    }
+
+    fn new(x_range: (i32, i32), y_range: (i32, i32), slots: usize) -> Self {
+        Self {
+            x_range,
+            y_range,
+            slots: vec![Slot::default(); slots.clamp(1, 64)],
+            slot: 0,
+            tool_fingers: 0,
+            last: None,
+        }
+    }
+
+    /// Feed an event; returns the new frame at `SYN_REPORT` if it changed
+    pub fn process(&mut self, event: &InputEvent) -> Option<TouchFrame> {
+        match event.kind() {
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_SLOT) => {
+                self.slot = event.value().max(0) as usize;
+            }
+            InputEventKind::AbsAxis(axis) => {
+                let slot = self.slots.get_mut(self.slot)?;
+                match axis {
+                    // -1 lifts the finger, anything else is a new contact
+                    AbsoluteAxisType::ABS_MT_TRACKING_ID => slot.active = event.value() >= 0,
+                    AbsoluteAxisType::ABS_MT_POSITION_X => slot.x = event.value(),
+                    AbsoluteAxisType::ABS_MT_POSITION_Y => slot.y = event.value(),
+                    _ => {}
+                }
+            }
+            InputEventKind::Key(key) => {
+                if let Some(count) = tool_finger_count(key) {
+                    if event.value() != 0 {
+                        self.tool_fingers = count;
+                    } else if self.tool_fingers == count {
+                        self.tool_fingers = 0;
+                    }
+                }
+            }
+            InputEventKind::Synchronization(Synchronization::SYN_REPORT) => {
+                let frame = self.frame();
+                if self.last != Some(frame) {
+                    self.last = Some(frame);
+                    return Some(frame);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn frame(&self) -> TouchFrame {
+        let active: Vec<&Slot> = self.slots.iter().filter(|s| s.active).collect();
+        let position = if active.is_empty() {
+            self.last.map(|f| f.position).unwrap_or_default()
+        } else {
+            let n = active.len() as f64;
+            let x = active.iter().map(|s| s.x as f64).sum::<f64>() / n;
+            let y = active.iter().map(|s| s.y as f64).sum::<f64>() / n;
+            (fraction(self.x_range, x), fraction(self.y_range, y))
+        };
+        TouchFrame {
+            fingers: (active.len() as u8).max(self.tool_fingers),
+            position,
+        }
+    }
+}
+
+/// Fingers reported by a `BTN_TOOL_*` key
+fn tool_finger_count(key: Key) -> Option<u8> {
+    match key {
+        Key::BTN_TOOL_FINGER => Some(1),
+        Key::BTN_TOOL_DOUBLETAP => Some(2),
+        Key::BTN_TOOL_TRIPLETAP => Some(3),
+        Key::BTN_TOOL_QUADTAP => Some(4),
+        Key::BTN_TOOL_QUINTTAP => Some(5),
+        _ => None,
+    }
+}
+
+fn fraction(range: (i32, i32), value: f64) -> f64 {
+    if range.1 <= range.0 {
+        return 0.0;
+    }
+    ((value - range.0 as f64) / (range.1 - range.0) as f64).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::EventType;
+
+    fn abs(axis: AbsoluteAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+    }
+
+    fn key(key: Key, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY, key.code(), value)
+    }
+
+    fn syn() -> InputEvent {
+        InputEvent::new(EventType::SYNCHRONIZATION, Synchronization::SYN_REPORT.0, 0)
+    }
+
+    fn feed(tracker: &mut TouchTracker, events: &[InputEvent]) -> Option<TouchFrame> {
+        events.iter().filter_map(|e| tracker.process(e)).last()
+    }
+
+    #[test]
+    fn test_slots_and_centroid() {
+        let mut t = TouchTracker::new((0, 1000), (0, 500), 2);
+        use AbsoluteAxisType as A;
+
+        let frame = feed(
+            &mut t,
+            &[
+                abs(A::ABS_MT_SLOT, 0),
+                abs(A::ABS_MT_TRACKING_ID, 7),
+                abs(A::ABS_MT_POSITION_X, 200),
+                abs(A::ABS_MT_POSITION_Y, 100),
+                abs(A::ABS_MT_SLOT, 1),
+                abs(A::ABS_MT_TRACKING_ID, 8),
+                abs(A::ABS_MT_POSITION_X, 400),
+                abs(A::ABS_MT_POSITION_Y, 300),
+                syn(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(frame.fingers, 2);
+        assert_eq!(frame.position, (0.3, 0.4));
+
+        // Unchanged frames are not repeated
+        assert_eq!(feed(&mut t, &[syn()]), None);
+
+        // Only two slots, but the pad counts three fingers
+        let frame = feed(&mut t, &[key(Key::BTN_TOOL_TRIPLETAP, 1), syn()]).unwrap();
+        assert_eq!(frame.fingers, 3);
+
+        let frame = feed(
+            &mut t,
+            &[
+                key(Key::BTN_TOOL_TRIPLETAP, 0),
+                abs(A::ABS_MT_TRACKING_ID, -1),
+                abs(A::ABS_MT_SLOT, 0),
+                abs(A::ABS_MT_TRACKING_ID, -1),
+                syn(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(frame.fingers, 0);
+        assert_eq!(frame.position, (0.3, 0.4));
+    }
+}
@@ -1,10 +1,15 @@
 //! Configuration management with hot-reload support
 
-use anyhow::{Context, Result};
+use crate::persist;
+use crate::validate::{self, Invalid, Issue, Severity};
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
@@ -38,6 +43,10 @@ pub enum ItemType {
 /// Configuration for the launcher
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema version of the file, upgraded by [`Config::migrate`] on load
+    #[serde(default)]
+    pub version: u32,
+
     /// Pinned programs (user-selected)
     #[serde(default)]
     pub pinned_programs: Vec<LaunchItem>,
@@ -72,8 +81,234 @@ pub struct Config {
     /// UI settings
     #[serde(default)]
     pub ui: UiConfig,
+
+    /// How frequent programs are ranked
+    #[serde(default)]
+    pub ranking: RankingConfig,
+
+    /// Named popup layouts, selected by gesture, schedule or the popup header
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<PopupProfile>,
+
+    /// Profile used when no gesture or schedule picks one (global layout if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+
+    /// Cross-machine sync through a shared directory (off if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncConfig>,
+
+    /// Files adding pins, shortcuts and profiles (glob patterns, relative to
+    /// the config directory), e.g. `shortcuts.d/*.toml`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// What the `include` files added, left out when saving
+    #[serde(skip)]
+    pub(crate) included: Fragment,
+}
+
+/// What an `include` file may add to the config
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Fragment {
+    #[serde(default)]
+    pinned_programs: Vec<LaunchItem>,
+    #[serde(default)]
+    pinned_documents: Vec<LaunchItem>,
+    #[serde(default)]
+    shortcuts: Vec<LaunchItem>,
+    #[serde(default)]
+    pinned_clipboard: Vec<String>,
+    #[serde(default)]
+    profiles: Vec<PopupProfile>,
 }
 
+/// Config file format, by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(ConfigFormat::Json),
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Parse into JSON values, so migrations work the same for every format
+    pub fn parse(self, content: &str) -> Result<Value> {
+        let value = match self {
+            ConfigFormat::Json => serde_json::from_str(content)?,
+            ConfigFormat::Toml => toml::from_str(content)?,
+            // An empty YAML file is `null`
+            ConfigFormat::Yaml => match serde_norway::from_str(content)? {
+                Value::Null => Value::Object(Map::new()),
+                value => value,
+            },
+        };
+        Ok(value)
+    }
+
+    pub fn to_string<T: Serialize>(self, value: &T) -> Result<String> {
+        let content = match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)?,
+            ConfigFormat::Toml => toml::to_string_pretty(value)?,
+            ConfigFormat::Yaml => serde_norway::to_string(value)?,
+        };
+        Ok(content)
+    }
+
+    /// Serialize over the file's current `existing` content
+    ///
+    /// TOML is edited in place, so comments and layout of unchanged settings
+    /// survive; the other formats (and TOML that doesn't parse) are rewritten.
+    pub fn update<T: Serialize>(self, existing: &str, value: &T) -> Result<String> {
+        match (self, existing.parse::<toml_edit::DocumentMut>()) {
+            (ConfigFormat::Toml, Ok(mut document)) => {
+                let toml_edit::Value::InlineTable(new) =
+                    value.serialize(toml_edit::ser::ValueSerializer::new())?
+                else {
+                    bail!("Config doesn't serialize to a TOML table");
+                };
+                merge_toml_table(document.as_table_mut(), new);
+                Ok(document.to_string())
+            }
+            _ => self.to_string(value),
+        }
+    }
+}
+
+/// Replace the entries of `old` with `new`'s, keeping the decor of those that remain
+fn merge_toml_table(old: &mut dyn toml_edit::TableLike, new: toml_edit::InlineTable) {
+    let stale: Vec<String> = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in stale {
+        old.remove(&key);
+    }
+    for (key, value) in new {
+        match old.get_mut(&key) {
+            Some(item) => merge_toml_item(item, value),
+            None => {
+                old.insert(&key, expand_toml(value));
+            }
+        }
+    }
+}
+
+fn merge_toml_item(old: &mut toml_edit::Item, new: toml_edit::Value) {
+    use toml_edit::{Item, Value};
+    match (old, new) {
+        (Item::Value(old), new) => merge_toml_value(old, new),
+        (Item::Table(old), Value::InlineTable(new)) => merge_toml_table(old, new),
+        (Item::ArrayOfTables(old), Value::Array(new)) if new.iter().all(Value::is_inline_table) => {
+            while old.len() > new.len() {
+                old.remove(old.len() - 1);
+            }
+            for (i, value) in new.into_iter().enumerate() {
+                let Value::InlineTable(value) = value else {
+                    continue;
+                };
+                match old.get_mut(i) {
+                    Some(table) => merge_toml_table(table, value),
+                    None => old.push(expand_toml_table(value)),
+                }
+            }
+        }
+        (old, new) => *old = expand_toml(new),
+    }
+}
+
+fn merge_toml_value(old: &mut toml_edit::Value, new: toml_edit::Value) {
+    use toml_edit::Value;
+    match (old, new) {
+        (Value::InlineTable(old), Value::InlineTable(new)) => merge_toml_table(old, new),
+        (Value::Array(old), Value::Array(new)) => {
+            while old.len() > new.len() {
+                old.remove(old.len() - 1);
+            }
+            for (i, mut value) in new.into_iter().enumerate() {
+                match old.get_mut(i) {
+                    Some(item) => merge_toml_value(item, value),
+                    None => {
+                        // Continue the array's spacing, e.g. one item per line
+                        // (a first item has no leading space on a single line)
+                        if let Some(last) = old.iter().last() {
+                            let prefix = last.decor().prefix().and_then(|p| p.as_str());
+                            if old.len() >= 2 || prefix.is_some_and(|p| p.contains('\n')) {
+                                *value.decor_mut() = last.decor().clone();
+                            }
+                        }
+                        old.push_formatted(value);
+                    }
+                }
+            }
+        }
+        (old, new) if same_toml_scalar(old, &new) => {}
+        (old, mut new) => {
+            *new.decor_mut() = old.decor().clone();
+            *old = new;
+        }
+    }
+}
+
+fn same_toml_scalar(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    use toml_edit::Value;
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        _ => false,
+    }
+}
+
+/// Lay out a new setting the way `toml::to_string_pretty` would: tables get headers
+fn expand_toml(value: toml_edit::Value) -> toml_edit::Item {
+    use toml_edit::{ArrayOfTables, Item, Value};
+    match value {
+        Value::InlineTable(table) => Item::Table(expand_toml_table(table)),
+        Value::Array(array) if !array.is_empty() && array.iter().all(Value::is_inline_table) => {
+            let mut tables = ArrayOfTables::new();
+            for value in array {
+                if let Value::InlineTable(table) = value {
+                    tables.push(expand_toml_table(table));
+                }
+            }
+            Item::ArrayOfTables(tables)
+        }
+        value => Item::Value(value),
+    }
+}
+
+fn expand_toml_table(table: toml_edit::InlineTable) -> toml_edit::Table {
+    let mut expanded = toml_edit::Table::new();
+    for (key, value) in table {
+        expanded.insert(&key, expand_toml(value));
+    }
+    expanded
+}
+
+/// Where a migration keeps the original of a version `version` file
+fn version_backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    path.with_file_name(name)
+}
+
+/// Main config files, highest precedence first; only the first that exists is read
+pub const CONFIG_FILES: [&str; 4] = ["config.toml", "config.yaml", "config.yml", "config.json"];
+
 fn default_max_frequent() -> usize {
     5
 }
@@ -91,6 +326,41 @@ pub struct TriggerConfig {
     /// Debounce time to prevent accidental triggers (ms)
     #[serde(default = "default_debounce")]
     pub debounce_ms: u64,
+
+    /// Gestures that open the popup (default: simultaneous L+R click)
+    #[serde(default = "default_gestures")]
+    pub gestures: Vec<GestureConfig>,
+
+    /// Grab mice and re-emit their events so chord clicks don't reach applications
+    #[serde(default)]
+    pub grab_devices: bool,
+
+    /// Per-device include/exclude rules and threshold overrides (first match wins)
+    #[serde(default)]
+    pub devices: Vec<DeviceRule>,
+
+    /// Only listen to devices matched by a non-excluding rule in `devices`
+    #[serde(default)]
+    pub only_listed_devices: bool,
+
+    /// Where input events are read from
+    #[serde(default)]
+    pub backend: InputBackendKind,
+}
+
+/// Input event source
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputBackendKind {
+    /// First working backend: evdev, libinput, X11
+    #[default]
+    Auto,
+    /// `/dev/input` devices (needs the `input` group)
+    Evdev,
+    /// libinput on `/dev/input` devices (needs the `input` group)
+    Libinput,
+    /// X11 XInput2 raw events (no special permissions, X11/XWayland only)
+    X11,
 }
 
 fn default_simultaneous_threshold() -> u64 {
@@ -101,15 +371,240 @@ fn default_debounce() -> u64 {
     500
 }
 
+fn default_gestures() -> Vec<GestureConfig> {
+    vec![GestureConfig {
+        gesture: Gesture::Chord {
+            buttons: vec![MouseButton::Left, MouseButton::Right],
+        },
+        profile: None,
+    }]
+}
+
 impl Default for TriggerConfig {
     fn default() -> Self {
         Self {
             simultaneous_threshold_ms: default_simultaneous_threshold(),
             debounce_ms: default_debounce(),
+            gestures: default_gestures(),
+            grab_devices: false,
+            devices: vec![],
+            only_listed_devices: false,
+            backend: InputBackendKind::Auto,
         }
     }
 }
 
+impl TriggerConfig {
+    /// Thresholds `(simultaneous_threshold_ms, debounce_ms)` for a device, or
+    /// `None` if the device should be ignored
+    pub fn device_thresholds(&self, device: &DeviceIdentity) -> Option<(u64, u64)> {
+        match self.devices.iter().find(|rule| rule.matches(device)) {
+            Some(rule) if rule.exclude => None,
+            Some(rule) => Some((
+                rule.simultaneous_threshold_ms
+                    .unwrap_or(self.simultaneous_threshold_ms),
+                rule.debounce_ms.unwrap_or(self.debounce_ms),
+            )),
+            None if self.only_listed_devices => None,
+            None => Some((self.simultaneous_threshold_ms, self.debounce_ms)),
+        }
+    }
+}
+
+/// What a [`DeviceRule`] is matched against
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceIdentity {
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    /// Physical path, e.g. `usb-0000:00:14.0-2/input0`
+    pub phys: String,
+}
+
+/// Matches input devices; all given criteria must match
+///
+/// A rule without criteria matches every device.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DeviceRule {
+    /// Case-insensitive substring of the device name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Vendor id in hex, as shown by `lsusb` (e.g. `"046d"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+
+    /// Product id in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+
+    /// Substring of the physical path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phys: Option<String>,
+
+    /// Ignore matching devices
+    #[serde(default)]
+    pub exclude: bool,
+
+    /// Override `simultaneous_threshold_ms` for chords completed on this device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub simultaneous_threshold_ms: Option<u64>,
+
+    /// Override `debounce_ms` for triggers from this device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debounce_ms: Option<u64>,
+}
+
+impl DeviceRule {
+    pub fn matches(&self, device: &DeviceIdentity) -> bool {
+        let hex_matches = |id: &Option<String>, value: u16| {
+            id.as_deref().is_none_or(|id| {
+                u16::from_str_radix(id.trim_start_matches("0x"), 16).ok() == Some(value)
+            })
+        };
+        self.name
+            .as_deref()
+            .is_none_or(|name| device.name.to_lowercase().contains(&name.to_lowercase()))
+            && hex_matches(&self.vendor, device.vendor)
+            && hex_matches(&self.product, device.product)
+            && self
+                .phys
+                .as_deref()
+                .is_none_or(|phys| device.phys.contains(phys))
+    }
+}
+
+/// A trigger gesture and the popup profile it opens
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GestureConfig {
+    #[serde(flatten)]
+    pub gesture: Gesture,
+
+    /// Popup profile to open (default layout if unset)
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Gesture {
+    /// Press all buttons within `simultaneous_threshold_ms`
+    Chord { buttons: Vec<MouseButton> },
+    /// Click a button while holding keyboard modifiers
+    ModifierClick {
+        modifiers: Vec<Modifier>,
+        button: MouseButton,
+    },
+    /// Hold a button down
+    LongPress {
+        button: MouseButton,
+        #[serde(default = "default_long_press")]
+        duration_ms: u64,
+    },
+    /// Click the same button twice
+    DoubleClick {
+        button: MouseButton,
+        #[serde(default = "default_double_click")]
+        interval_ms: u64,
+    },
+    /// Move the pointer into a screen corner
+    HotCorner {
+        corner: Corner,
+        #[serde(default)]
+        dwell_ms: u64,
+    },
+    /// Press a keyboard key while holding modifiers, e.g. Super+Space
+    ///
+    /// `key` is a Linux key name without the `KEY_` prefix (`space`, `f12`, `a`).
+    Hotkey {
+        #[serde(default)]
+        modifiers: Vec<Modifier>,
+        key: String,
+    },
+    /// Tap a modifier key twice with no other key in between
+    DoubleTap {
+        modifier: Modifier,
+        #[serde(default = "default_double_click")]
+        interval_ms: u64,
+    },
+    /// Tap several fingers on a touchpad or touchscreen
+    TouchTap {
+        fingers: u8,
+        #[serde(default = "default_tap")]
+        max_duration_ms: u64,
+    },
+    /// Swipe several fingers; `distance` is a fraction of the device size
+    TouchSwipe {
+        fingers: u8,
+        direction: Direction,
+        #[serde(default = "default_swipe_distance")]
+        distance: f64,
+    },
+    /// Hold several fingers still
+    TouchHold {
+        fingers: u8,
+        #[serde(default = "default_long_press")]
+        duration_ms: u64,
+    },
+}
+
+fn default_long_press() -> u64 {
+    600
+}
+
+fn default_double_click() -> u64 {
+    300
+}
+
+fn default_tap() -> u64 {
+    250
+}
+
+fn default_swipe_distance() -> f64 {
+    0.15
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// Side button (`BTN_SIDE`), usually "back"
+    Side,
+    /// Extra button (`BTN_EXTRA`), usually "forward"
+    Extra,
+    Forward,
+    Back,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UiConfig {
     /// Window width
@@ -123,6 +618,23 @@ pub struct UiConfig {
     /// Dark mode (always true for now)
     #[serde(default = "default_dark_mode")]
     pub dark_mode: bool,
+
+    /// Where the popup opens (default: at the cursor)
+    #[serde(default)]
+    pub placement: Placement,
+}
+
+/// Popup placement mode
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Placement {
+    /// At the cursor, flipped left/up near monitor edges
+    #[default]
+    AtCursor,
+    /// Centered on the monitor with the cursor
+    Centered,
+    /// In a corner of the monitor with the cursor
+    Corner { corner: Corner },
 }
 
 fn default_width() -> f32 {
@@ -143,13 +655,230 @@ impl Default for UiConfig {
             width: default_width(),
             margin: default_margin(),
             dark_mode: default_dark_mode(),
+            placement: Placement::default(),
         }
     }
 }
 
+/// Weights for blending usage signals into one Frequent Programs ranking
+///
+/// Each signal is scaled to 0..1 (relative to its top item) before weighting,
+/// so the weights compare the signals, not their units.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankingConfig {
+    /// Weight of launches from the popup (recency-weighted)
+    #[serde(default = "default_weight")]
+    pub usage_weight: f64,
+
+    /// Weight of platform signals (shell history, etc.)
+    #[serde(default = "default_weight")]
+    pub platform_weight: f64,
+
+    /// Weight of past picks for the typed query when ranking search results
+    #[serde(default = "default_weight")]
+    pub query_weight: f64,
+
+    /// Age at which a use weighs half as much, per kind of usage
+    #[serde(default)]
+    pub half_life_days: HalfLifeConfig,
+
+    /// Extra weight of past uses in the current time-of-day bucket (0 disables)
+    #[serde(default = "default_period_weight")]
+    pub time_of_day_weight: f64,
+
+    /// Extra weight of past uses on the current weekday (0 disables)
+    #[serde(default = "default_period_weight")]
+    pub weekday_weight: f64,
+
+    /// Hours per time-of-day bucket (4: night, early morning, morning, ...)
+    #[serde(default = "default_bucket_hours")]
+    pub time_of_day_bucket_hours: u32,
+}
+
+/// Half-lives in days for usage scores
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HalfLifeConfig {
+    /// Programs and shortcuts
+    #[serde(default = "default_half_life")]
+    pub programs: f64,
+
+    #[serde(default = "default_half_life")]
+    pub documents: f64,
+
+    /// Clipboard entries pasted over a focused app
+    #[serde(default = "default_half_life")]
+    pub snippets: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_period_weight() -> f64 {
+    0.5
+}
+
+fn default_bucket_hours() -> u32 {
+    4
+}
+
+fn default_half_life() -> f64 {
+    7.0
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            usage_weight: default_weight(),
+            platform_weight: default_weight(),
+            query_weight: default_weight(),
+            half_life_days: HalfLifeConfig::default(),
+            time_of_day_weight: default_period_weight(),
+            weekday_weight: default_period_weight(),
+            time_of_day_bucket_hours: default_bucket_hours(),
+        }
+    }
+}
+
+impl Default for HalfLifeConfig {
+    fn default() -> Self {
+        Self {
+            programs: default_half_life(),
+            documents: default_half_life(),
+            snippets: default_half_life(),
+        }
+    }
+}
+
+/// Sync of pins, shortcuts, pinned snippets and usage between machines
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncConfig {
+    /// Directory shared between machines (Syncthing, Nextcloud, a git checkout);
+    /// a leading `~/` is the home directory
+    pub dir: PathBuf,
+
+    /// Name of this machine's file in `dir` (default: the host name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine: Option<String>,
+
+    /// Path prefixes as other machines write them, mapped to this machine's
+    /// (e.g. `"/home/alice"` to `"/Users/alice"`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub path_overrides: BTreeMap<String, String>,
+}
+
+/// A section of the popup
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    PinnedPrograms,
+    FrequentPrograms,
+    PinnedDocuments,
+    RecentDocuments,
+    Shortcuts,
+    Clipboard,
+}
+
+impl Section {
+    /// All sections, in the default order
+    pub const ALL: [Section; 6] = [
+        Section::PinnedPrograms,
+        Section::FrequentPrograms,
+        Section::PinnedDocuments,
+        Section::RecentDocuments,
+        Section::Shortcuts,
+        Section::Clipboard,
+    ];
+}
+
+fn default_sections() -> Vec<Section> {
+    Section::ALL.to_vec()
+}
+
+/// A named popup layout; unset fields fall back to the global settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PopupProfile {
+    pub name: String,
+
+    /// Sections to show, in order
+    #[serde(default = "default_sections")]
+    pub sections: Vec<Section>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_frequent_programs: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_frequent_documents: Option<usize>,
+
+    /// Clipboard entries shown (the rest is reachable through search)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_clipboard_items: Option<usize>,
+
+    /// Pins of this profile, instead of the global ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_programs: Option<Vec<LaunchItem>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_documents: Option<Vec<LaunchItem>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shortcuts: Option<Vec<LaunchItem>>,
+
+    /// When this profile opens by default (first matching profile wins)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<Schedule>,
+}
+
+/// A daily time window, e.g. `09:00`-`17:30` on weekdays
+///
+/// Windows may wrap past midnight (`22:00`-`06:00`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Schedule {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+
+    /// Days the window starts on (default: every day)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+}
+
+impl Schedule {
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let (in_window, day) = if self.from <= self.to {
+            (self.from <= time && time < self.to, now.weekday())
+        } else if time >= self.from {
+            (true, now.weekday())
+        } else {
+            // After midnight: the window started the day before
+            (time < self.to, now.weekday().pred())
+        };
+        in_window && (self.days.is_empty() || self.days.contains(&day))
+    }
+}
+
+/// The effective popup layout: a profile with global fallbacks applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    /// Profile name (`None` for the global layout)
+    pub profile: Option<String>,
+    pub sections: Vec<Section>,
+    pub max_frequent_programs: usize,
+    pub max_frequent_documents: usize,
+    pub max_clipboard_items: usize,
+    pub pinned_programs: Vec<LaunchItem>,
+    pub pinned_documents: Vec<LaunchItem>,
+    pub shortcuts: Vec<LaunchItem>,
+}
+
+fn default_clipboard_items() -> usize {
+    10
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             pinned_programs: vec![],
             pinned_documents: vec![],
             pinned_clipboard: vec![],
@@ -177,6 +906,135 @@ impl Default for Config {
             max_clipboard_history: default_max_clipboard(),
             trigger: TriggerConfig::default(),
             ui: UiConfig::default(),
+            ranking: RankingConfig::default(),
+            profiles: vec![],
+            default_profile: None,
+            sync: None,
+            include: vec![],
+            included: Fragment::default(),
+        }
+    }
+}
+
+/// Current config schema version
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Upgrade steps; the one at index N upgrades version N to N + 1
+const MIGRATIONS: [fn(&mut Map<String, Value>); CONFIG_VERSION as usize] = [migrate_v0];
+
+/// Version 0: files without a version, including those of the Python launchers
+///
+/// `launcher.pyw` writes the same layout, but may write `null` for lists and
+/// item types in other cases. `python_simple_windows` has a flat `items`
+/// list, which becomes pinned programs and documents (separators dropped).
+fn migrate_v0(config: &mut Map<String, Value>) {
+    if let Some(Value::Array(items)) = config.remove("items") {
+        for item in items {
+            let Value::Object(mut item) = item else {
+                continue;
+            };
+            let path = item.get("path").and_then(Value::as_str).unwrap_or("");
+            if path.is_empty() || item.get("separator") == Some(&Value::Bool(true)) {
+                continue;
+            }
+            let section = if is_program_path(path) {
+                "pinned_programs"
+            } else {
+                "pinned_documents"
+            };
+            item.remove("separator");
+            let pins = config
+                .entry(section)
+                .or_insert_with(|| Value::Array(vec![]));
+            if let Value::Array(pins) = pins {
+                pins.push(Value::Object(item));
+            }
+        }
+    }
+
+    // Nulls where the launcher expects a value fall back to the default
+    config.retain(|_, value| !value.is_null());
+    for section in ["pinned_programs", "pinned_documents", "shortcuts"] {
+        let Some(Value::Array(items)) = config.get_mut(section) else {
+            continue;
+        };
+        for item in items.iter_mut().filter_map(Value::as_object_mut) {
+            item.retain(|_, value| !value.is_null());
+            if let Some(Value::String(kind)) = item.get_mut("item_type") {
+                *kind = kind.to_lowercase();
+                if !["program", "document", "shortcut"].contains(&kind.as_str()) {
+                    item.remove("item_type");
+                }
+            }
+        }
+    }
+}
+
+/// Whether a Windows launcher item runs a program (rather than opening a file)
+fn is_program_path(path: &str) -> bool {
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    match extension {
+        None => true,
+        Some(ext) => ["exe", "bat", "cmd", "com", "lnk", "msc", "ps1"].contains(&ext.as_str()),
+    }
+}
+
+fn without_version(value: &Value) -> Value {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+        object.remove("version");
+    }
+    value
+}
+
+/// Read an `include` file of any config format
+fn load_fragment(path: &Path) -> Result<Fragment, Issue> {
+    let format = ConfigFormat::from_path(path)
+        .ok_or_else(|| Issue::error(path, None, "unknown config format".to_string()))?;
+    let content = fs::read_to_string(path)
+        .map_err(|e| Issue::error(path, None, format!("failed to read: {}", e)))?;
+    validate::parse(path, &content, format).map(|(fragment, _)| fragment)
+}
+
+/// Append the items whose key isn't taken yet, remembering them in `added`
+fn add_new<T: Clone>(items: &mut Vec<T>, added: &mut Vec<T>, new: Vec<T>, key: fn(&T) -> String) {
+    for item in new {
+        if !items.iter().any(|existing| key(existing) == key(&item)) {
+            items.push(item.clone());
+            added.push(item);
+        }
+    }
+}
+
+fn remove_all<T: PartialEq>(items: &mut Vec<T>, remove: &[T]) {
+    items.retain(|item| !remove.contains(item));
+}
+
+/// Config of the Python launcher (`launcher.pyw`), if it lives elsewhere
+///
+/// It shares this launcher's location on Linux; on Windows it's in
+/// `%APPDATA%\launcher`, elsewhere in `~/.config/launcher`.
+fn legacy_config() -> Option<Value> {
+    let base = if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else {
+        directories::BaseDirs::new()?.home_dir().join(".config")
+    };
+    let path = base.join("launcher").join("config.json");
+    if Config::config_path().ok()? == path {
+        return None;
+    }
+    let content = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(value) => {
+            log::info!("Importing the Python launcher's config from {:?}", path);
+            Some(value)
+        }
+        Err(e) => {
+            log::warn!("Ignoring the Python launcher's config {:?}: {}", path, e);
+            None
         }
     }
 }
@@ -184,36 +1042,302 @@ impl Default for Config {
 impl Config {
     /// Get the config file path
     pub fn config_path() -> Result<PathBuf> {
+        Ok(Self::main_file(&Self::config_dir()?))
+    }
+
+    /// The first of [`CONFIG_FILES`] in `dir` that exists, `config.json` if none does
+    pub fn main_file(dir: &Path) -> PathBuf {
+        CONFIG_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| dir.join("config.json"))
+    }
+
+    /// Get the config directory
+    pub fn config_dir() -> Result<PathBuf> {
         let dirs = directories::ProjectDirs::from("com", "rmanov", "launcher")
             .context("Failed to determine config directory")?;
         let config_dir = dirs.config_dir();
         fs::create_dir_all(config_dir).context("Failed to create config directory")?;
-        Ok(config_dir.join("config.json"))
+        Ok(config_dir.to_path_buf())
+    }
+
+    fn format(path: &Path) -> Result<ConfigFormat> {
+        ConfigFormat::from_path(path)
+            .with_context(|| format!("Unknown config format of {:?}", path))
     }
 
     /// Load config from file, creating default if missing
+    ///
+    /// Older files that a migration changes are saved upgraded, keeping the
+    /// original as `<file>.v<version>.bak`. Without a file, the config of the Python
+    /// launcher is imported if there is one.
     pub fn load() -> Result<Self> {
+        Self::load_checked().map(|(config, _)| config)
+    }
+
+    /// Load config like [`Config::load`], also returning its warnings
+    ///
+    /// A file with errors (syntax, types, impossible values) fails with an
+    /// [`Invalid`] error listing all its issues.
+    pub fn load_checked() -> Result<(Self, Vec<Issue>)> {
         let path = Self::config_path()?;
+        let format = Self::format(&path)?;
+        let ignored = CONFIG_FILES
+            .iter()
+            .map(|name| path.with_file_name(name))
+            .filter(|other| *other != path && other.exists());
+        let mut issues: Vec<Issue> = ignored
+            .map(|other| {
+                let message = format!("ignored, {:?} is used instead", path);
+                Issue::warning(&other, None, message)
+            })
+            .collect();
 
-        if path.exists() {
-            let content = fs::read_to_string(&path)
-                .with_context(|| format!("Failed to read config from {:?}", path))?;
-            serde_json::from_str(&content)
-                .with_context(|| format!("Failed to parse config from {:?}", path))
-        } else {
-            let config = Config::default();
+        let (content, imported) = match fs::read_to_string(&path) {
+            Ok(content) => (content, false),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => match legacy_config() {
+                Some(value) => (serde_json::to_string_pretty(&value)?, true),
+                None => {
+                    let config = Config::default();
+                    config.save()?;
+                    return Ok((config, issues));
+                }
+            },
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+
+        let (config, version, upgraded) = match Self::parse_file(&path, &content, format) {
+            Ok((config, version, upgraded, warnings)) => {
+                issues.extend(warnings);
+                (config, version, upgraded)
+            }
+            Err(Invalid(errors)) => {
+                issues.extend(errors);
+                return Err(Invalid(issues).into());
+            }
+        };
+        if upgraded || imported {
+            if !imported {
+                let backup = version_backup_path(&path, version);
+                if !backup.exists() {
+                    fs::copy(&path, &backup)
+                        .with_context(|| format!("Failed to back up config to {:?}", backup))?;
+                }
+                log::info!(
+                    "Upgraded config from version {} to {} (original kept as {:?})",
+                    version,
+                    CONFIG_VERSION,
+                    backup
+                );
+            }
             config.save()?;
-            Ok(config)
         }
+        Ok((config, issues))
     }
 
-    /// Save config to file
+    /// Parse, migrate and check a config file, adding its includes
+    ///
+    /// Returns the config, the version the file had, whether the migration
+    /// changed it, and the warnings.
+    fn parse_file(
+        path: &Path,
+        content: &str,
+        format: ConfigFormat,
+    ) -> Result<(Self, u32, bool, Vec<Issue>), Invalid> {
+        let invalid = |issue: Issue| Invalid(vec![issue]);
+        let (mut value, _) = validate::parse::<Value>(path, content, format).map_err(invalid)?;
+        let original = value.clone();
+        let version = Self::migrate(&mut value)
+            .map_err(|e| invalid(Issue::error(path, None, e.to_string())))?;
+        // A file that only lacks `version` is left as written, comments and all
+        let upgraded = without_version(&value) != without_version(&original);
+        let (mut config, mut issues) = if upgraded {
+            validate::parse_value::<Config>(path, content, value)
+        } else {
+            // Parsed from the text itself, for positions of type errors
+            validate::parse::<Config>(path, content, format).map(|(mut config, issues)| {
+                config.version = version.max(CONFIG_VERSION);
+                (config, issues)
+            })
+        }
+        .map_err(invalid)?;
+
+        if let Some(dir) = path.parent() {
+            issues.extend(config.add_includes(dir));
+        }
+        issues.extend(validate::check(&config, path, content));
+        if issues.iter().any(|issue| issue.severity == Severity::Error) {
+            return Err(Invalid(issues));
+        }
+        Ok((config, version, upgraded, issues))
+    }
+
+    /// The newest backup of the config file without errors, if any
+    ///
+    /// Saved versions (`<file>.bak.N`) come first, then the originals kept
+    /// by migrations (`<file>.v<version>.bak`), newest version first.
+    pub fn load_backup() -> Option<Self> {
+        Self::load_backup_of(&Self::config_path().ok()?)
+    }
+
+    fn load_backup_of(path: &Path) -> Option<Self> {
+        let format = Self::format(path).ok()?;
+        let saved = (1..=persist::BACKUPS).map(|n| persist::backup_path(path, n));
+        let migrated = (0..CONFIG_VERSION)
+            .rev()
+            .map(|version| version_backup_path(path, version));
+        saved.chain(migrated).find_map(|backup| {
+            let content = fs::read_to_string(&backup).ok()?;
+            let (config, ..) = Self::parse_file(&backup, &content, format).ok()?;
+            log::warn!("Using the last working config, {:?}", backup);
+            Some(config)
+        })
+    }
+
+    /// Upgrade a config file's JSON to [`CONFIG_VERSION`]
+    ///
+    /// Returns the version the file had. Files from a newer launcher are
+    /// left as they are (settings this version doesn't know are dropped on
+    /// the next save).
+    pub fn migrate(value: &mut Value) -> Result<u32> {
+        let Some(object) = value.as_object_mut() else {
+            bail!("Config must be a JSON object");
+        };
+        let version = object.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+        if version > CONFIG_VERSION {
+            log::warn!(
+                "Config version {} is newer than this launcher's ({}); it won't be saved",
+                version,
+                CONFIG_VERSION
+            );
+            return Ok(version);
+        }
+        for migration in &MIGRATIONS[version as usize..] {
+            migration(object);
+        }
+        object.insert("version".to_string(), CONFIG_VERSION.into());
+        Ok(version)
+    }
+
+    /// Save config to file, in its format, without what `include` files added
+    ///
+    /// A TOML file keeps its comments; see [`ConfigFormat::update`]. A config
+    /// from a newer launcher isn't saved, as that would drop what this one
+    /// doesn't know.
     pub fn save(&self) -> Result<()> {
+        if self.version > CONFIG_VERSION {
+            bail!(
+                "Not saving the config: its version {} is newer than this launcher's ({})",
+                self.version,
+                CONFIG_VERSION
+            );
+        }
         let path = Self::config_path()?;
-        let content = serde_json::to_string_pretty(self)?;
-        fs::write(&path, content)
-            .with_context(|| format!("Failed to write config to {:?}", path))?;
-        Ok(())
+        let mut config = self.clone();
+        config.remove_included();
+        let format = Self::format(&path)?;
+        let content = match fs::read_to_string(&path) {
+            Ok(existing) => format.update(&existing, &config)?,
+            Err(_) => format.to_string(&config)?,
+        };
+        persist::write_atomic(&path, content.as_bytes(), persist::BACKUPS)
+            .with_context(|| format!("Failed to write config to {:?}", path))
+    }
+
+    /// Whether [`Config::save`] would drop comments from the main file
+    ///
+    /// YAML is rewritten whole; any `#` counts, even one inside a string.
+    pub fn save_drops_comments() -> Result<bool> {
+        let path = Self::config_path()?;
+        if Self::format(&path)? != ConfigFormat::Yaml {
+            return Ok(false);
+        }
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        Ok(content.contains('#'))
+    }
+
+    /// Add pins, shortcuts and profiles from the `include` files
+    ///
+    /// Patterns are expanded in order, files of a pattern in name order.
+    /// Items are appended after the main file's; an item whose name (or
+    /// snippet text) is already taken, by the main file or an earlier
+    /// include, is skipped. Unreadable files are skipped, returned as warnings
+    /// with the patterns that match nothing.
+    pub fn add_includes(&mut self, dir: &Path) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        let main = Self::main_file(dir);
+        for pattern in self.include.clone() {
+            let full = dir.join(&pattern);
+            let paths = match glob::glob(&full.to_string_lossy()) {
+                Ok(paths) => paths.filter_map(|path| path.ok()).collect::<Vec<_>>(),
+                Err(e) => {
+                    let message = format!("invalid include pattern `{}`: {}", pattern, e);
+                    issues.push(Issue::warning(&main, None, message));
+                    continue;
+                }
+            };
+            if paths.is_empty() {
+                let message = format!("include `{}` matches no files", pattern);
+                issues.push(Issue::warning(&main, None, message));
+            }
+            for path in paths {
+                match load_fragment(&path) {
+                    Ok(fragment) => self.add_fragment(fragment),
+                    Err(issue) => issues.push(Issue {
+                        severity: Severity::Warning,
+                        message: format!("{} (include skipped)", issue.message),
+                        ..issue
+                    }),
+                }
+            }
+        }
+        issues
+    }
+
+    fn add_fragment(&mut self, fragment: Fragment) {
+        let included = &mut self.included;
+        let name = |item: &LaunchItem| item.name.clone();
+        add_new(
+            &mut self.pinned_programs,
+            &mut included.pinned_programs,
+            fragment.pinned_programs,
+            name,
+        );
+        add_new(
+            &mut self.pinned_documents,
+            &mut included.pinned_documents,
+            fragment.pinned_documents,
+            name,
+        );
+        add_new(
+            &mut self.shortcuts,
+            &mut included.shortcuts,
+            fragment.shortcuts,
+            name,
+        );
+        add_new(
+            &mut self.pinned_clipboard,
+            &mut included.pinned_clipboard,
+            fragment.pinned_clipboard,
+            String::clone,
+        );
+        add_new(
+            &mut self.profiles,
+            &mut included.profiles,
+            fragment.profiles,
+            |profile| profile.name.clone(),
+        );
+    }
+
+    pub(crate) fn remove_included(&mut self) {
+        let included = std::mem::take(&mut self.included);
+        remove_all(&mut self.pinned_programs, &included.pinned_programs);
+        remove_all(&mut self.pinned_documents, &included.pinned_documents);
+        remove_all(&mut self.shortcuts, &included.shortcuts);
+        remove_all(&mut self.pinned_clipboard, &included.pinned_clipboard);
+        remove_all(&mut self.profiles, &included.profiles);
     }
 
     /// Pin a program
@@ -256,49 +1380,184 @@ impl Config {
     pub fn unpin_clipboard(&mut self, text: &str) {
         self.pinned_clipboard.retain(|t| t != text);
     }
+
+    /// Look up a profile by name
+    pub fn profile(&self, name: &str) -> Option<&PopupProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Pick the profile to open: the requested one (from a gesture), else
+    /// the first scheduled for `now`, else `default_profile`
+    pub fn select_profile(&self, requested: Option<&str>, now: NaiveDateTime) -> Option<String> {
+        if let Some(name) = requested {
+            if self.profile(name).is_some() {
+                return Some(name.to_string());
+            }
+            log::warn!("Unknown popup profile {:?}, using the default", name);
+        }
+        self.profiles
+            .iter()
+            .find(|p| p.schedule.iter().any(|s| s.contains(now)))
+            .map(|p| p.name.clone())
+            .or_else(|| {
+                self.default_profile
+                    .clone()
+                    .filter(|name| self.profile(name).is_some())
+            })
+    }
+
+    /// Layout of a profile, or the global layout for `None` or an unknown name
+    pub fn layout(&self, profile: Option<&str>) -> Layout {
+        let profile = profile.and_then(|name| self.profile(name));
+        let pick =
+            |own: Option<&Vec<LaunchItem>>, global: &Vec<LaunchItem>| own.unwrap_or(global).clone();
+        Layout {
+            profile: profile.map(|p| p.name.clone()),
+            sections: profile.map_or_else(default_sections, |p| p.sections.clone()),
+            max_frequent_programs: profile
+                .and_then(|p| p.max_frequent_programs)
+                .unwrap_or(self.max_frequent_programs),
+            max_frequent_documents: profile
+                .and_then(|p| p.max_frequent_documents)
+                .unwrap_or(self.max_frequent_documents),
+            max_clipboard_items: profile
+                .and_then(|p| p.max_clipboard_items)
+                .unwrap_or_else(default_clipboard_items),
+            pinned_programs: pick(
+                profile.and_then(|p| p.pinned_programs.as_ref()),
+                &self.pinned_programs,
+            ),
+            pinned_documents: pick(
+                profile.and_then(|p| p.pinned_documents.as_ref()),
+                &self.pinned_documents,
+            ),
+            shortcuts: pick(profile.and_then(|p| p.shortcuts.as_ref()), &self.shortcuts),
+        }
+    }
+
+    /// Pin an item in a profile that has its own pins, else globally
+    pub fn pin_in_profile(&mut self, profile: Option<&str>, item: LaunchItem) {
+        let own = profile
+            .and_then(|name| self.profiles.iter_mut().find(|p| p.name == name))
+            .and_then(|p| match item.item_type {
+                ItemType::Program | ItemType::Shortcut => p.pinned_programs.as_mut(),
+                ItemType::Document => p.pinned_documents.as_mut(),
+            });
+        match (own, item.item_type) {
+            (Some(pins), _) => {
+                if !pins.iter().any(|p| p.path == item.path) {
+                    pins.push(item);
+                }
+            }
+            (None, ItemType::Program | ItemType::Shortcut) => self.pin_program(item),
+            (None, ItemType::Document) => self.pin_document(item),
+        }
+    }
 }
 
 /// Configuration manager with hot-reload support
+///
+/// A config file with errors never replaces a working config: the manager
+/// keeps the last one that loaded (at startup, the newest good backup or the
+/// defaults) and reports the file's [`Issue`]s until they're fixed.
 pub struct ConfigManager {
     config: Arc<RwLock<Config>>,
+    issues: Arc<RwLock<Vec<Issue>>>,
     _watcher: Option<RecommendedWatcher>,
     reloaded: Arc<AtomicBool>,
 }
 
+/// Load the config file, recording its issues; `None` if it has errors
+fn load_into(issues: &RwLock<Vec<Issue>>) -> Option<Config> {
+    let (config, mut new_issues) = match Config::load_checked() {
+        Ok((config, issues)) => (Some(config), issues),
+        Err(e) => match e.downcast::<Invalid>() {
+            Ok(Invalid(issues)) => (None, issues),
+            Err(e) => {
+                let path = Config::config_path().unwrap_or_default();
+                (None, vec![Issue::error(&path, None, format!("{:#}", e))])
+            }
+        },
+    };
+    new_issues.sort_by_key(|issue| issue.severity);
+
+    let mut issues = issues
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if *issues != new_issues {
+        for issue in &new_issues {
+            match issue.severity {
+                Severity::Error => log::error!("{}", issue),
+                Severity::Warning => log::warn!("{}", issue),
+            }
+        }
+        if config.is_none() {
+            log::error!("Config has errors; keeping the last working config");
+        }
+        *issues = new_issues;
+    }
+    config
+}
+
+/// Whether `path` is the main config file in `dir` or matches one of its `include`s
+///
+/// Changes to anything else there, like the temporary and backup files of a
+/// save, don't need a reload.
+fn affects_config(dir: &Path, include: &[String], path: &Path) -> bool {
+    path == Config::main_file(dir)
+        || include.iter().any(|pattern| {
+            glob::Pattern::new(&dir.join(pattern).to_string_lossy())
+                .is_ok_and(|pattern| pattern.matches_path(path))
+        })
+}
+
 impl ConfigManager {
     /// Create a new config manager with file watching
     pub fn new() -> Result<Self> {
-        let config = Config::load()?;
+        let issues = Arc::new(RwLock::new(Vec::new()));
+        let config = load_into(&issues)
+            .or_else(Config::load_backup)
+            .unwrap_or_default();
         let config = Arc::new(RwLock::new(config));
         let reloaded = Arc::new(AtomicBool::new(false));
 
-        let config_path = Config::config_path()?;
+        let config_dir = Config::config_dir()?;
 
+        let watcher_dir = config_dir.clone();
         let watcher_config = config.clone();
+        let watcher_issues = issues.clone();
         let watcher_reloaded = reloaded.clone();
         let mut watcher = notify::recommended_watcher(move |res: Result<Event, _>| {
             if let Ok(event) = res {
-                if event.kind.is_modify() {
-                    if let Ok(new_config) = Config::load() {
+                let relevant = {
+                    let include = &watcher_config
+                        .read()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .include;
+                    event
+                        .paths
+                        .iter()
+                        .any(|path| affects_config(&watcher_dir, include, path))
+                };
+                if relevant && (event.kind.is_modify() || event.kind.is_create()) {
+                    if let Some(new_config) = load_into(&watcher_issues) {
                         if let Ok(mut cfg) = watcher_config.write() {
                             *cfg = new_config;
-                            watcher_reloaded.store(true, Ordering::SeqCst);
                             log::info!("Config hot-reloaded");
                         }
                     }
+                    // Also when the file broke, to show its errors
+                    watcher_reloaded.store(true, Ordering::SeqCst);
                 }
             }
         })?;
 
-        watcher.watch(
-            config_path
-                .parent()
-                .context("Config path has no parent directory")?,
-            RecursiveMode::NonRecursive,
-        )?;
+        // Recursive, for `include` files in subdirectories like `shortcuts.d`
+        watcher.watch(&config_dir, RecursiveMode::Recursive)?;
 
         Ok(Self {
             config,
+            issues,
             _watcher: Some(watcher),
             reloaded,
         })
@@ -312,11 +1571,32 @@ impl ConfigManager {
         })
     }
 
+    /// Problems in the config file, errors first
+    pub fn issues(&self) -> Vec<Issue> {
+        self.issues
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Whether the config file has errors (and the last working config is in use)
+    pub fn has_errors(&self) -> bool {
+        self.issues()
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
     /// Get a write lock and save after modification
+    ///
+    /// Fails while the file has errors, rather than overwriting it with the
+    /// last working config.
     pub fn modify<F>(&self, f: F) -> Result<()>
     where
         F: FnOnce(&mut Config),
     {
+        if self.has_errors() {
+            bail!("Not saving the config until the errors in its file are fixed");
+        }
         let mut config = self.config.write().unwrap_or_else(|poisoned| {
             log::warn!("Config lock was poisoned, recovering");
             poisoned.into_inner()
@@ -325,6 +1605,46 @@ impl ConfigManager {
         config.save()
     }
 
+    /// Change the config as read from its file and save, under the write lock
+    ///
+    /// For changes that must not be based on a stale in-memory config, since
+    /// the watcher may not have picked up an edit yet. `f` returns whether to
+    /// save; the in-memory config is replaced then.
+    pub fn modify_file<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Config) -> Result<bool>,
+    {
+        if self.has_errors() {
+            bail!("Not saving the config until the errors in its file are fixed");
+        }
+        let mut current = self.config.write().unwrap_or_else(|poisoned| {
+            log::warn!("Config lock was poisoned, recovering");
+            poisoned.into_inner()
+        });
+        let mut config = Config::load()?;
+        if f(&mut config)? {
+            config.save()?;
+            *current = config;
+            self.reloaded.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Re-read the config file now, without waiting for the file watcher
+    pub fn reload(&self) -> Result<()> {
+        let new_config = load_into(&self.issues);
+        self.reloaded.store(true, Ordering::SeqCst);
+        let new_config =
+            new_config.context("Config has errors; keeping the last working config")?;
+        let mut config = self.config.write().unwrap_or_else(|poisoned| {
+            log::warn!("Config lock was poisoned, recovering");
+            poisoned.into_inner()
+        });
+        *config = new_config;
+        log::info!("Config reloaded");
+        Ok(())
+    }
+
     /// Check if config was reloaded (non-blocking)
     pub fn check_reload(&self) -> bool {
         self.reloaded.swap(false, Ordering::SeqCst)
@@ -343,6 +1663,199 @@ mod tests {
         assert!(config.ui.dark_mode);
     }
 
+    #[test]
+    fn test_gesture_config() {
+        let json = r#"{
+            "gestures": [
+                {"type": "chord", "buttons": ["middle", "right"]},
+                {"type": "modifier_click", "modifiers": ["super"], "button": "left", "profile": "work"},
+                {"type": "long_press", "button": "side"},
+                {"type": "hot_corner", "corner": "top_left", "dwell_ms": 300},
+                {"type": "touch_swipe", "fingers": 4, "direction": "up"},
+                {"type": "hotkey", "modifiers": ["super"], "key": "space"},
+                {"type": "double_tap", "modifier": "ctrl"}
+            ]
+        }"#;
+        let trigger: TriggerConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(trigger.gestures.len(), 7);
+        assert_eq!(trigger.gestures[1].profile.as_deref(), Some("work"));
+        assert_eq!(
+            trigger.gestures[2].gesture,
+            Gesture::LongPress {
+                button: MouseButton::Side,
+                duration_ms: 600
+            }
+        );
+
+        assert_eq!(
+            trigger.gestures[4].gesture,
+            Gesture::TouchSwipe {
+                fingers: 4,
+                direction: Direction::Up,
+                distance: 0.15
+            }
+        );
+
+        assert_eq!(
+            trigger.gestures[6].gesture,
+            Gesture::DoubleTap {
+                modifier: Modifier::Ctrl,
+                interval_ms: 300
+            }
+        );
+
+        // Old configs without gestures keep the L+R chord
+        let trigger: TriggerConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(trigger.gestures, default_gestures());
+        assert_eq!(trigger.backend, InputBackendKind::Auto);
+    }
+
+    #[test]
+    fn test_device_rules() {
+        let json = r#"{
+            "simultaneous_threshold_ms": 100,
+            "devices": [
+                {"name": "touchpad", "exclude": true},
+                {"vendor": "0x047d", "product": "2048", "simultaneous_threshold_ms": 350}
+            ]
+        }"#;
+        let trigger: TriggerConfig = serde_json::from_str(json).unwrap();
+
+        let touchpad = DeviceIdentity {
+            name: "SynPS/2 Synaptics TouchPad".to_string(),
+            ..Default::default()
+        };
+        let trackball = DeviceIdentity {
+            name: "Kensington Expert Mouse".to_string(),
+            vendor: 0x047d,
+            product: 0x2048,
+            phys: "usb-0000:00:14.0-2/input0".to_string(),
+        };
+        let mouse = DeviceIdentity {
+            name: "Logitech USB Mouse".to_string(),
+            vendor: 0x046d,
+            ..Default::default()
+        };
+
+        assert_eq!(trigger.device_thresholds(&touchpad), None);
+        assert_eq!(trigger.device_thresholds(&trackball), Some((350, 500)));
+        assert_eq!(trigger.device_thresholds(&mouse), Some((100, 500)));
+
+        let only_listed = TriggerConfig {
+            only_listed_devices: true,
+            ..trigger
+        };
+        assert_eq!(only_listed.device_thresholds(&mouse), None);
+        assert_eq!(only_listed.device_thresholds(&trackball), Some((350, 500)));
+    }
+
+    #[test]
+    fn test_placement_config() {
+        let ui: UiConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(ui.placement, Placement::AtCursor);
+
+        let json = r#"{"placement": {"mode": "corner", "corner": "bottom_right"}}"#;
+        let ui: UiConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            ui.placement,
+            Placement::Corner {
+                corner: Corner::BottomRight
+            }
+        );
+    }
+
+    #[test]
+    fn test_popup_profiles() {
+        let json = r#"{
+            "pinned_programs": [{"name": "Firefox", "path": "firefox"}],
+            "profiles": [
+                {
+                    "name": "work",
+                    "sections": ["pinned_programs", "recent_documents"],
+                    "max_frequent_documents": 8,
+                    "pinned_programs": [{"name": "Slack", "path": "slack"}],
+                    "schedule": [{"from": "09:00", "to": "17:30", "days": ["mon", "tue", "wed", "thu", "fri"]}]
+                },
+                {"name": "clipboard-only", "sections": ["clipboard"], "max_clipboard_items": 20},
+                {"name": "night", "schedule": [{"from": "22:00", "to": "06:00", "days": ["fri"]}]}
+            ],
+            "default_profile": "clipboard-only"
+        }"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        let at = |date: &str| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap();
+
+        // 2024-01-01 is a Monday
+        let monday_noon = at("2024-01-01 12:00");
+        assert_eq!(
+            config.select_profile(None, monday_noon).as_deref(),
+            Some("work")
+        );
+        assert_eq!(
+            config
+                .select_profile(Some("clipboard-only"), monday_noon)
+                .as_deref(),
+            Some("clipboard-only")
+        );
+        // Unknown names fall back to the schedule
+        assert_eq!(
+            config
+                .select_profile(Some("gaming"), monday_noon)
+                .as_deref(),
+            Some("work")
+        );
+        assert_eq!(
+            config
+                .select_profile(None, at("2024-01-06 12:00"))
+                .as_deref(),
+            Some("clipboard-only")
+        );
+        // Friday night until Saturday morning
+        assert_eq!(
+            config
+                .select_profile(None, at("2024-01-06 03:00"))
+                .as_deref(),
+            Some("night")
+        );
+        assert_eq!(
+            config
+                .select_profile(None, at("2024-01-02 03:00"))
+                .as_deref(),
+            Some("clipboard-only")
+        );
+
+        let work = config.layout(Some("work"));
+        assert_eq!(
+            work.sections,
+            vec![Section::PinnedPrograms, Section::RecentDocuments]
+        );
+        assert_eq!(work.pinned_programs[0].name, "Slack");
+        assert_eq!(work.max_frequent_documents, 8);
+        assert_eq!(work.max_frequent_programs, 5);
+        assert_eq!(work.shortcuts, config.shortcuts);
+
+        let global = config.layout(None);
+        assert_eq!(global.sections, Section::ALL.to_vec());
+        assert_eq!(global.pinned_programs[0].name, "Firefox");
+        assert_eq!(
+            config.layout(Some("clipboard-only")).max_clipboard_items,
+            20
+        );
+
+        // Pins go to the profile's own list when it has one
+        let mut config = config;
+        let item = |name: &str| LaunchItem {
+            name: name.to_string(),
+            path: name.to_lowercase(),
+            icon: None,
+            args: vec![],
+            item_type: ItemType::Program,
+        };
+        config.pin_in_profile(Some("work"), item("Zoom"));
+        config.pin_in_profile(Some("night"), item("Steam"));
+        assert_eq!(config.layout(Some("work")).pinned_programs.len(), 2);
+        assert_eq!(config.pinned_programs.len(), 2);
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = Config::default();
@@ -350,4 +1863,274 @@ mod tests {
         let parsed: Config = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed.max_frequent_programs, config.max_frequent_programs);
     }
+
+    #[test]
+    fn test_migrations() {
+        let migrate = |json: &str| {
+            let mut value: Value = serde_json::from_str(json).unwrap();
+            let version = Config::migrate(&mut value).unwrap();
+            (version, serde_json::from_value::<Config>(value).unwrap())
+        };
+
+        // launcher.pyw
+        let (version, config) = migrate(
+            r#"{
+                "pinned_programs": [{"name": "Firefox", "path": "firefox", "icon": null, "args": null}],
+                "shortcuts": [{"name": "Lock", "path": "loginctl", "item_type": "Shortcut"},
+                              {"name": "Odd", "path": "odd", "item_type": "widget"}],
+                "pinned_clipboard": null,
+                "trigger": {"simultaneous_threshold_ms": 80, "debounce_ms": 500},
+                "ui": {"width": 320, "dark_mode": false}
+            }"#,
+        );
+        assert_eq!(version, 0);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.pinned_programs[0].name, "Firefox");
+        assert_eq!(config.shortcuts[0].item_type, ItemType::Shortcut);
+        assert_eq!(config.shortcuts[1].item_type, ItemType::Program);
+        assert!(config.pinned_clipboard.is_empty());
+        assert_eq!(config.trigger.simultaneous_threshold_ms, 80);
+        assert_eq!(config.ui.width, 320.0);
+
+        // python_simple_windows
+        let (_, config) = migrate(
+            r#"{"items": [
+                {"name": "Notepad", "path": "notepad.exe", "icon": "📝"},
+                {"name": "─────", "path": "", "icon": " ", "separator": true},
+                {"name": "Report", "path": "C:\\Users\\me\\report.docx"},
+                {"name": "Explorer", "path": "explorer"}
+            ]}"#,
+        );
+        let names = |items: &[LaunchItem]| -> Vec<String> {
+            items.iter().map(|item| item.name.clone()).collect()
+        };
+        assert_eq!(names(&config.pinned_programs), ["Notepad", "Explorer"]);
+        assert_eq!(names(&config.pinned_documents), ["Report"]);
+        assert_eq!(config.pinned_programs[0].icon.as_deref(), Some("📝"));
+
+        // Current and newer files are left alone
+        let (version, config) = migrate(r#"{"version": 1, "max_frequent_programs": 7}"#);
+        assert_eq!((version, config.max_frequent_programs), (1, 7));
+        let (version, config) = migrate(r#"{"version": 99}"#);
+        assert_eq!((version, config.version), (99, 99));
+        assert!(Config::migrate(&mut Value::Array(vec![])).is_err());
+    }
+
+    #[test]
+    fn test_formats_and_includes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("shortcuts.d")).unwrap();
+
+        // Every format round-trips the whole config
+        let config = Config::default();
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let content = format.to_string(&config).unwrap();
+            let parsed: Config = serde_json::from_value(format.parse(&content).unwrap()).unwrap();
+            assert_eq!(parsed.shortcuts, config.shortcuts, "{:?}", format);
+            assert_eq!(
+                parsed.trigger.gestures, config.trigger.gestures,
+                "{:?}",
+                format
+            );
+        }
+        assert_eq!(ConfigFormat::Yaml.parse("").unwrap(), serde_json::json!({}));
+
+        // TOML wins over JSON
+        assert_eq!(Config::main_file(dir), dir.join("config.json"));
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        fs::write(dir.join("config.toml"), "").unwrap();
+        assert_eq!(Config::main_file(dir), dir.join("config.toml"));
+
+        let main = r#"
+            # Comments are fine here
+            include = ["shortcuts.d/*.toml", "extra.yaml", "missing/*.json"]
+
+            [[shortcuts]]
+            name = "Lock"
+            path = "loginctl"
+            args = ["lock-session"]
+        "#;
+        fs::write(
+            dir.join("shortcuts.d/10-team.toml"),
+            r#"
+            [[shortcuts]]
+            name = "Lock"
+            path = "xdg-screensaver"
+
+            [[shortcuts]]
+            name = "VPN"
+            path = "nmcli"
+            args = ["con", "up", "work"]
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("shortcuts.d/20-bad.toml"),
+            "max_frequent_programs = 3",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("extra.yaml"),
+            "pinned_clipboard:\n  - git status\n",
+        )
+        .unwrap();
+
+        let value = ConfigFormat::Toml.parse(main).unwrap();
+        let mut config: Config = serde_json::from_value(value).unwrap();
+        let issues = config.add_includes(dir);
+        let messages: Vec<&str> = issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "unknown field `max_frequent_programs`, expected one of `pinned_programs`, \
+                 `pinned_documents`, `shortcuts`, `pinned_clipboard`, `profiles` (include skipped)",
+                "include `missing/*.json` matches no files"
+            ]
+        );
+        assert_eq!(issues[0].position, Some((1, 1)));
+        let names: Vec<&str> = config.shortcuts.iter().map(|s| s.name.as_str()).collect();
+        // The main file's "Lock" wins; the fragment with other settings is skipped
+        assert_eq!(names, ["Lock", "VPN"]);
+        assert_eq!(config.shortcuts[0].path, "loginctl");
+        assert_eq!(config.pinned_clipboard, ["git status"]);
+        assert_eq!(config.max_frequent_programs, 5);
+
+        // Saving leaves included items in their files
+        let mut saved = config.clone();
+        saved.remove_included();
+        assert_eq!(saved.shortcuts.len(), 1);
+        assert!(saved.pinned_clipboard.is_empty());
+        assert_eq!(saved.include.len(), 3);
+    }
+
+    #[test]
+    fn test_toml_update_keeps_comments() {
+        let existing = r#"# My launcher
+max_frequent_programs = 7 # a few more
+
+[trigger]
+# Faster double clicks
+debounce_ms = 80
+
+[[shortcuts]]
+name = "Lock" # screen
+path = "loginctl"
+args = [
+    "lock-session",
+]
+"#;
+        let mut config: Config =
+            serde_json::from_value(ConfigFormat::Toml.parse(existing).unwrap()).unwrap();
+        config.trigger.debounce_ms = 120;
+        config.shortcuts[0].args.push("now".to_string());
+        config.shortcuts.push(LaunchItem {
+            name: "VPN".to_string(),
+            path: "nmcli".to_string(),
+            icon: None,
+            args: vec![],
+            item_type: ItemType::Shortcut,
+        });
+        let updated = ConfigFormat::Toml.update(existing, &config).unwrap();
+
+        for kept in [
+            "# My launcher\n",
+            "max_frequent_programs = 7 # a few more\n",
+            "# Faster double clicks\ndebounce_ms = 120\n",
+            "name = \"Lock\" # screen\n",
+            "    \"lock-session\",\n    \"now\",\n]",
+        ] {
+            assert!(
+                updated.contains(kept),
+                "{:?} missing from\n{}",
+                kept,
+                updated
+            );
+        }
+        let parsed: Config =
+            serde_json::from_value(ConfigFormat::Toml.parse(&updated).unwrap()).unwrap();
+        assert_eq!(parsed.shortcuts, config.shortcuts);
+        assert_eq!(parsed.trigger.gestures, config.trigger.gestures);
+
+        // Removed entries go, and YAML is rewritten
+        config.shortcuts.clear();
+        let updated = ConfigFormat::Toml.update(&updated, &config).unwrap();
+        assert!(!updated.contains("[[shortcuts]]"), "{}", updated);
+        assert!(updated.contains("# Faster double clicks"));
+        let yaml = ConfigFormat::Yaml.update("# gone\n", &config).unwrap();
+        assert!(!yaml.contains("# gone"));
+    }
+
+    #[test]
+    fn test_watched_files() {
+        let dir = Path::new("/cfg");
+        let include = ["shortcuts.d/*.toml".to_string()];
+        assert!(affects_config(dir, &include, &dir.join("config.json")));
+        assert!(affects_config(
+            dir,
+            &include,
+            &dir.join("shortcuts.d/10-team.toml")
+        ));
+        for ignored in [
+            ".config.json.tmp.42.0",
+            "config.json.bak.1",
+            "config.json.v0.bak",
+            "usage.json",
+            "shortcuts.d/.10-team.toml.tmp.42.0",
+        ] {
+            assert!(
+                !affects_config(dir, &include, &dir.join(ignored)),
+                "{}",
+                ignored
+            );
+        }
+    }
+
+    #[test]
+    fn test_newer_version_not_saved() {
+        let path = Path::new("/cfg/config.json");
+        let content = format!(
+            r#"{{"version": {}, "max_frequent_programs": 3}}"#,
+            CONFIG_VERSION + 1
+        );
+        let (config, version, upgraded, _) =
+            Config::parse_file(path, &content, ConfigFormat::Json).unwrap();
+        assert_eq!(version, CONFIG_VERSION + 1);
+        assert!(!upgraded);
+        assert_eq!(config.version, CONFIG_VERSION + 1);
+        assert_eq!(config.max_frequent_programs, 3);
+
+        let error = config.save().unwrap_err();
+        assert!(error.to_string().contains("newer"), "{}", error);
+    }
+
+    #[test]
+    fn test_load_backup() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("config.json");
+        fs::write(&path, "{ broken").unwrap();
+        assert!(Config::load_backup_of(&path).is_none());
+
+        // Only the original from before the upgrade is left
+        fs::write(
+            version_backup_path(&path, 0),
+            r#"{"max_frequent_programs": 3}"#,
+        )
+        .unwrap();
+        let config = Config::load_backup_of(&path).unwrap();
+        assert_eq!(config.max_frequent_programs, 3);
+        assert_eq!(config.version, CONFIG_VERSION);
+
+        // A saved version is newer
+        fs::write(
+            persist::backup_path(&path, 2),
+            r#"{"max_frequent_programs": 4}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load_backup_of(&path).unwrap().max_frequent_programs,
+            4
+        );
+    }
 }
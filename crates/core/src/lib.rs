@@ -3,9 +3,22 @@
 //! Provides configuration management, usage tracking, and platform-specific data sources.
 
 pub mod config;
+pub mod persist;
 pub mod platform;
+pub mod stats;
+pub mod sync;
 pub mod usage;
+pub mod validate;
 
-pub use config::{Config, ConfigManager, ItemType, LaunchItem};
+pub use config::{
+    Config, ConfigManager, Corner, DeviceIdentity, DeviceRule, Direction, Gesture, GestureConfig,
+    HalfLifeConfig, InputBackendKind, ItemType, LaunchItem, Layout, Modifier, MouseButton,
+    Placement, PopupProfile, RankingConfig, Schedule, Section, SyncConfig, TriggerConfig,
+};
 pub use platform::PlatformDataSource;
-pub use usage::{UsageData, UsageRecord, UsageTracker};
+pub use stats::{Period, UsageStats};
+pub use usage::{
+    ContextUsage, DailyLaunches, Frecency, LaunchCounts, Scorer, UsageData, UsageKind, UsageRecord,
+    UsageTracker,
+};
+pub use validate::{Issue, Severity};
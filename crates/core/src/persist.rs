@@ -0,0 +1,228 @@
+//! Crash-safe JSON files shared by the config, usage and clipboard stores
+//!
+//! Writes go to a temporary file in the same directory, are fsynced and then
+//! renamed over the live file, so a crash leaves either the old or the new
+//! version, never a torn one. The replaced version is kept as a numbered
+//! backup (`usage.json.bak.1` is the newest). Loading falls back to the
+//! newest backup that parses if the live file is corrupt.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Backups kept per file
+pub const BACKUPS: usize = 3;
+
+/// Path of the `n`th backup of `path` (1 is the newest)
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak.{}", n));
+    path.with_file_name(name)
+}
+
+/// Numbers the temporary files of this process, for writes from several threads
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace `path` with `content` atomically, keeping `backups` old versions
+pub fn write_atomic(path: &Path, content: &[u8], backups: usize) -> Result<()> {
+    let dir = path.parent().context("Path has no parent directory")?;
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(
+        ".tmp.{}.{}",
+        std::process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(temp_name);
+
+    let written = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .with_context(|| format!("Failed to write {:?}", temp));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    if backups > 0 && path.exists() {
+        rotate_backups(path, backups);
+    }
+
+    fs::rename(&temp, path).with_context(|| format!("Failed to replace {:?}", path))?;
+    sync_dir(dir);
+    Ok(())
+}
+
+/// Shift backups up by one and keep the current file as backup 1
+fn rotate_backups(path: &Path, backups: usize) {
+    for n in (1..backups).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            let _ = fs::rename(&from, backup_path(path, n + 1));
+        }
+    }
+    // A hard link keeps the old contents once the live file is replaced,
+    // without copying; not every filesystem supports one
+    let newest = backup_path(path, 1);
+    let _ = fs::remove_file(&newest);
+    if fs::hard_link(path, &newest).is_err() {
+        if let Err(e) = fs::copy(path, &newest) {
+            log::warn!("Failed to back up {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Make a rename durable
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// Take an exclusive advisory lock for `path`, blocking until it's free
+///
+/// The lock is held on a `<file>.lock` next to it, since the file itself is
+/// replaced on every write; it is released when the returned file is dropped.
+pub fn lock(path: &Path) -> Result<File> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    let lock_path = path.with_file_name(name);
+    if let Some(dir) = lock_path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    let file = File::create(&lock_path)
+        .with_context(|| format!("Failed to open lock file {:?}", lock_path))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {:?}", lock_path))?;
+    Ok(file)
+}
+
+/// Serialize `value` as pretty JSON and write it with [`write_atomic`]
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let content = serde_json::to_string_pretty(value)?;
+    write_atomic(path, content.as_bytes(), BACKUPS)
+}
+
+/// Load JSON from `path`, recovering from the newest backup that parses
+///
+/// Returns `Ok(None)` if neither the file nor a backup exists, and an error
+/// only if the file exists but neither it nor any backup can be parsed.
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    load(path, |content| Ok(serde_json::from_str(content)?))
+}
+
+/// Like [`load_json`], for any format `parse` understands
+pub fn load<T>(path: &Path, parse: impl Fn(&str) -> Result<T>) -> Result<Option<T>> {
+    let error = match read(path, &parse) {
+        Ok(Some(value)) => return Ok(Some(value)),
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    for n in 1..=BACKUPS {
+        let backup = backup_path(path, n);
+        match read(&backup, &parse) {
+            Ok(Some(value)) => {
+                match &error {
+                    Some(e) => log::warn!("{:#}; recovered from {:?}", e, backup),
+                    None => log::warn!("{:?} is missing; recovered from {:?}", path, backup),
+                }
+                return Ok(Some(value));
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Backup unusable: {:#}", e),
+        }
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(None),
+    }
+}
+
+/// Read and parse one file; `Ok(None)` if it doesn't exist
+fn read<T>(path: &Path, parse: impl Fn(&str) -> Result<T>) -> Result<Option<T>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    parse(&content)
+        .map(Some)
+        .with_context(|| format!("Failed to parse {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_write_and_recovery() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("data.json");
+
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), None);
+        for version in 1..=5u32 {
+            save_json(&path, &vec![version]).unwrap();
+        }
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), Some(vec![5]));
+        // Backups hold the previous versions, newest first, at most BACKUPS
+        for n in 1..=BACKUPS {
+            let backup: Vec<u32> = load_json(&backup_path(&path, n)).unwrap().unwrap();
+            assert_eq!(backup, vec![5 - n as u32]);
+        }
+        assert!(!backup_path(&path, BACKUPS + 1).exists());
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1 + BACKUPS);
+
+        // A torn write is recovered from the newest backup
+        fs::write(&path, "[5, 6").unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), Some(vec![4]));
+        fs::write(backup_path(&path, 1), "").unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), Some(vec![3]));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), Some(vec![3]));
+
+        // Nothing parses: the error names the live file
+        fs::write(&path, "{").unwrap();
+        for n in 1..=BACKUPS {
+            fs::write(backup_path(&path, n), "{").unwrap();
+        }
+        let error = load_json::<Vec<u32>>(&path).unwrap_err();
+        assert!(format!("{:#}", error).contains("data.json\""));
+    }
+
+    #[test]
+    fn test_concurrent_writes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("data.json");
+
+        // Each write has its own temporary file, so none fails or is torn
+        std::thread::scope(|scope| {
+            for thread in 0..8u32 {
+                let path = &path;
+                scope.spawn(move || {
+                    for version in 0..20 {
+                        save_json(path, &vec![thread; version + 1]).unwrap();
+                    }
+                });
+            }
+        });
+        let saved: Vec<u32> = load_json(&path).unwrap().unwrap();
+        assert_eq!(saved.len(), 20);
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1 + BACKUPS);
+    }
+}
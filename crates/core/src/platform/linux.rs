@@ -17,6 +17,12 @@ pub struct LinuxDataSource {
     home_dir: PathBuf,
 }
 
+impl Default for LinuxDataSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LinuxDataSource {
     pub fn new() -> Self {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/home"));
@@ -261,6 +267,14 @@ impl PlatformDataSource for LinuxDataSource {
     }
 
     fn frequent_programs(&self, limit: usize) -> Result<Vec<LaunchItem>> {
+        Ok(self
+            .program_frequencies(limit)?
+            .into_iter()
+            .map(|(item, _)| item)
+            .collect())
+    }
+
+    fn program_frequencies(&self, limit: usize) -> Result<Vec<(LaunchItem, f64)>> {
         let frequency = self.get_shell_history_frequency()?;
         let apps = self.installed_apps()?;
 
@@ -280,9 +294,13 @@ impl PlatformDataSource for LinuxDataSource {
         freq_vec.sort_by(|a, b| b.1.cmp(a.1));
 
         // Get top apps that match shell commands
-        let result: Vec<LaunchItem> = freq_vec
+        let result: Vec<(LaunchItem, f64)> = freq_vec
             .iter()
-            .filter_map(|(cmd, _)| cmd_to_app.get(cmd.as_str()).map(|app| (*app).clone()))
+            .filter_map(|(cmd, count)| {
+                cmd_to_app
+                    .get(cmd.as_str())
+                    .map(|app| ((*app).clone(), **count as f64))
+            })
             .take(limit)
             .collect();
 
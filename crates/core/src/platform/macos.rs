@@ -248,6 +248,14 @@ impl PlatformDataSource for MacOSDataSource {
     }
 
     fn frequent_programs(&self, limit: usize) -> Result<Vec<LaunchItem>> {
+        Ok(self
+            .program_frequencies(limit)?
+            .into_iter()
+            .map(|(item, _)| item)
+            .collect())
+    }
+
+    fn program_frequencies(&self, limit: usize) -> Result<Vec<(LaunchItem, f64)>> {
         let frequency = self.get_shell_history_frequency()?;
         let apps = self.installed_apps()?;
 
@@ -266,11 +274,13 @@ impl PlatformDataSource for MacOSDataSource {
         freq_vec.sort_by(|a, b| b.1.cmp(a.1));
 
         // Get top apps that match shell commands
-        let result: Vec<LaunchItem> = freq_vec
+        let result: Vec<(LaunchItem, f64)> = freq_vec
             .iter()
-            .filter_map(|(cmd, _)| {
+            .filter_map(|(cmd, count)| {
                 let cmd_lower = cmd.to_lowercase();
-                cmd_to_app.get(&cmd_lower).map(|app| (*app).clone())
+                cmd_to_app
+                    .get(&cmd_lower)
+                    .map(|app| ((*app).clone(), **count as f64))
             })
             .take(limit)
             .collect();
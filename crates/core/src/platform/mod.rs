@@ -23,6 +23,20 @@ pub trait PlatformDataSource {
     /// Get frequently used programs (from shell history, etc.)
     fn frequent_programs(&self, limit: usize) -> Result<Vec<LaunchItem>>;
 
+    /// Frequently used programs with their frequency (higher is more frequent),
+    /// most frequent first
+    ///
+    /// Sources without counts rank by position in `frequent_programs`.
+    fn program_frequencies(&self, limit: usize) -> Result<Vec<(LaunchItem, f64)>> {
+        let programs = self.frequent_programs(limit)?;
+        let count = programs.len();
+        Ok(programs
+            .into_iter()
+            .enumerate()
+            .map(|(rank, item)| (item, (count - rank) as f64))
+            .collect())
+    }
+
     /// Launch an item
     fn launch(&self, item: &LaunchItem) -> Result<()>;
 }
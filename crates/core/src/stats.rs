@@ -0,0 +1,351 @@
+//! Usage statistics for reviews: launches per period, top documents, trends
+//! and clipboard paste counts, rendered as a table, CSV or JSON
+
+use crate::usage::{DailyLaunches, UsageData};
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// Length of a statistics period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    /// Monday to Sunday
+    Week,
+}
+
+impl Period {
+    /// First day of the period containing `date`
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+        }
+    }
+
+    fn days(self) -> u64 {
+        match self {
+            Period::Day => 1,
+            Period::Week => 7,
+        }
+    }
+}
+
+/// Launches of one program or document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemStats {
+    pub name: String,
+    pub path: String,
+    /// Launches per period, oldest first
+    pub launches: Vec<u32>,
+    /// Launches over all periods
+    pub total: u32,
+    /// Launches in the latest period minus the average of the earlier ones
+    pub trend: f64,
+}
+
+/// Paste count of one clipboard entry
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PasteStats {
+    pub text: String,
+    pub count: u32,
+}
+
+/// Statistics over the most recent periods
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageStats {
+    pub period: Period,
+    /// First day of each period, oldest first
+    pub periods: Vec<NaiveDate>,
+    /// Programs launched in the periods, most launched first
+    pub programs: Vec<ItemStats>,
+    /// Top documents opened in the periods, most opened first
+    pub documents: Vec<ItemStats>,
+    /// Top pasted clipboard entries (all time), most pasted first
+    pub clipboard: Vec<PasteStats>,
+}
+
+impl UsageStats {
+    /// Compute statistics for the `count` periods up to and including `now`
+    ///
+    /// Launches are counted by the local date they happened on, from
+    /// [`UsageData::daily`], which keeps the whole history.
+    /// `pastes` are clipboard entries with their paste counts. Documents and
+    /// clipboard entries are limited to the `top` most used.
+    pub fn compute<Tz: TimeZone>(
+        data: &UsageData,
+        pastes: &[(String, u32)],
+        period: Period,
+        count: usize,
+        now: &DateTime<Tz>,
+        top: usize,
+    ) -> Self {
+        let last = period.start(now.date_naive());
+        let periods: Vec<NaiveDate> = (0..count as u64)
+            .rev()
+            .map(|back| last - Days::new(back * period.days()))
+            .collect();
+
+        let items = |counts: &HashMap<String, DailyLaunches>| -> Vec<ItemStats> {
+            let mut items: Vec<ItemStats> = counts
+                .iter()
+                .map(|(path, launches)| item_stats(path, launches, period, &periods))
+                .filter(|item| item.total > 0)
+                .collect();
+            items.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+            items
+        };
+        let programs = items(&data.daily.programs);
+        let mut documents = items(&data.daily.documents);
+        documents.truncate(top);
+
+        let mut clipboard: Vec<PasteStats> = pastes
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(text, count)| PasteStats {
+                text: text.clone(),
+                count: *count,
+            })
+            .collect();
+        clipboard.sort_by_key(|paste| std::cmp::Reverse(paste.count));
+        clipboard.truncate(top);
+
+        Self {
+            period,
+            periods,
+            programs,
+            documents,
+            clipboard,
+        }
+    }
+
+    /// Long-format CSV: `kind,name,path,period,count`, one row per item and
+    /// period (clipboard rows have no path and no period)
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,name,path,period,count\n");
+        for (kind, items) in [("program", &self.programs), ("document", &self.documents)] {
+            for item in items {
+                for (start, launches) in self.periods.iter().zip(&item.launches) {
+                    let _ = writeln!(
+                        csv,
+                        "{},{},{},{},{}",
+                        kind,
+                        csv_field(&item.name),
+                        csv_field(&item.path),
+                        start,
+                        launches
+                    );
+                }
+            }
+        }
+        for paste in &self.clipboard {
+            let _ = writeln!(
+                csv,
+                "clipboard,{},,,{}",
+                csv_field(&paste.text),
+                paste.count
+            );
+        }
+        csv
+    }
+
+    /// Human-readable tables
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let header: Vec<String> = self
+            .periods
+            .iter()
+            .map(|start| start.format("%m-%d").to_string())
+            .collect();
+
+        let unit = match self.period {
+            Period::Day => "day",
+            Period::Week => "week",
+        };
+
+        for (title, items) in [("Programs", &self.programs), ("Documents", &self.documents)] {
+            let _ = writeln!(text, "{} (launches per {})", title, unit);
+            if items.is_empty() {
+                let _ = writeln!(text, "  (none)\n");
+                continue;
+            }
+            let _ = writeln!(
+                text,
+                "  {:<24} {} {:>6} {:>6}",
+                "",
+                header
+                    .iter()
+                    .map(|h| format!("{:>6}", h))
+                    .collect::<String>(),
+                "total",
+                "trend"
+            );
+            for item in items {
+                let counts: String = item.launches.iter().map(|n| format!("{:>6}", n)).collect();
+                let _ = writeln!(
+                    text,
+                    "  {:<24} {} {:>6} {:>+6.1}",
+                    truncate(&item.name, 24),
+                    counts,
+                    item.total,
+                    item.trend
+                );
+            }
+            text.push('\n');
+        }
+
+        let _ = writeln!(text, "Clipboard (pastes, all time)");
+        if self.clipboard.is_empty() {
+            let _ = writeln!(text, "  (none)");
+        }
+        for paste in &self.clipboard {
+            let line = paste.text.replace('\n', " ");
+            let _ = writeln!(text, "  {:>5}  {}", paste.count, truncate(&line, 60));
+        }
+        text
+    }
+}
+
+/// Count an item's launches per period
+fn item_stats(
+    path: &str,
+    daily: &DailyLaunches,
+    period: Period,
+    periods: &[NaiveDate],
+) -> ItemStats {
+    let mut launches = vec![0u32; periods.len()];
+    for day in daily.days.keys() {
+        if let Ok(index) = periods.binary_search(&period.start(*day)) {
+            launches[index] += daily.on(*day);
+        }
+    }
+    let total = launches.iter().sum();
+    let trend = match launches.split_last() {
+        Some((latest, earlier)) if !earlier.is_empty() => {
+            let average = earlier.iter().sum::<u32>() as f64 / earlier.len() as f64;
+            *latest as f64 - average
+        }
+        _ => 0.0,
+    };
+    ItemStats {
+        name: daily.name.clone(),
+        path: path.to_string(),
+        launches,
+        total,
+        trend,
+    }
+}
+
+/// Quote a CSV field if needed (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Shorten to `max` characters with an ellipsis
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    /// Counts of `(date, machine, launches)`
+    fn counts(name: &str, days: &[(&str, &str, u32)]) -> (String, DailyLaunches) {
+        let mut launches = DailyLaunches {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        for (day, machine, count) in days {
+            launches
+                .days
+                .entry(day.parse().unwrap())
+                .or_default()
+                .insert(machine.to_string(), *count);
+        }
+        (format!("/usr/bin/{}", name.to_lowercase()), launches)
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let mut data = UsageData::default();
+        data.daily.programs.extend([
+            counts(
+                "Firefox",
+                &[
+                    ("2024-06-03", "desktop", 1),
+                    ("2024-06-04", "desktop", 1),
+                    // Both machines' launches count
+                    ("2024-06-05", "desktop", 1),
+                    ("2024-06-05", "laptop", 1),
+                ],
+            ),
+            counts("Kitty", &[("2024-06-05", "laptop", 1)]),
+            // Before the window
+            counts("Gimp", &[("2024-05-01", "desktop", 1)]),
+        ]);
+        data.daily
+            .documents
+            .extend([counts("report.pdf", &[("2024-06-05", "desktop", 1)])]);
+        let pastes = vec![
+            ("git status".to_string(), 3),
+            ("never pasted".to_string(), 0),
+            ("a, \"quoted\"".to_string(), 5),
+        ];
+        let now = FixedOffset::east_opt(2 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 5, 18, 0, 0)
+            .unwrap();
+
+        let stats = UsageStats::compute(&data, &pastes, Period::Day, 3, &now, 10);
+        assert_eq!(
+            stats.periods,
+            [
+                NaiveDate::from_ymd_opt(2024, 6, 3).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 6, 4).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 6, 5).unwrap(),
+            ]
+        );
+        assert_eq!(stats.programs.len(), 2);
+        assert_eq!(stats.programs[0].name, "Firefox");
+        assert_eq!(stats.programs[0].launches, [1, 1, 2]);
+        assert_eq!(stats.programs[0].trend, 1.0);
+        assert_eq!(stats.programs[1].launches, [0, 0, 1]);
+        assert_eq!(stats.documents[0].total, 1);
+        assert_eq!(stats.clipboard.len(), 2);
+        assert_eq!(stats.clipboard[0].count, 5);
+
+        // Weeks start on Monday
+        let weekly = UsageStats::compute(&data, &pastes, Period::Week, 2, &now, 10);
+        assert_eq!(
+            weekly.periods[1],
+            NaiveDate::from_ymd_opt(2024, 6, 3).unwrap()
+        );
+        assert_eq!(weekly.programs[0].launches, [0, 4]);
+
+        let csv = stats.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "kind,name,path,period,count");
+        assert!(lines.contains(&"program,Firefox,/usr/bin/firefox,2024-06-05,2"));
+        assert!(lines.contains(&"clipboard,\"a, \"\"quoted\"\"\",,,5"));
+        // 3 periods for 2 programs and 1 document, plus 2 clipboard rows
+        assert_eq!(lines.len(), 1 + 9 + 2);
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["period"], "day");
+        assert_eq!(json["periods"][0], "2024-06-03");
+        assert!(stats.to_text().contains("Firefox"));
+    }
+}
@@ -0,0 +1,564 @@
+//! Cross-machine sync of pins, shortcuts, pinned snippets and usage
+//!
+//! Every machine writes only its own `<machine>.json` in the sync directory,
+//! so file sync tools (Syncthing, Nextcloud, git) never see two machines edit
+//! the same file. A machine file holds the merged state as that machine last
+//! saw it:
+//!
+//! - pins, shortcuts and pinned snippets as last-writer-wins entries keyed by
+//!   name (snippets by text), with removals kept as tombstones
+//! - usage data, merged as a union of launch timestamps per path, and the
+//!   per-day launch counts of each machine
+//!
+//! Both merges are commutative and idempotent, so machines converge whatever
+//! order files arrive in, and conflict copies made by a sync tool merge like
+//! any other machine file. Paths in other machines' files are rewritten
+//! through [`SyncConfig::path_overrides`].
+
+use crate::config::{Config, ConfigManager, LaunchItem, SyncConfig};
+use crate::persist;
+use crate::usage::{DailyLaunches, UsageData, UsageRecord};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// A synced value, or a tombstone once removed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Entry<T> {
+    /// `None` once removed
+    pub value: Option<T>,
+    pub updated: DateTime<Utc>,
+    /// Machine that made the change, breaking ties between equal timestamps
+    pub machine: String,
+}
+
+impl<T> Entry<T> {
+    fn newer_than(&self, other: &Entry<T>) -> bool {
+        (self.updated, &self.machine) > (other.updated, &other.machine)
+    }
+}
+
+/// Synced entries keyed by item name (or snippet text)
+pub type Entries<T> = BTreeMap<String, Entry<T>>;
+
+/// What a machine shares through its file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub pinned_programs: Entries<LaunchItem>,
+    #[serde(default)]
+    pub pinned_documents: Entries<LaunchItem>,
+    #[serde(default)]
+    pub shortcuts: Entries<LaunchItem>,
+    #[serde(default)]
+    pub pinned_clipboard: Entries<String>,
+    #[serde(default)]
+    pub usage: UsageData,
+}
+
+fn item_key(item: &LaunchItem) -> String {
+    item.name.clone()
+}
+
+impl SyncState {
+    /// Record local additions, edits and removals as changes made at `now`
+    pub fn record(
+        &mut self,
+        config: &Config,
+        usage: &UsageData,
+        now: DateTime<Utc>,
+        machine: &str,
+    ) {
+        // Items from `include` files are distributed with those files
+        let mut config = config.clone();
+        config.remove_included();
+        let record = |entries: &mut Entries<LaunchItem>, items: &[LaunchItem]| {
+            record_entries(entries, items, item_key, now, machine)
+        };
+        record(&mut self.pinned_programs, &config.pinned_programs);
+        record(&mut self.pinned_documents, &config.pinned_documents);
+        record(&mut self.shortcuts, &config.shortcuts);
+        record_entries(
+            &mut self.pinned_clipboard,
+            &config.pinned_clipboard,
+            String::clone,
+            now,
+            machine,
+        );
+        self.usage.merge(usage);
+    }
+
+    /// Merge another machine's state into this one
+    pub fn merge(&mut self, other: &SyncState) {
+        merge_entries(&mut self.pinned_programs, &other.pinned_programs);
+        merge_entries(&mut self.pinned_documents, &other.pinned_documents);
+        merge_entries(&mut self.shortcuts, &other.shortcuts);
+        merge_entries(&mut self.pinned_clipboard, &other.pinned_clipboard);
+        self.usage.merge(&other.usage);
+    }
+
+    /// Update the config to the synced pins; returns whether anything changed
+    pub fn apply(&self, config: &mut Config) -> bool {
+        let mut changed =
+            apply_entries(&mut config.pinned_programs, &self.pinned_programs, item_key);
+        changed |= apply_entries(
+            &mut config.pinned_documents,
+            &self.pinned_documents,
+            item_key,
+        );
+        changed |= apply_entries(&mut config.shortcuts, &self.shortcuts, item_key);
+        changed |= apply_entries(
+            &mut config.pinned_clipboard,
+            &self.pinned_clipboard,
+            String::clone,
+        );
+        changed
+    }
+
+    /// Rewrite another machine's paths to this machine's
+    pub fn localize(&mut self, overrides: &BTreeMap<String, String>) {
+        if overrides.is_empty() {
+            return;
+        }
+        for entries in [
+            &mut self.pinned_programs,
+            &mut self.pinned_documents,
+            &mut self.shortcuts,
+        ] {
+            for item in entries
+                .values_mut()
+                .filter_map(|entry| entry.value.as_mut())
+            {
+                item.path = localize(&item.path, overrides);
+            }
+        }
+        let usage = &mut self.usage;
+        localize_records(&mut usage.programs, overrides);
+        localize_records(&mut usage.documents, overrides);
+        localize_counts(&mut usage.daily.programs, overrides);
+        localize_counts(&mut usage.daily.documents, overrides);
+        for context in usage.contexts.values_mut() {
+            localize_records(&mut context.programs, overrides);
+        }
+        for picks in usage.selections.values_mut() {
+            localize_records(picks, overrides);
+        }
+    }
+}
+
+fn record_entries<T: Clone + PartialEq>(
+    entries: &mut Entries<T>,
+    items: &[T],
+    key: fn(&T) -> String,
+    now: DateTime<Utc>,
+    machine: &str,
+) {
+    let change = |value: Option<T>| Entry {
+        value,
+        updated: now,
+        machine: machine.to_string(),
+    };
+    let mut present = HashSet::new();
+    for item in items {
+        let key = key(item);
+        if entries.get(&key).and_then(|entry| entry.value.as_ref()) != Some(item) {
+            entries.insert(key.clone(), change(Some(item.clone())));
+        }
+        present.insert(key);
+    }
+    for (key, entry) in entries.iter_mut() {
+        if entry.value.is_some() && !present.contains(key) {
+            *entry = change(None);
+        }
+    }
+}
+
+fn merge_entries<T: Clone>(entries: &mut Entries<T>, other: &Entries<T>) {
+    for (key, entry) in other {
+        let newer = entries
+            .get(key)
+            .is_none_or(|existing| entry.newer_than(existing));
+        if newer {
+            entries.insert(key.clone(), entry.clone());
+        }
+    }
+}
+
+/// Replace `items` with the live entries, keeping the local order and
+/// appending items added elsewhere, oldest first
+fn apply_entries<T: Clone + PartialEq>(
+    items: &mut Vec<T>,
+    entries: &Entries<T>,
+    key: fn(&T) -> String,
+) -> bool {
+    let mut seen = HashSet::new();
+    let mut synced: Vec<T> = items
+        .iter()
+        .filter(|item| seen.insert(key(item)))
+        .filter_map(|item| match entries.get(&key(item)) {
+            Some(entry) => entry.value.clone(),
+            None => Some(item.clone()),
+        })
+        .collect();
+    let mut added: Vec<&Entry<T>> = entries
+        .iter()
+        .filter(|(key, entry)| entry.value.is_some() && !seen.contains(*key))
+        .map(|(_, entry)| entry)
+        .collect();
+    added.sort_by_key(|entry| entry.updated);
+    synced.extend(added.into_iter().filter_map(|entry| entry.value.clone()));
+
+    let changed = synced != *items;
+    *items = synced;
+    changed
+}
+
+/// Apply the longest matching prefix override; prefixes end at a path
+/// separator, a space (before arguments) or the end of the path
+fn localize(path: &str, overrides: &BTreeMap<String, String>) -> String {
+    overrides
+        .iter()
+        .filter(|(from, _)| {
+            path.strip_prefix(from.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\', ' ']))
+        })
+        .max_by_key(|(from, _)| from.len())
+        .map(|(from, to)| format!("{}{}", to, &path[from.len()..]))
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn localize_records(
+    records: &mut HashMap<String, UsageRecord>,
+    overrides: &BTreeMap<String, String>,
+) {
+    for (path, mut record) in std::mem::take(records) {
+        record.path = localize(&record.path, overrides);
+        let path = localize(&path, overrides);
+        match records.get_mut(&path) {
+            Some(existing) => existing.merge(&record),
+            None => {
+                records.insert(path, record);
+            }
+        }
+    }
+}
+
+fn localize_counts(
+    counts: &mut HashMap<String, DailyLaunches>,
+    overrides: &BTreeMap<String, String>,
+) {
+    for (path, launches) in std::mem::take(counts) {
+        counts
+            .entry(localize(&path, overrides))
+            .or_default()
+            .merge(&launches);
+    }
+}
+
+/// What a sync changed locally
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+    config: bool,
+    usage: bool,
+}
+
+/// Sync `config` and `usage` with the machine files in `dir`
+fn sync_dir(
+    dir: &Path,
+    machine: &str,
+    overrides: &BTreeMap<String, String>,
+    config: &mut Config,
+    usage: &mut UsageData,
+    now: DateTime<Utc>,
+) -> Result<Changes> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let own_path = dir.join(format!("{}.json", machine));
+    let mut state: SyncState = persist::load_json(&own_path)?.unwrap_or_default();
+    state.record(config, usage, now, machine);
+
+    for path in machine_files(dir)? {
+        if path == own_path {
+            continue;
+        }
+        match persist::load_json::<SyncState>(&path) {
+            Ok(Some(mut remote)) => {
+                remote.localize(overrides);
+                state.merge(&remote);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Skipping sync file: {:#}", e),
+        }
+    }
+
+    let usage_before = serde_json::to_value(&*usage)?;
+    usage.merge(&state.usage);
+    let changes = Changes {
+        config: state.apply(config),
+        usage: serde_json::to_value(&*usage)? != usage_before,
+    };
+
+    // Keys are sorted in a `Value`, so an unchanged state serializes the
+    // same and other machines aren't woken up for nothing
+    let content = serde_json::to_string_pretty(&serde_json::to_value(&state)?)?;
+    if fs::read_to_string(&own_path).ok().as_deref() != Some(content.as_str()) {
+        // No backups: they would be synced to every machine
+        persist::write_atomic(&own_path, content.as_bytes(), 0)?;
+    }
+    Ok(changes)
+}
+
+/// Machine files are `*.json`; temporary files start with a dot
+fn is_machine_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    name.ends_with(".json") && !name.starts_with('.')
+}
+
+fn machine_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {:?}", dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_machine_file(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The sync directory with a leading `~` expanded
+pub fn sync_dir_path(settings: &SyncConfig) -> PathBuf {
+    match settings.dir.strip_prefix("~") {
+        Ok(rest) => directories::BaseDirs::new()
+            .map(|dirs| dirs.home_dir().join(rest))
+            .unwrap_or_else(|| settings.dir.clone()),
+        Err(_) => settings.dir.clone(),
+    }
+}
+
+/// This machine's file name (without `.json`)
+pub fn machine_name(settings: &SyncConfig) -> String {
+    let name = settings
+        .machine
+        .clone()
+        .or_else(hostname)
+        .unwrap_or_else(|| "default".to_string());
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let output = Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Sync the config file and `usage.json` with the sync directory
+pub fn run(settings: &SyncConfig, config_manager: &ConfigManager) -> Result<()> {
+    let mut usage = UsageData::load()?;
+    let mut usage_changed = false;
+    // Under the config's write lock, so a pin made meanwhile isn't lost
+    config_manager.modify_file(|config| {
+        let changes = sync_dir(
+            &sync_dir_path(settings),
+            &machine_name(settings),
+            &settings.path_overrides,
+            config,
+            &mut usage,
+            Utc::now(),
+        )?;
+        usage_changed = changes.usage;
+        if changes.config && Config::save_drops_comments()? {
+            log::warn!(
+                "Not applying pins and shortcuts from other machines: \
+                 saving would drop the comments in the YAML config"
+            );
+            return Ok(false);
+        }
+        if changes.config {
+            log::info!("Applied pins and shortcuts from other machines");
+        }
+        Ok(changes.config)
+    })?;
+
+    if usage_changed {
+        usage.save()?;
+    }
+    Ok(())
+}
+
+/// Sync now and again whenever another machine's file or the config changes
+///
+/// Returns the watcher, which must be kept alive; `None` if sync is off.
+/// Changing the sync settings takes effect on restart.
+pub fn start(config_manager: Arc<ConfigManager>) -> Result<Option<RecommendedWatcher>> {
+    let Some(settings) = config_manager.get().sync.clone() else {
+        return Ok(None);
+    };
+    let dir = sync_dir_path(&settings);
+    let own_path = dir.join(format!("{}.json", machine_name(&settings)));
+    log::info!("Syncing with {:?} as {:?}", dir, own_path);
+    if let Err(e) = run(&settings, &config_manager) {
+        log::warn!("Sync failed: {:#}", e);
+    }
+
+    let config_path = Config::config_path()?;
+    let config_dir = config_path
+        .parent()
+        .context("Config path has no parent directory")?
+        .to_path_buf();
+    let watcher_settings = settings.clone();
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, _>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if !(event.kind.is_modify() || event.kind.is_create()) {
+            return;
+        }
+        let relevant = event
+            .paths
+            .iter()
+            .any(|path| *path == config_path || (*path != own_path && is_machine_file(path)));
+        if relevant {
+            if let Err(e) = run(&watcher_settings, &config_manager) {
+                log::warn!("Sync failed: {:#}", e);
+            }
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    watcher.watch(&config_dir, RecursiveMode::NonRecursive)?;
+    Ok(Some(watcher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, path: &str) -> LaunchItem {
+        LaunchItem {
+            name: name.to_string(),
+            path: path.to_string(),
+            icon: None,
+            args: vec![],
+            item_type: Default::default(),
+        }
+    }
+
+    fn names(items: &[LaunchItem]) -> Vec<&str> {
+        items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sync_between_machines() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let at = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        let home = BTreeMap::from([("/home/alice".to_string(), "/Users/alice".to_string())]);
+
+        let mut desktop = Config {
+            pinned_programs: vec![
+                item("Firefox", "firefox"),
+                item("Tool", "/home/alice/bin/tool"),
+            ],
+            pinned_clipboard: vec!["git status".to_string()],
+            shortcuts: vec![],
+            ..Config::default()
+        };
+        let mut desktop_usage = UsageData::default();
+        desktop_usage.record_program_launch(&item("Tool", "/home/alice/bin/tool --fast"));
+        let mut laptop = Config {
+            pinned_programs: vec![item("Kitty", "kitty")],
+            shortcuts: vec![],
+            ..Config::default()
+        };
+        let mut laptop_usage = UsageData::default();
+
+        let sync = |machine, config: &mut Config, usage: &mut UsageData, secs, overrides| {
+            sync_dir(dir, machine, overrides, config, usage, at(secs)).unwrap()
+        };
+        let none = BTreeMap::new();
+        sync("desktop", &mut desktop, &mut desktop_usage, 0, &none);
+        let changes = sync("laptop", &mut laptop, &mut laptop_usage, 1, &home);
+        assert_eq!(
+            changes,
+            Changes {
+                config: true,
+                usage: true
+            }
+        );
+        assert_eq!(names(&laptop.pinned_programs), ["Kitty", "Firefox", "Tool"]);
+        assert_eq!(laptop.pinned_programs[2].path, "/Users/alice/bin/tool");
+        assert_eq!(laptop.pinned_clipboard, ["git status"]);
+        assert!(laptop_usage
+            .programs
+            .contains_key("/Users/alice/bin/tool --fast"));
+
+        // Nothing new: nothing changes and the file isn't rewritten
+        let laptop_file = dir.join("laptop.json");
+        let written = fs::metadata(&laptop_file).unwrap().modified().unwrap();
+        let changes = sync("laptop", &mut laptop, &mut laptop_usage, 2, &home);
+        assert_eq!(changes, Changes::default());
+        assert_eq!(
+            fs::metadata(&laptop_file).unwrap().modified().unwrap(),
+            written
+        );
+
+        // A removal on the laptop reaches the desktop; its own addition stays
+        laptop.pinned_programs.retain(|item| item.name != "Firefox");
+        sync("laptop", &mut laptop, &mut laptop_usage, 3, &home);
+        desktop.pinned_programs.push(item("Gimp", "gimp"));
+        sync("desktop", &mut desktop, &mut desktop_usage, 4, &none);
+        assert_eq!(names(&desktop.pinned_programs), ["Tool", "Gimp", "Kitty"]);
+        // The laptop's paths come back as the desktop's own
+        assert_eq!(desktop.pinned_programs[0].path, "/home/alice/bin/tool");
+
+        // The later edit of the same item wins on both machines
+        laptop.pinned_programs[0].path = "kitty --single-instance".to_string();
+        sync("laptop", &mut laptop, &mut laptop_usage, 5, &home);
+        desktop.pinned_programs[2].path = "kitty -1".to_string();
+        sync("desktop", &mut desktop, &mut desktop_usage, 6, &none);
+        sync("laptop", &mut laptop, &mut laptop_usage, 7, &home);
+        assert_eq!(laptop.pinned_programs[0].path, "kitty -1");
+        assert_eq!(desktop.pinned_programs[2].path, "kitty -1");
+    }
+
+    #[test]
+    fn test_localize() {
+        let overrides = BTreeMap::from([
+            ("/home/alice".to_string(), "/Users/alice".to_string()),
+            ("/home/alice/bin".to_string(), "/opt/bin".to_string()),
+        ]);
+        assert_eq!(
+            localize("/home/alice/doc.pdf", &overrides),
+            "/Users/alice/doc.pdf"
+        );
+        assert_eq!(
+            localize("/home/alice/bin/tool -v", &overrides),
+            "/opt/bin/tool -v"
+        );
+        assert_eq!(
+            localize("/home/alicex/doc.pdf", &overrides),
+            "/home/alicex/doc.pdf"
+        );
+    }
+}
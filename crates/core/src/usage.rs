@@ -1,59 +1,228 @@
 //! Usage tracking with recency-weighted scoring
+//!
+//! Scores are frecency: each launch decays exponentially with age, and
+//! launches at the same time of day or on the same weekday as now keep more
+//! of their weight, so periodic habits (mail in the morning, a time tracker
+//! on Fridays) rank higher at the right time.
 
+use crate::config::{ItemType, LaunchItem, RankingConfig};
+use crate::persist;
 use anyhow::{Context, Result};
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, TimeZone, Timelike, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-/// Half-life for recency weighting (7 days)
-const HALF_LIFE_DAYS: i64 = 7;
+/// Default half-life for recency weighting (7 days)
+const HALF_LIFE_DAYS: f64 = 7.0;
+
+/// Launches kept per record
+const MAX_LAUNCHES: usize = 100;
+
+/// Longest query prefix learned from search picks
+const MAX_PREFIX_CHARS: usize = 10;
+
+/// Kind of usage, each with its own half-life
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageKind {
+    /// Programs and shortcuts
+    Program,
+    Document,
+    /// Pasted clipboard entries
+    Snippet,
+}
+
+/// Frecency model parameters for one kind of usage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frecency {
+    /// Age in days at which a launch weighs half as much
+    pub half_life_days: f64,
+    /// Extra weight of launches in the same time-of-day bucket as now
+    pub time_of_day_weight: f64,
+    /// Extra weight of launches on the same weekday as now
+    pub weekday_weight: f64,
+    /// Hours per time-of-day bucket
+    pub bucket_hours: u32,
+}
+
+impl Default for Frecency {
+    /// Plain exponential decay with a 7-day half-life
+    fn default() -> Self {
+        Self {
+            half_life_days: HALF_LIFE_DAYS,
+            time_of_day_weight: 0.0,
+            weekday_weight: 0.0,
+            bucket_hours: 24,
+        }
+    }
+}
+
+impl Frecency {
+    /// Model for a kind of usage from the ranking settings
+    pub fn new(ranking: &RankingConfig, kind: UsageKind) -> Self {
+        let half_lives = &ranking.half_life_days;
+        Self {
+            half_life_days: match kind {
+                UsageKind::Program => half_lives.programs,
+                UsageKind::Document => half_lives.documents,
+                UsageKind::Snippet => half_lives.snippets,
+            },
+            time_of_day_weight: ranking.time_of_day_weight,
+            weekday_weight: ranking.weekday_weight,
+            bucket_hours: ranking.time_of_day_bucket_hours,
+        }
+    }
+}
+
+/// Scores records as of a fixed time
+#[derive(Debug, Clone)]
+pub struct Scorer {
+    now: DateTime<FixedOffset>,
+    ranking: RankingConfig,
+}
+
+impl Scorer {
+    /// Score at `now`; its offset decides the local time of day and weekday
+    pub fn at(now: DateTime<FixedOffset>, ranking: &RankingConfig) -> Self {
+        Self {
+            now,
+            ranking: ranking.clone(),
+        }
+    }
+
+    /// Score at the current local time
+    pub fn now(ranking: &RankingConfig) -> Self {
+        Self::at(Local::now().fixed_offset(), ranking)
+    }
+
+    /// Score of a record of the given kind
+    pub fn score(&self, record: &UsageRecord, kind: UsageKind) -> f64 {
+        record.score_at(&self.now, &Frecency::new(&self.ranking, kind))
+    }
+}
 
 /// A usage record for a single item
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct UsageRecord {
     /// Path to the item
     pub path: String,
     /// Display name
     pub name: String,
+    /// Arguments the item was launched with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Type of the launched item
+    #[serde(default, skip_serializing_if = "is_program")]
+    pub item_type: ItemType,
     /// List of timestamps when the item was launched
     pub launches: Vec<DateTime<Utc>>,
 }
 
+fn is_program(item_type: &ItemType) -> bool {
+    *item_type == ItemType::Program
+}
+
+/// Key of an item's usage records: its path and arguments, so shortcuts
+/// that run one program with different arguments are told apart
+fn item_key(item: &LaunchItem) -> String {
+    std::iter::once(item.path.as_str())
+        .chain(item.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 impl UsageRecord {
     pub fn new(path: String, name: String) -> Self {
         Self {
             path,
             name,
             launches: vec![Utc::now()],
+            ..Default::default()
+        }
+    }
+
+    /// A record of a first launch of `item`
+    pub fn of_item(item: &LaunchItem) -> Self {
+        Self {
+            args: item.args.clone(),
+            item_type: item.item_type,
+            ..Self::new(item.path.clone(), item.name.clone())
+        }
+    }
+
+    /// The item to launch again, as it was launched
+    pub fn item(&self) -> LaunchItem {
+        LaunchItem {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            icon: None,
+            args: self.args.clone(),
+            item_type: self.item_type,
         }
     }
 
     /// Record a new launch
     pub fn record_launch(&mut self) {
         self.launches.push(Utc::now());
-        // Keep only last 100 launches to prevent unbounded growth
-        if self.launches.len() > 100 {
-            self.launches.drain(0..self.launches.len() - 100);
+        self.truncate();
+    }
+
+    /// Add the launches of `other` that this record doesn't have
+    pub fn merge(&mut self, other: &UsageRecord) {
+        if self.name.is_empty() {
+            self.name = other.name.clone();
+        }
+        self.launches.extend(&other.launches);
+        self.launches.sort();
+        self.launches.dedup();
+        self.truncate();
+    }
+
+    /// Keep only last 100 launches to prevent unbounded growth
+    fn truncate(&mut self) {
+        if self.launches.len() > MAX_LAUNCHES {
+            self.launches.drain(0..self.launches.len() - MAX_LAUNCHES);
         }
     }
 
     /// Calculate recency-weighted score
     /// Uses exponential decay with 7-day half-life
     pub fn score(&self) -> f64 {
-        let now = Utc::now();
-        let half_life = Duration::days(HALF_LIFE_DAYS);
+        self.score_at(&Utc::now(), &Frecency::default())
+    }
+
+    /// Frecency score at `now`
+    ///
+    /// Each launch weighs 2^(-age/half_life), scaled down unless it falls in
+    /// the same time-of-day bucket and on the same weekday as `now` (in the
+    /// time zone of `now`). A fresh launch at the current time weighs 1.0.
+    pub fn score_at<Tz: TimeZone>(&self, now: &DateTime<Tz>, frecency: &Frecency) -> f64 {
+        let half_life_secs = frecency.half_life_days * 86_400.0;
+        let bucket_hours = frecency.bucket_hours.clamp(1, 24);
+        let full_weight = 1.0 + frecency.time_of_day_weight + frecency.weekday_weight;
+
+        self.launches.iter().fold(0.0, |acc, launch_time| {
+            let launch_time = launch_time.with_timezone(&now.timezone());
 
-        self.launches.iter().fold(0.0, |acc, &launch_time| {
-            let age = now.signed_duration_since(launch_time);
+            let mut weight = 1.0;
+            if launch_time.hour() / bucket_hours == now.hour() / bucket_hours {
+                weight += frecency.time_of_day_weight;
+            }
+            if launch_time.weekday() == now.weekday() {
+                weight += frecency.weekday_weight;
+            }
+            let weight = weight / full_weight;
+
+            let age = now.clone().signed_duration_since(launch_time);
             if age.num_seconds() < 0 {
-                return acc + 1.0; // Future timestamps count as full weight
+                return acc + weight; // Future timestamps count as full weight
             }
 
             // Exponential decay: score = 2^(-age/half_life)
-            let decay = (-age.num_seconds() as f64 / half_life.num_seconds() as f64).exp2();
-            acc + decay
+            let decay = (-age.num_seconds() as f64 / half_life_secs).exp2();
+            acc + weight * decay
         })
     }
 }
@@ -65,10 +234,237 @@ pub struct UsageData {
     pub programs: HashMap<String, UsageRecord>,
     /// Document usage records keyed by path
     pub documents: HashMap<String, UsageRecord>,
+    /// Per focused-app usage, keyed by app id (e.g. `firefox`, `kitty`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub contexts: HashMap<String, ContextUsage>,
+    /// Search picks keyed by query prefix, then by the chosen item's path
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub selections: HashMap<String, HashMap<String, UsageRecord>>,
+    /// Launches per day for statistics, never truncated or pruned
+    #[serde(default, skip_serializing_if = "LaunchCounts::is_empty")]
+    pub daily: LaunchCounts,
+    /// What `daily` counted since the last save, see [`UsageData::save`]
+    #[serde(skip)]
+    unsaved: LaunchCounts,
     /// Last cleanup timestamp
     pub last_cleanup: Option<DateTime<Utc>>,
 }
 
+/// What was used while a given app had focus
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ContextUsage {
+    /// Program and shortcut launches keyed by path
+    #[serde(default)]
+    pub programs: HashMap<String, UsageRecord>,
+    /// Clipboard pastes keyed by the pasted text
+    #[serde(default)]
+    pub snippets: HashMap<String, UsageRecord>,
+}
+
+impl ContextUsage {
+    fn is_empty(&self) -> bool {
+        self.programs.is_empty() && self.snippets.is_empty()
+    }
+}
+
+/// Launches of one item per local day, counted separately per machine
+///
+/// Each machine only raises its own counts, so copies of the data merge by
+/// taking the larger count of each day and machine, however often they meet.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct DailyLaunches {
+    /// Display name
+    pub name: String,
+    /// Launches by local date, then by machine
+    pub days: BTreeMap<NaiveDate, BTreeMap<String, u32>>,
+}
+
+impl DailyLaunches {
+    /// Launches on `day`, on all machines
+    pub fn on(&self, day: NaiveDate) -> u32 {
+        self.days
+            .get(&day)
+            .map_or(0, |machines| machines.values().sum())
+    }
+
+    /// Merge another copy of these counts, taking the larger of each
+    pub fn merge(&mut self, other: &DailyLaunches) {
+        self.combine(other, u32::max);
+    }
+
+    /// Combine `other`'s count of each day and machine into this one's
+    fn combine(&mut self, other: &DailyLaunches, f: fn(u32, u32) -> u32) {
+        if self.name.is_empty() {
+            self.name = other.name.clone();
+        }
+        for (day, machines) in &other.days {
+            let counts = self.days.entry(*day).or_default();
+            for (machine, count) in machines {
+                let combined = counts.entry(machine.clone()).or_default();
+                *combined = f(*combined, *count);
+            }
+        }
+    }
+}
+
+/// Per-day launch counts of programs and documents, keyed by path
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct LaunchCounts {
+    #[serde(default)]
+    pub programs: HashMap<String, DailyLaunches>,
+    #[serde(default)]
+    pub documents: HashMap<String, DailyLaunches>,
+}
+
+impl LaunchCounts {
+    fn is_empty(&self) -> bool {
+        self.programs.is_empty() && self.documents.is_empty()
+    }
+
+    /// Counts of the launches still in `programs` and `documents`, as this
+    /// machine's, for data saved before launches were counted per day
+    fn from_records(
+        programs: &HashMap<String, UsageRecord>,
+        documents: &HashMap<String, UsageRecord>,
+    ) -> Self {
+        let mut counts = Self::default();
+        for (records, kind) in [
+            (programs, &mut counts.programs),
+            (documents, &mut counts.documents),
+        ] {
+            for (path, record) in records {
+                for launch in &record.launches {
+                    count_in(
+                        kind,
+                        path,
+                        &record.name,
+                        launch.with_timezone(&Local).date_naive(),
+                    );
+                }
+            }
+        }
+        counts
+    }
+
+    fn combine(&mut self, other: &LaunchCounts, f: fn(u32, u32) -> u32) {
+        for (counts, other) in [
+            (&mut self.programs, &other.programs),
+            (&mut self.documents, &other.documents),
+        ] {
+            for (path, launches) in other {
+                counts.entry(path.clone()).or_default().combine(launches, f);
+            }
+        }
+    }
+}
+
+/// This machine's name in [`DailyLaunches`]
+fn machine() -> &'static str {
+    static MACHINE: OnceLock<String> = OnceLock::new();
+    MACHINE.get_or_init(|| crate::sync::hostname().unwrap_or_else(|| "default".to_string()))
+}
+
+/// Count a launch on `day` on this machine
+fn count_in(counts: &mut HashMap<String, DailyLaunches>, path: &str, name: &str, day: NaiveDate) {
+    let launches = counts.entry(path.to_string()).or_default();
+    if launches.name.is_empty() {
+        launches.name = name.to_string();
+    }
+    *launches
+        .days
+        .entry(day)
+        .or_default()
+        .entry(machine().to_string())
+        .or_default() += 1;
+}
+
+/// Record a use in a map of records, creating the record on first use
+fn record_in(records: &mut HashMap<String, UsageRecord>, path: &str, name: &str) {
+    if let Some(record) = records.get_mut(path) {
+        record.record_launch();
+    } else {
+        records.insert(
+            path.to_string(),
+            UsageRecord::new(path.to_string(), name.to_string()),
+        );
+    }
+}
+
+/// Record a launch of `item` in a map of records keyed by [`item_key`]
+fn record_item_in(records: &mut HashMap<String, UsageRecord>, item: &LaunchItem) {
+    let key = item_key(item);
+    match records.get_mut(&key) {
+        Some(record) => record.record_launch(),
+        None => {
+            records.insert(key, UsageRecord::of_item(item));
+        }
+    }
+}
+
+/// Merge `other` into `records`, unioning launches per path
+fn merge_records(records: &mut HashMap<String, UsageRecord>, other: &HashMap<String, UsageRecord>) {
+    for (path, record) in other {
+        match records.get_mut(path) {
+            Some(existing) => existing.merge(record),
+            None => {
+                records.insert(path.clone(), record.clone());
+            }
+        }
+    }
+}
+
+/// Base command of an exec line (e.g. "/usr/bin/code --new-window" → "code")
+fn base_command(path: &str) -> &str {
+    path.split_whitespace()
+        .next()
+        .and_then(|cmd| cmd.rsplit('/').next())
+        .unwrap_or(path)
+}
+
+/// Top N records by score
+fn top_of<'a>(
+    records: &'a HashMap<String, UsageRecord>,
+    n: usize,
+    scorer: &Scorer,
+    kind: UsageKind,
+) -> Vec<&'a UsageRecord> {
+    let mut records: Vec<_> = records
+        .values()
+        .map(|record| (record, scorer.score(record, kind)))
+        .collect();
+    records.sort_by(|a, b| b.1.total_cmp(&a.1));
+    records
+        .into_iter()
+        .take(n)
+        .map(|(record, _)| record)
+        .collect()
+}
+
+/// Normalized search query, at most `MAX_PREFIX_CHARS` long
+fn query_key(query: &str) -> String {
+    query
+        .trim()
+        .to_lowercase()
+        .chars()
+        .take(MAX_PREFIX_CHARS)
+        .collect()
+}
+
+/// Bounded 0..1 transform of an unbounded score
+fn saturate(score: f64) -> f64 {
+    score / (1.0 + score)
+}
+
+/// Drop records whose score fell below `threshold`
+fn prune(
+    records: &mut HashMap<String, UsageRecord>,
+    scorer: &Scorer,
+    kind: UsageKind,
+    threshold: f64,
+) {
+    records.retain(|_, record| scorer.score(record, kind) >= threshold);
+}
+
 impl UsageData {
     /// Get the usage data file path
     pub fn data_path() -> Result<PathBuf> {
@@ -81,89 +477,278 @@ impl UsageData {
 
     /// Load usage data from file
     pub fn load() -> Result<Self> {
-        let path = Self::data_path()?;
+        Self::load_from(&Self::data_path()?)
+    }
 
-        if path.exists() {
-            let content = fs::read_to_string(&path)
-                .with_context(|| format!("Failed to read usage data from {:?}", path))?;
-            serde_json::from_str(&content)
-                .with_context(|| format!("Failed to parse usage data from {:?}", path))
-        } else {
-            Ok(Self::default())
+    fn load_from(path: &Path) -> Result<Self> {
+        let data: Option<Self> = persist::load_json(path).context("Failed to load usage data")?;
+        let mut data = data.unwrap_or_default();
+        if data.daily.is_empty() {
+            data.daily = LaunchCounts::from_records(&data.programs, &data.documents);
         }
+        Ok(data)
     }
 
-    /// Save usage data to file
-    pub fn save(&self) -> Result<()> {
-        let path = Self::data_path()?;
-        let content = serde_json::to_string_pretty(self)?;
-        fs::write(&path, content)
+    /// Save usage data to file, merged with what is stored there
+    ///
+    /// The daemon, the CLI and a synced copy may all have saved launches since
+    /// this data was loaded, so the file is re-read under an advisory lock and
+    /// launches are unioned per path. Per-day counts are merged without what
+    /// this copy counted since its last save, which is then added on top, so
+    /// no process's launches are lost. `self` becomes the merged data.
+    pub fn save(&mut self) -> Result<()> {
+        self.save_to(&Self::data_path()?)
+    }
+
+    fn save_to(&mut self, path: &Path) -> Result<()> {
+        let _lock = persist::lock(path)?;
+        let unsaved = std::mem::take(&mut self.unsaved);
+        self.daily.combine(&unsaved, u32::saturating_sub);
+        match Self::load_from(path) {
+            Ok(stored) => self.merge(&stored),
+            Err(e) => log::warn!("{:#}; overwriting it", e),
+        }
+        self.daily.combine(&unsaved, u32::saturating_add);
+        persist::save_json(path, self)
+            .with_context(|| format!("Failed to write usage data to {:?}", path))
+    }
+
+    /// Load usage data, running the daily cleanup on the stored file if due
+    ///
+    /// The cleanup is written back under the lock without merging, as a merge
+    /// would bring the pruned records back from the file.
+    fn load_cleaned(path: &Path, scorer: &Scorer) -> Result<Self> {
+        let data = Self::load_from(path)?;
+        if !data.cleanup_due(scorer) {
+            return Ok(data);
+        }
+        let _lock = persist::lock(path)?;
+        let mut data = Self::load_from(path)?;
+        data.cleanup(scorer);
+        persist::save_json(path, &data)
             .with_context(|| format!("Failed to write usage data to {:?}", path))?;
-        Ok(())
+        Ok(data)
     }
 
-    /// Record a program launch
-    pub fn record_program_launch(&mut self, path: &str, name: &str) {
-        if let Some(record) = self.programs.get_mut(path) {
-            record.record_launch();
-        } else {
-            self.programs.insert(
-                path.to_string(),
-                UsageRecord::new(path.to_string(), name.to_string()),
-            );
+    /// Merge usage from another copy of the data, unioning launches per path
+    pub fn merge(&mut self, other: &UsageData) {
+        merge_records(&mut self.programs, &other.programs);
+        merge_records(&mut self.documents, &other.documents);
+        for (context, usage) in &other.contexts {
+            let merged = self.contexts.entry(context.clone()).or_default();
+            merge_records(&mut merged.programs, &usage.programs);
+            merge_records(&mut merged.snippets, &usage.snippets);
+        }
+        for (query, picks) in &other.selections {
+            merge_records(self.selections.entry(query.clone()).or_default(), picks);
         }
+        self.daily.combine(&other.daily, u32::max);
+        self.last_cleanup = self.last_cleanup.max(other.last_cleanup);
+    }
+
+    /// Record a program or shortcut launch
+    pub fn record_program_launch(&mut self, item: &LaunchItem) {
+        record_item_in(&mut self.programs, item);
+        let (key, today) = (item_key(item), Local::now().date_naive());
+        count_in(&mut self.daily.programs, &key, &item.name, today);
+        count_in(&mut self.unsaved.programs, &key, &item.name, today);
     }
 
     /// Record a document open
     pub fn record_document_open(&mut self, path: &str, name: &str) {
-        if let Some(record) = self.documents.get_mut(path) {
-            record.record_launch();
-        } else {
-            self.documents.insert(
-                path.to_string(),
-                UsageRecord::new(path.to_string(), name.to_string()),
-            );
+        record_in(&mut self.documents, path, name);
+        let today = Local::now().date_naive();
+        count_in(&mut self.daily.documents, path, name, today);
+        count_in(&mut self.unsaved.documents, path, name, today);
+    }
+
+    /// Record a program or shortcut launched while `context` had focus
+    pub fn record_context_program(&mut self, context: &str, item: &LaunchItem) {
+        let usage = self.contexts.entry(context.to_string()).or_default();
+        record_item_in(&mut usage.programs, item);
+    }
+
+    /// Record a clipboard snippet pasted into `context`
+    pub fn record_context_snippet(&mut self, context: &str, text: &str) {
+        let usage = self.contexts.entry(context.to_string()).or_default();
+        record_in(&mut usage.snippets, text, "");
+    }
+
+    /// Record an item picked from search results for `query`
+    ///
+    /// Every prefix of the query learns the pick, so typing "te" and picking
+    /// Telegram also promotes it for "t", and picks made after typing "tel"
+    /// count for "te".
+    pub fn record_selection(&mut self, query: &str, path: &str, name: &str) {
+        let key = query_key(query);
+        if key.is_empty() {
+            return;
+        }
+        for (end, _) in key.char_indices().skip(1).chain([(key.len(), ' ')]) {
+            let picks = self.selections.entry(key[..end].to_string()).or_default();
+            record_in(picks, path, name);
         }
     }
 
     /// Get top N programs by score
-    pub fn top_programs(&self, n: usize) -> Vec<&UsageRecord> {
-        let mut programs: Vec<_> = self.programs.values().collect();
-        programs.sort_by(|a, b| b.score().total_cmp(&a.score()));
-        programs.into_iter().take(n).collect()
+    pub fn top_programs(&self, n: usize, scorer: &Scorer) -> Vec<&UsageRecord> {
+        top_of(&self.programs, n, scorer, UsageKind::Program)
     }
 
     /// Get top N documents by score
-    pub fn top_documents(&self, n: usize) -> Vec<&UsageRecord> {
-        let mut documents: Vec<_> = self.documents.values().collect();
-        documents.sort_by(|a, b| b.score().total_cmp(&a.score()));
-        documents.into_iter().take(n).collect()
+    pub fn top_documents(&self, n: usize, scorer: &Scorer) -> Vec<&UsageRecord> {
+        top_of(&self.documents, n, scorer, UsageKind::Document)
+    }
+
+    /// Rank programs by blending popup launches with platform frequencies
+    ///
+    /// Programs are matched by base command and arguments, so a launch of
+    /// `/usr/bin/code` from the popup and `code` in the shell history count
+    /// for the same app, while shortcuts keep their arguments.
+    pub fn rank_programs(
+        &self,
+        platform: Vec<(LaunchItem, f64)>,
+        scorer: &Scorer,
+        n: usize,
+    ) -> Vec<LaunchItem> {
+        let ranking = &scorer.ranking;
+        let max_platform = platform
+            .iter()
+            .fold(0.0, |max, (_, frequency)| f64::max(max, *frequency));
+        let usage: Vec<_> = self
+            .programs
+            .values()
+            .map(|record| (record, scorer.score(record, UsageKind::Program)))
+            .collect();
+        let max_usage = usage
+            .iter()
+            .fold(0.0, |max, (_, score)| f64::max(max, *score));
+
+        let mut ranked: Vec<(LaunchItem, f64)> = Vec::new();
+        for (item, frequency) in platform {
+            if max_platform > 0.0 {
+                let score = ranking.platform_weight * frequency / max_platform;
+                ranked.push((item, score));
+            }
+        }
+        for (record, score) in usage {
+            if max_usage <= 0.0 {
+                break;
+            }
+            let score = ranking.usage_weight * score / max_usage;
+            let command = base_command(&record.path);
+            match ranked
+                .iter_mut()
+                .find(|(item, _)| base_command(&item.path) == command && item.args == record.args)
+            {
+                Some((_, total)) => *total += score,
+                None => ranked.push((record.item(), score)),
+            }
+        }
+
+        // Stable, so platform order breaks ties
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.into_iter().take(n).map(|(item, _)| item).collect()
+    }
+
+    /// Rank search matches for `query` by match quality, past picks for the
+    /// query and frecency
+    ///
+    /// `matches` carry their fuzzy match score. Match scores are scaled to
+    /// 0..1 relative to the best match; pick and frecency scores are
+    /// saturated to 0..1, then weighted by `query_weight` and `usage_weight`.
+    pub fn rank_matches(
+        &self,
+        query: &str,
+        matches: Vec<(LaunchItem, f64)>,
+        scorer: &Scorer,
+        n: usize,
+    ) -> Vec<LaunchItem> {
+        let ranking = &scorer.ranking;
+        let best = matches
+            .iter()
+            .fold(0.0, |max, (_, score)| f64::max(max, *score));
+        let picks = self.selections.get(&query_key(query));
+        // Summed per command, like `rank_programs`, as several exec lines
+        // (`code`, `code --new-window`) launch the same program
+        let mut frecency: HashMap<&str, f64> = HashMap::new();
+        for record in self.programs.values() {
+            *frecency.entry(base_command(&record.path)).or_default() +=
+                scorer.score(record, UsageKind::Program);
+        }
+
+        let mut ranked: Vec<(LaunchItem, f64)> = matches
+            .into_iter()
+            .filter(|(_, score)| *score > 0.0)
+            .map(|(item, score)| {
+                let picked = picks
+                    .and_then(|picks| picks.get(&item.path))
+                    .map_or(0.0, |record| scorer.score(record, UsageKind::Program));
+                let used = frecency
+                    .get(base_command(&item.path))
+                    .copied()
+                    .unwrap_or(0.0);
+                let total = score / best
+                    + ranking.query_weight * saturate(picked)
+                    + ranking.usage_weight * saturate(used);
+                (item, total)
+            })
+            .collect();
+
+        // Stable, so match order breaks ties
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.into_iter().take(n).map(|(item, _)| item).collect()
+    }
+
+    /// Get top N programs launched while `context` had focus
+    pub fn context_programs(&self, context: &str, n: usize, scorer: &Scorer) -> Vec<&UsageRecord> {
+        self.contexts
+            .get(context)
+            .map(|usage| top_of(&usage.programs, n, scorer, UsageKind::Program))
+            .unwrap_or_default()
+    }
+
+    /// Get top N snippets pasted into `context`; `path` holds the text
+    pub fn context_snippets(&self, context: &str, n: usize, scorer: &Scorer) -> Vec<&UsageRecord> {
+        self.contexts
+            .get(context)
+            .map(|usage| top_of(&usage.snippets, n, scorer, UsageKind::Snippet))
+            .unwrap_or_default()
     }
 
     /// Clean up old data (entries with score < 0.01)
-    pub fn cleanup(&mut self) {
+    pub fn cleanup(&mut self, scorer: &Scorer) {
         let threshold = 0.01;
 
-        self.programs
-            .retain(|_, record| record.score() >= threshold);
-        self.documents
-            .retain(|_, record| record.score() >= threshold);
+        prune(&mut self.programs, scorer, UsageKind::Program, threshold);
+        prune(&mut self.documents, scorer, UsageKind::Document, threshold);
+        for usage in self.contexts.values_mut() {
+            prune(&mut usage.programs, scorer, UsageKind::Program, threshold);
+            prune(&mut usage.snippets, scorer, UsageKind::Snippet, threshold);
+        }
+        self.contexts.retain(|_, usage| !usage.is_empty());
+        for picks in self.selections.values_mut() {
+            prune(picks, scorer, UsageKind::Program, threshold);
+        }
+        self.selections.retain(|_, picks| !picks.is_empty());
 
-        self.last_cleanup = Some(Utc::now());
+        self.last_cleanup = Some(scorer.now.with_timezone(&Utc));
     }
 
     /// Perform daily cleanup if needed
-    pub fn maybe_cleanup(&mut self) {
-        let should_cleanup = match self.last_cleanup {
+    pub fn maybe_cleanup(&mut self, scorer: &Scorer) {
+        if self.cleanup_due(scorer) {
+            self.cleanup(scorer);
+        }
+    }
+
+    fn cleanup_due(&self, scorer: &Scorer) -> bool {
+        match self.last_cleanup {
             Some(last) => {
-                let elapsed = Utc::now().signed_duration_since(last);
+                let elapsed = scorer.now.signed_duration_since(last);
                 elapsed.num_hours() >= 24
             }
             None => true,
-        };
-
-        if should_cleanup {
-            self.cleanup();
         }
     }
 }
@@ -175,16 +760,23 @@ pub struct UsageTracker {
 }
 
 impl UsageTracker {
-    pub fn new() -> Result<Self> {
-        let mut data = UsageData::load()?;
-        data.maybe_cleanup();
+    pub fn new(ranking: &RankingConfig) -> Result<Self> {
+        // Losing usage history is better than not starting; the unreadable
+        // file is kept as a backup on the next save
+        let scorer = Scorer::now(ranking);
+        let data = UsageData::data_path()
+            .and_then(|path| UsageData::load_cleaned(&path, &scorer))
+            .unwrap_or_else(|e| {
+                log::error!("{:#}; starting with empty usage data", e);
+                UsageData::default()
+            });
 
         Ok(Self { data, dirty: false })
     }
 
-    /// Record a program launch
-    pub fn record_program(&mut self, path: &str, name: &str) {
-        self.data.record_program_launch(path, name);
+    /// Record a program or shortcut launch
+    pub fn record_program(&mut self, item: &LaunchItem) {
+        self.data.record_program_launch(item);
         self.dirty = true;
     }
 
@@ -194,14 +786,76 @@ impl UsageTracker {
         self.dirty = true;
     }
 
+    /// Record a program or shortcut launched while `context` had focus
+    pub fn record_context_program(&mut self, context: &str, item: &LaunchItem) {
+        self.data.record_context_program(context, item);
+        self.dirty = true;
+    }
+
+    /// Record a clipboard snippet pasted into `context`
+    pub fn record_context_snippet(&mut self, context: &str, text: &str) {
+        self.data.record_context_snippet(context, text);
+        self.dirty = true;
+    }
+
     /// Get top programs
-    pub fn top_programs(&self, n: usize) -> Vec<&UsageRecord> {
-        self.data.top_programs(n)
+    pub fn top_programs(&self, n: usize, ranking: &RankingConfig) -> Vec<&UsageRecord> {
+        self.data.top_programs(n, &Scorer::now(ranking))
+    }
+
+    /// Rank programs by blending popup launches with platform frequencies
+    pub fn rank_programs(
+        &self,
+        platform: Vec<(LaunchItem, f64)>,
+        ranking: &RankingConfig,
+        n: usize,
+    ) -> Vec<LaunchItem> {
+        self.data.rank_programs(platform, &Scorer::now(ranking), n)
+    }
+
+    /// Record an item picked from search results for `query`
+    pub fn record_selection(&mut self, query: &str, path: &str, name: &str) {
+        self.data.record_selection(query, path, name);
+        self.dirty = true;
+    }
+
+    /// Rank search matches by match quality, past picks and frecency
+    pub fn rank_matches(
+        &self,
+        query: &str,
+        matches: Vec<(LaunchItem, f64)>,
+        ranking: &RankingConfig,
+        n: usize,
+    ) -> Vec<LaunchItem> {
+        self.data
+            .rank_matches(query, matches, &Scorer::now(ranking), n)
+    }
+
+    /// Get top programs for a focused app
+    pub fn context_programs(
+        &self,
+        context: &str,
+        n: usize,
+        ranking: &RankingConfig,
+    ) -> Vec<&UsageRecord> {
+        self.data
+            .context_programs(context, n, &Scorer::now(ranking))
+    }
+
+    /// Get top snippets for a focused app
+    pub fn context_snippets(
+        &self,
+        context: &str,
+        n: usize,
+        ranking: &RankingConfig,
+    ) -> Vec<&UsageRecord> {
+        self.data
+            .context_snippets(context, n, &Scorer::now(ranking))
     }
 
     /// Get top documents
-    pub fn top_documents(&self, n: usize) -> Vec<&UsageRecord> {
-        self.data.top_documents(n)
+    pub fn top_documents(&self, n: usize, ranking: &RankingConfig) -> Vec<&UsageRecord> {
+        self.data.top_documents(n, &Scorer::now(ranking))
     }
 
     /// Save if there are unsaved changes
@@ -231,6 +885,16 @@ impl Drop for UsageTracker {
 mod tests {
     use super::*;
 
+    fn program(path: &str, name: &str) -> LaunchItem {
+        LaunchItem {
+            name: name.to_string(),
+            path: path.to_string(),
+            icon: None,
+            args: vec![],
+            item_type: ItemType::Program,
+        }
+    }
+
     #[test]
     fn test_usage_record_score() {
         let mut record = UsageRecord::new("/usr/bin/firefox".to_string(), "Firefox".to_string());
@@ -254,12 +918,346 @@ mod tests {
         let mut data = UsageData::default();
 
         // Add some programs
-        data.record_program_launch("/usr/bin/firefox", "Firefox");
-        data.record_program_launch("/usr/bin/firefox", "Firefox");
-        data.record_program_launch("/usr/bin/code", "VS Code");
+        data.record_program_launch(&program("/usr/bin/firefox", "Firefox"));
+        data.record_program_launch(&program("/usr/bin/firefox", "Firefox"));
+        data.record_program_launch(&program("/usr/bin/code", "VS Code"));
 
-        let top = data.top_programs(2);
+        let top = data.top_programs(2, &Scorer::now(&RankingConfig::default()));
         assert_eq!(top.len(), 2);
         assert_eq!(top[0].name, "Firefox"); // Firefox has more launches
     }
+
+    #[test]
+    fn test_rank_programs() {
+        let item = |name: &str, path: &str| LaunchItem {
+            name: name.to_string(),
+            path: path.to_string(),
+            icon: None,
+            args: vec![],
+            item_type: ItemType::Program,
+        };
+        let mut data = UsageData::default();
+        data.record_program_launch(&program("/usr/bin/code --new-window", "VS Code"));
+        data.record_program_launch(&program("/usr/bin/code --new-window", "VS Code"));
+        data.record_program_launch(&program("gimp", "GIMP"));
+        let platform = vec![(item("Vim", "vim %F"), 40.0), (item("Code", "code"), 20.0)];
+        let names = |ranking: &RankingConfig| -> Vec<String> {
+            data.rank_programs(platform.clone(), &Scorer::now(ranking), 5)
+                .into_iter()
+                .map(|item| item.name)
+                .collect()
+        };
+
+        // Code: 0.5 + 1.0, Vim: 1.0, GIMP (popup only): 0.5
+        assert_eq!(names(&RankingConfig::default()), ["Code", "Vim", "GIMP"]);
+        let platform_only = RankingConfig {
+            usage_weight: 0.0,
+            ..RankingConfig::default()
+        };
+        assert_eq!(names(&platform_only), ["Vim", "Code", "GIMP"]);
+        let usage_heavy = RankingConfig {
+            usage_weight: 3.0,
+            platform_weight: 1.0,
+            ..RankingConfig::default()
+        };
+        assert_eq!(names(&usage_heavy), ["Code", "GIMP", "Vim"]);
+        assert_eq!(
+            UsageData::default().rank_programs(vec![], &Scorer::now(&usage_heavy), 5),
+            []
+        );
+
+        // Shortcuts come back with their arguments, one per command line
+        let shortcut = |name: &str, arg: &str| LaunchItem {
+            args: vec![arg.to_string()],
+            item_type: ItemType::Shortcut,
+            ..item(name, "loginctl")
+        };
+        let lock = shortcut("Lock Screen", "lock-session");
+        let log_out = shortcut("Log Out", "terminate-session");
+        let mut data = UsageData::default();
+        data.record_program_launch(&lock);
+        data.record_program_launch(&lock);
+        data.record_program_launch(&log_out);
+        let ranked = data.rank_programs(vec![], &Scorer::now(&RankingConfig::default()), 5);
+        assert_eq!(ranked, [lock, log_out]);
+    }
+
+    #[test]
+    fn test_score_at() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap();
+        // Monday 2024-06-03 09:00 at UTC+2
+        let now = at("2024-06-03T09:00:00+02:00");
+        let record = |launches: &[&str]| UsageRecord {
+            path: "thunderbird".to_string(),
+            name: "Thunderbird".to_string(),
+            launches: launches.iter().map(|s| at(s).with_timezone(&Utc)).collect(),
+            ..Default::default()
+        };
+        let plain = Frecency::default();
+        let periodic = Frecency {
+            time_of_day_weight: 1.0,
+            weekday_weight: 1.0,
+            bucket_hours: 4,
+            ..plain
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        // Deterministic decay: one half-life ago weighs 0.5
+        let week_ago = record(&["2024-05-27T09:00:00+02:00"]);
+        assert!(close(week_ago.score_at(&now, &plain), 0.5));
+        assert!(close(week_ago.score_at(&now, &periodic), 0.5));
+        let short = Frecency {
+            half_life_days: 1.0,
+            ..plain
+        };
+        assert!(close(week_ago.score_at(&now, &short), 0.5f64.powi(7)));
+
+        // Bucket matching uses the time zone of `now`: 06:30 UTC is 08:30 here
+        let this_morning = record(&["2024-06-03T06:30:00Z"]);
+        assert!(this_morning.score_at(&now, &periodic) > 0.99);
+        // Same weekday, other time of day: 2 of 3 parts
+        let decay = |age_days: f64| (-age_days / 7.0).exp2();
+        let monday_evening = record(&["2024-05-27T20:00:00+02:00"]);
+        let expected = decay(6.0 + 13.0 / 24.0) * 2.0 / 3.0;
+        assert!(close(monday_evening.score_at(&now, &periodic), expected));
+        // Same time of day, other weekday
+        let tuesday_morning = record(&["2024-05-28T10:00:00+02:00"]);
+        let expected = decay(6.0 - 1.0 / 24.0) * 2.0 / 3.0;
+        assert!(close(tuesday_morning.score_at(&now, &periodic), expected));
+
+        // Morning habit outranks a more recent evening launch in the morning
+        let mail = record(&["2024-05-31T08:30:00+02:00", "2024-05-30T09:15:00+02:00"]);
+        let game = record(&["2024-06-02T21:00:00+02:00", "2024-06-01T22:00:00+02:00"]);
+        assert!(mail.score_at(&now, &plain) < game.score_at(&now, &plain));
+        assert!(mail.score_at(&now, &periodic) > game.score_at(&now, &periodic));
+        let evening = at("2024-06-03T21:00:00+02:00");
+        assert!(mail.score_at(&evening, &periodic) < game.score_at(&evening, &periodic));
+
+        // Half-life per kind comes from the ranking settings
+        let mut ranking = RankingConfig::default();
+        ranking.half_life_days.documents = 30.0;
+        assert_eq!(
+            Frecency::new(&ranking, UsageKind::Document).half_life_days,
+            30.0
+        );
+        assert_eq!(
+            Frecency::new(&ranking, UsageKind::Program).half_life_days,
+            7.0
+        );
+    }
+
+    #[test]
+    fn test_query_selections() {
+        let item = |name: &str| LaunchItem {
+            name: name.to_string(),
+            path: name.to_lowercase(),
+            icon: None,
+            args: vec![],
+            item_type: ItemType::Program,
+        };
+        let scorer = Scorer::now(&RankingConfig::default());
+        // Terminal matches "te" slightly better
+        let matches = || vec![(item("Terminal"), 33.0), (item("Telegram"), 32.0)];
+        let names = |data: &UsageData, query: &str| -> Vec<String> {
+            data.rank_matches(query, matches(), &scorer, 5)
+                .into_iter()
+                .map(|item| item.name)
+                .collect()
+        };
+
+        let mut data = UsageData::default();
+        assert_eq!(names(&data, "te"), ["Terminal", "Telegram"]);
+
+        data.record_selection("Tel", "telegram", "Telegram");
+        data.record_selection("te", "telegram", "Telegram");
+        let mut prefixes: Vec<_> = data.selections.keys().map(String::as_str).collect();
+        prefixes.sort();
+        assert_eq!(prefixes, ["t", "te", "tel"]);
+        assert_eq!(data.selections["te"]["telegram"].launches.len(), 2);
+        assert_eq!(names(&data, "te"), ["Telegram", "Terminal"]);
+        assert_eq!(names(&data, " TE "), ["Telegram", "Terminal"]);
+        // Other queries aren't affected by picks
+        assert_eq!(names(&data, "erm"), ["Terminal", "Telegram"]);
+
+        // Picks decay like launches and are cleaned up
+        let stale = Utc::now() - chrono::Duration::days(365);
+        for picks in data.selections.values_mut() {
+            for record in picks.values_mut() {
+                record.launches = vec![stale];
+            }
+        }
+        assert_eq!(names(&data, "te"), ["Terminal", "Telegram"]);
+        data.cleanup(&scorer);
+        assert!(data.selections.is_empty());
+
+        // Launches through different exec lines of one program add up
+        for _ in 0..3 {
+            data.record_program_launch(&program("terminal", "Terminal"));
+        }
+        for path in ["telegram", "/usr/bin/telegram -- %u"] {
+            data.record_program_launch(&program(path, "Telegram"));
+            data.record_program_launch(&program(path, "Telegram"));
+        }
+        assert_eq!(names(&data, "te"), ["Telegram", "Terminal"]);
+    }
+
+    #[test]
+    fn test_context_usage() {
+        let mut data = UsageData::default();
+        let scorer = Scorer::now(&RankingConfig::default());
+        data.record_context_program("kitty", &program("/usr/bin/htop", "htop"));
+        data.record_context_program("kitty", &program("/usr/bin/htop", "htop"));
+        data.record_context_program("kitty", &program("/usr/bin/nvim", "Neovim"));
+        data.record_context_program("firefox", &program("/usr/bin/keepassxc", "KeePassXC"));
+        data.record_context_snippet("kitty", "git status");
+
+        let kitty = data.context_programs("kitty", 5, &scorer);
+        assert_eq!(kitty.len(), 2);
+        assert_eq!(kitty[0].name, "htop");
+        assert_eq!(
+            data.context_programs("firefox", 5, &scorer)[0].name,
+            "KeePassXC"
+        );
+        assert!(data.context_programs("gimp", 5, &scorer).is_empty());
+        assert_eq!(
+            data.context_snippets("kitty", 5, &scorer)[0].path,
+            "git status"
+        );
+        assert!(data.context_snippets("firefox", 5, &scorer).is_empty());
+        // Global usage is recorded separately
+        assert!(data.programs.is_empty());
+
+        // Contexts left without records are dropped
+        let stale = Utc::now() - chrono::Duration::days(365);
+        for usage in data.contexts.values_mut() {
+            for record in usage
+                .programs
+                .values_mut()
+                .chain(usage.snippets.values_mut())
+            {
+                record.launches = vec![stale];
+            }
+        }
+        data.cleanup(&scorer);
+        assert!(data.contexts.is_empty());
+
+        // Suggestions launch the item as it was launched
+        let new_tab = LaunchItem {
+            args: vec!["--new-tab".to_string()],
+            item_type: ItemType::Shortcut,
+            ..program("kitty", "New Tab")
+        };
+        data.record_context_program("kitty", &new_tab);
+        data.record_context_program("kitty", &program("kitty", "Kitty"));
+        let items: Vec<LaunchItem> = data
+            .context_programs("kitty", 5, &scorer)
+            .into_iter()
+            .map(UsageRecord::item)
+            .collect();
+        assert_eq!(items.len(), 2);
+        assert!(items.contains(&new_tab));
+    }
+
+    #[test]
+    fn test_merge() {
+        let at = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        let record = |name: &str, launches: &[i64]| UsageRecord {
+            path: "firefox".to_string(),
+            name: name.to_string(),
+            launches: launches.iter().map(|&secs| at(secs)).collect(),
+            ..Default::default()
+        };
+        let mut ours = UsageData::default();
+        ours.programs
+            .insert("firefox".to_string(), record("Firefox", &[1, 3]));
+        ours.last_cleanup = Some(at(0));
+        let mut theirs = UsageData::default();
+        theirs
+            .programs
+            .insert("firefox".to_string(), record("Firefox (old)", &[2, 3]));
+        theirs.record_context_snippet("kitty", "git status");
+        theirs.record_selection("fi", "firefox", "Firefox");
+        theirs.last_cleanup = Some(at(10));
+
+        ours.merge(&theirs);
+        let firefox = &ours.programs["firefox"];
+        assert_eq!(firefox.name, "Firefox");
+        assert_eq!(firefox.launches, [at(1), at(2), at(3)]);
+        assert!(ours.contexts["kitty"].snippets.contains_key("git status"));
+        assert!(ours.selections["f"].contains_key("firefox"));
+        assert_eq!(ours.last_cleanup, Some(at(10)));
+
+        let many = record("Firefox", &(0..150).collect::<Vec<_>>());
+        let mut capped = record("Firefox", &[200]);
+        capped.merge(&many);
+        assert_eq!(capped.launches.len(), MAX_LAUNCHES);
+        assert_eq!(capped.launches.last(), Some(&at(200)));
+
+        // Per-day counts outlive truncation and cleanup
+        let mut data = UsageData::default();
+        for _ in 0..150 {
+            data.record_program_launch(&program("firefox", "Firefox"));
+        }
+        let later = Utc::now().fixed_offset() + chrono::Duration::days(365);
+        data.cleanup(&Scorer::at(later, &RankingConfig::default()));
+        assert!(data.programs.is_empty());
+        let counted = &data.daily.programs["firefox"];
+        assert_eq!(counted.name, "Firefox");
+        assert_eq!(
+            counted.days.keys().map(|day| counted.on(*day)).sum::<u32>(),
+            150
+        );
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("usage.json");
+        let (writers, saves) = (4, 10);
+
+        // Each writer starts from an empty copy, like separate processes
+        // that loaded the file before any of the others saved
+        let handles: Vec<_> = (0..writers)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let mut data = UsageData::default();
+                    for save in 0..saves {
+                        let launch = DateTime::from_timestamp(
+                            1_700_000_000 + (writer * saves + save) as i64,
+                            0,
+                        )
+                        .unwrap();
+                        for key in ["shared".to_string(), format!("writer-{}", writer)] {
+                            data.programs
+                                .entry(key.clone())
+                                .or_insert_with(|| UsageRecord {
+                                    path: key,
+                                    ..Default::default()
+                                })
+                                .launches
+                                .push(launch);
+                        }
+                        data.record_program_launch(&program("counted", "Counted"));
+                        data.save_to(&path).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stored = UsageData::load_from(&path).unwrap();
+        assert_eq!(stored.programs["shared"].launches.len(), writers * saves);
+        for writer in 0..writers {
+            let key = format!("writer-{}", writer);
+            assert_eq!(stored.programs[&key].launches.len(), saves);
+        }
+        // Counted on this machine by every writer, none lost to the merges
+        let counted = &stored.daily.programs["counted"];
+        let total: u32 = counted.days.keys().map(|day| counted.on(*day)).sum();
+        assert_eq!(total as usize, writers * saves);
+    }
 }
@@ -0,0 +1,560 @@
+//! Config file validation with line and column positions
+//!
+//! [`parse`] reports syntax and type errors where the parser found them and
+//! unknown keys as warnings; [`check`] reports settings that parse but can't
+//! work, like a zero chord threshold, and pinned programs that aren't
+//! installed.
+
+use crate::config::{Config, ConfigFormat, Gesture, LaunchItem, MouseButton};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How bad an [`Issue`] is; errors keep the file from being used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem in a config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub file: PathBuf,
+    /// 1-based line and column, if the problem could be located
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn error(file: &Path, position: Option<(usize, usize)>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            file: file.to_path_buf(),
+            position,
+            message,
+        }
+    }
+
+    pub fn warning(file: &Path, position: Option<(usize, usize)>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(file, position, message)
+        }
+    }
+}
+
+/// `config.toml:3:17: message`, with the file name only
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = self.file.file_name() {
+            write!(f, "{}:", name.to_string_lossy())?;
+            if let Some((line, column)) = self.position {
+                write!(f, "{}:{}:", line, column)?;
+            }
+            f.write_str(" ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// A config file that can't be used, with all its issues (warnings included)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invalid(pub Vec<Issue>);
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self
+            .0
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(Issue::to_string)
+            .collect();
+        write!(f, "Invalid config: {}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for Invalid {}
+
+/// Deserialize a config file, with unknown keys as warnings
+pub fn parse<T: DeserializeOwned>(
+    file: &Path,
+    content: &str,
+    format: ConfigFormat,
+) -> Result<(T, Vec<Issue>), Issue> {
+    let mut unknown = Vec::new();
+    let callback = |path: serde_ignored::Path| unknown.push(path.to_string());
+    let result = match format {
+        ConfigFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(content);
+            serde_ignored::deserialize(&mut deserializer, callback)
+                .and_then(|value| deserializer.end().map(|_| value))
+                .map_err(|e| (e.to_string(), Some((e.line(), e.column()))))
+        }
+        ConfigFormat::Toml => {
+            serde_ignored::deserialize(toml::Deserializer::new(content), callback).map_err(|e| {
+                let position = e.span().map(|span| line_col(content, span.start));
+                (e.message().to_string(), position)
+            })
+        }
+        // An empty YAML document is an empty config, not a missing value
+        ConfigFormat::Yaml
+            if serde_norway::from_str::<Value>(content).is_ok_and(|v| v.is_null()) =>
+        {
+            serde_ignored::deserialize(Value::Object(Default::default()), callback)
+                .map_err(|e| (e.to_string(), None))
+        }
+        ConfigFormat::Yaml => {
+            serde_ignored::deserialize(serde_norway::Deserializer::from_str(content), callback)
+                .map_err(|e| {
+                    let position = e.location().map(|at| (at.line(), at.column()));
+                    (e.to_string(), position)
+                })
+        }
+    };
+    match result {
+        Ok(value) => Ok((value, unknown_keys(file, content, &unknown))),
+        Err((message, position)) => {
+            // serde_json and serde_norway repeat the position in the message
+            let message = match message.find(" at line ") {
+                Some(end) => message[..end].to_string(),
+                None => message,
+            };
+            Err(Issue::error(file, position, message))
+        }
+    }
+}
+
+/// Deserialize an already parsed (migrated) config file
+///
+/// Positions of unknown keys are looked up in the file's `content`; errors
+/// have no position.
+pub fn parse_value<T: DeserializeOwned>(
+    file: &Path,
+    content: &str,
+    value: Value,
+) -> Result<(T, Vec<Issue>), Issue> {
+    let mut unknown = Vec::new();
+    let value = serde_ignored::deserialize(value, |path| unknown.push(path.to_string()))
+        .map_err(|e| Issue::error(file, None, e.to_string()))?;
+    Ok((value, unknown_keys(file, content, &unknown)))
+}
+
+fn unknown_keys(file: &Path, content: &str, paths: &[String]) -> Vec<Issue> {
+    paths
+        .iter()
+        .map(|path| {
+            // `shortcuts.0.colour` is found as `colour` after `shortcuts`
+            let words: Vec<&str> = path
+                .split('.')
+                .filter(|word| *word != "?" && word.parse::<usize>().is_err())
+                .collect();
+            Issue::warning(
+                file,
+                locate(content, &words),
+                format!("unknown key `{}` is ignored", path),
+            )
+        })
+        .collect()
+}
+
+/// Check the settings of a parsed config; `content` is its file, for positions
+pub fn check(config: &Config, file: &Path, content: &str) -> Vec<Issue> {
+    let mut checker = Checker {
+        file,
+        content,
+        issues: Vec::new(),
+    };
+
+    let trigger = &config.trigger;
+    checker.range(
+        "trigger.simultaneous_threshold_ms",
+        trigger.simultaneous_threshold_ms,
+        1..=2000,
+    );
+    checker.range("trigger.debounce_ms", trigger.debounce_ms, 0..=60_000);
+    for rule in &trigger.devices {
+        if let Some(threshold) = rule.simultaneous_threshold_ms {
+            checker.range("devices.simultaneous_threshold_ms", threshold, 1..=2000);
+        }
+        if let Some(debounce) = rule.debounce_ms {
+            checker.range("devices.debounce_ms", debounce, 0..=60_000);
+        }
+    }
+    for gesture in &trigger.gestures {
+        match &gesture.gesture {
+            Gesture::LongPress { duration_ms, .. } => {
+                checker.range("gestures.duration_ms", *duration_ms, 1..=10_000)
+            }
+            Gesture::DoubleClick { interval_ms, .. } | Gesture::DoubleTap { interval_ms, .. } => {
+                checker.range("gestures.interval_ms", *interval_ms, 1..=2000)
+            }
+            Gesture::HotCorner { dwell_ms, .. } => {
+                checker.range("gestures.dwell_ms", *dwell_ms, 0..=10_000)
+            }
+            Gesture::TouchTap {
+                fingers,
+                max_duration_ms,
+            } => {
+                checker.range("gestures.fingers", *fingers, 1..=10);
+                checker.range("gestures.max_duration_ms", *max_duration_ms, 1..=2000);
+            }
+            Gesture::TouchSwipe {
+                fingers, distance, ..
+            } => {
+                checker.range("gestures.fingers", *fingers, 1..=10);
+                checker.range("gestures.distance", *distance, 0.01..=1.0);
+            }
+            Gesture::TouchHold {
+                fingers,
+                duration_ms,
+            } => {
+                checker.range("gestures.fingers", *fingers, 1..=10);
+                checker.range("gestures.duration_ms", *duration_ms, 1..=10_000);
+            }
+            Gesture::Chord { buttons } => checker.chord(buttons),
+            Gesture::ModifierClick { .. } | Gesture::Hotkey { .. } => {}
+        }
+        if let Some(profile) = &gesture.profile {
+            checker.profile_exists(config, "gestures", profile);
+        }
+    }
+
+    checker.range(
+        "max_frequent_programs",
+        config.max_frequent_programs,
+        0..=100,
+    );
+    checker.range(
+        "max_frequent_documents",
+        config.max_frequent_documents,
+        0..=100,
+    );
+    checker.range(
+        "max_clipboard_history",
+        config.max_clipboard_history,
+        1..=100_000,
+    );
+    checker.range("ui.width", config.ui.width, 100.0..=4000.0);
+    checker.range("ui.margin", config.ui.margin, 0.0..=500.0);
+
+    let ranking = &config.ranking;
+    for (key, weight) in [
+        ("ranking.usage_weight", ranking.usage_weight),
+        ("ranking.platform_weight", ranking.platform_weight),
+        ("ranking.query_weight", ranking.query_weight),
+        ("ranking.time_of_day_weight", ranking.time_of_day_weight),
+        ("ranking.weekday_weight", ranking.weekday_weight),
+    ] {
+        checker.range(key, weight, 0.0..=100.0);
+    }
+    let half_lives = &ranking.half_life_days;
+    for (key, days) in [
+        ("half_life_days.programs", half_lives.programs),
+        ("half_life_days.documents", half_lives.documents),
+        ("half_life_days.snippets", half_lives.snippets),
+    ] {
+        checker.range(key, days, 0.01..=3650.0);
+    }
+    checker.range(
+        "ranking.time_of_day_bucket_hours",
+        ranking.time_of_day_bucket_hours,
+        1..=24,
+    );
+
+    checker.items("pinned_programs", &config.pinned_programs);
+    checker.items("shortcuts", &config.shortcuts);
+    for profile in &config.profiles {
+        if let Some(pins) = &profile.pinned_programs {
+            checker.items("pinned_programs", pins);
+        }
+        if let Some(shortcuts) = &profile.shortcuts {
+            checker.items("shortcuts", shortcuts);
+        }
+        if let Some(max) = profile.max_frequent_programs {
+            checker.range("profiles.max_frequent_programs", max, 0..=100);
+        }
+        if let Some(max) = profile.max_frequent_documents {
+            checker.range("profiles.max_frequent_documents", max, 0..=100);
+        }
+    }
+    if let Some(profile) = &config.default_profile {
+        checker.profile_exists(config, "default_profile", profile);
+    }
+
+    checker.issues
+}
+
+struct Checker<'a> {
+    file: &'a Path,
+    content: &'a str,
+    issues: Vec<Issue>,
+}
+
+impl Checker<'_> {
+    /// Error unless `value` of `key` (a dotted path) is in `range`
+    fn range<T: PartialOrd + fmt::Display>(
+        &mut self,
+        key: &str,
+        value: T,
+        range: std::ops::RangeInclusive<T>,
+    ) {
+        if !range.contains(&value) {
+            let words: Vec<&str> = key.split('.').collect();
+            self.issues.push(Issue::error(
+                self.file,
+                locate(self.content, &words),
+                format!(
+                    "`{}` is {}, expected {} to {}",
+                    key,
+                    value,
+                    range.start(),
+                    range.end()
+                ),
+            ));
+        }
+    }
+
+    /// Warn about programs that aren't installed and names used twice
+    fn items(&mut self, section: &str, items: &[LaunchItem]) {
+        for (i, item) in items.iter().enumerate() {
+            if item.item_type != crate::ItemType::Document && !program_exists(&item.path) {
+                self.issues.push(Issue::warning(
+                    self.file,
+                    locate(self.content, &[section, &item.name, &item.path]),
+                    format!("`{}` of `{}` is not installed", item.path, item.name),
+                ));
+            }
+            if section == "shortcuts" && items[..i].iter().any(|other| other.name == item.name) {
+                self.issues.push(Issue::warning(
+                    self.file,
+                    locate(self.content, &[section, &item.name, &item.name]),
+                    format!("duplicate shortcut `{}`", item.name),
+                ));
+            }
+        }
+    }
+
+    /// Error unless a chord has at least two different buttons
+    fn chord(&mut self, buttons: &[MouseButton]) {
+        let message = if buttons.len() < 2 {
+            format!(
+                "`gestures.buttons` of a chord has {} button(s), expected at least 2",
+                buttons.len()
+            )
+        } else if let Some(button) = (1..buttons.len())
+            .find(|&i| buttons[..i].contains(&buttons[i]))
+            .map(|i| buttons[i])
+        {
+            format!(
+                "`gestures.buttons` of a chord has `{}` twice",
+                format!("{:?}", button).to_lowercase()
+            )
+        } else {
+            return;
+        };
+        self.issues.push(Issue::error(
+            self.file,
+            locate(self.content, &["gestures", "buttons"]),
+            message,
+        ));
+    }
+
+    fn profile_exists(&mut self, config: &Config, key: &str, name: &str) {
+        if config.profile(name).is_none() {
+            self.issues.push(Issue::warning(
+                self.file,
+                locate(self.content, &[key, name]),
+                format!("unknown profile `{}`", name),
+            ));
+        }
+    }
+}
+
+/// Whether `path` is a program that exists, as a path or on `PATH`
+///
+/// URLs count as existing; a command line is checked by its first word.
+fn program_exists(path: &str) -> bool {
+    let exists = |program: &str| {
+        let program = Path::new(program);
+        if program.components().count() > 1 {
+            return program.exists();
+        }
+        let Some(paths) = std::env::var_os("PATH") else {
+            return true;
+        };
+        std::env::split_paths(&paths).any(|dir| {
+            let candidate = dir.join(program);
+            candidate.is_file()
+                || cfg!(target_os = "windows")
+                    && ["exe", "com", "bat", "cmd"]
+                        .iter()
+                        .any(|ext| candidate.with_extension(ext).is_file())
+        })
+    };
+    path.contains("://")
+        || exists(path)
+        || path
+            .split_whitespace()
+            .next()
+            .is_some_and(|first| first != path && exists(first))
+}
+
+/// Position of the last of `words`, each found after the previous one
+///
+/// `["trigger", "debounce_ms"]` finds the first `debounce_ms` after the
+/// first `trigger`, whether it's a JSON key, a TOML key or a TOML table name.
+fn locate(content: &str, words: &[&str]) -> Option<(usize, usize)> {
+    let mut start = 0;
+    let mut found = None;
+    for word in words {
+        let offset = start + find_word(&content[start..], word)?;
+        found = Some(offset);
+        start = offset + word.len();
+    }
+    found.map(|offset| line_col(content, offset))
+}
+
+fn find_word(haystack: &str, word: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    if word.is_empty() {
+        return None;
+    }
+    haystack.match_indices(word).map(|(i, _)| i).find(|&i| {
+        !haystack[..i].chars().next_back().is_some_and(is_word)
+            && !haystack[i + word.len()..]
+                .chars()
+                .next()
+                .is_some_and(is_word)
+    })
+}
+
+/// 1-based line and column (in characters) of a byte offset
+fn line_col(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions_and_checks() {
+        let file = Path::new("/tmp/config.toml");
+        let positions = |issues: &[Issue]| -> Vec<Option<(usize, usize)>> {
+            issues.iter().map(|issue| issue.position).collect()
+        };
+
+        // Syntax and type errors, in every format
+        let error =
+            parse::<Config>(file, "[trigger]\ndebounce_ms = = 3", ConfigFormat::Toml).unwrap_err();
+        assert_eq!(error.position, Some((2, 15)));
+        assert_eq!(error.severity, Severity::Error);
+        let error = parse::<Config>(
+            file,
+            "{\n  \"trigger\": {\"debounce_ms\": -5}\n}",
+            ConfigFormat::Json,
+        )
+        .unwrap_err();
+        assert_eq!(error.position.map(|(line, _)| line), Some(2));
+        assert!(!error.message.contains(" at line "), "{}", error.message);
+        let error = parse::<Config>(file, "ui:\n  width: wide\n", ConfigFormat::Yaml).unwrap_err();
+        assert_eq!(error.position.map(|(line, _)| line), Some(2));
+        assert_eq!(error.to_string().split(':').nth(1), Some("2"));
+        assert!(parse::<Config>(file, "", ConfigFormat::Yaml).is_ok());
+
+        // Unknown keys, impossible values, duplicates, missing programs
+        let content = r#"
+max_frequent_programs = 5
+colour = "red"
+
+[[shortcuts]]
+name = "Lock"
+path = "surely-not-an-installed-program"
+
+[[shortcuts]]
+name = "Lock"
+path = "https://example.com"
+
+[trigger]
+simultaneous_threshold_ms = 0
+
+[ui]
+width = 20
+"#;
+        let (mut config, warnings) = parse::<Config>(file, content, ConfigFormat::Toml).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "unknown key `colour` is ignored");
+        assert_eq!(
+            warnings[0].to_string(),
+            "config.toml:3:1: unknown key `colour` is ignored"
+        );
+
+        let issues = check(&config, file, content);
+        let errors: Vec<&Issue> = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].message,
+            "`trigger.simultaneous_threshold_ms` is 0, expected 1 to 2000"
+        );
+        assert_eq!(errors[0].position, Some((14, 1)));
+        assert_eq!(errors[1].position, Some((17, 1)));
+        let warnings: Vec<&Issue> = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .collect();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| w.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "`surely-not-an-installed-program` of `Lock` is not installed",
+                "duplicate shortcut `Lock`"
+            ]
+        );
+        assert_eq!(positions(&issues[2..]), [Some((7, 9)), Some((10, 9))]);
+
+        // An installed program by path, and a command line
+        let exe = std::env::current_exe().unwrap();
+        config.shortcuts = vec![LaunchItem {
+            name: "Self".to_string(),
+            path: format!("{} --help", exe.display()),
+            icon: None,
+            args: vec![],
+            item_type: crate::ItemType::Shortcut,
+        }];
+        config.trigger.simultaneous_threshold_ms = 200;
+        config.ui.width = 300.0;
+        assert_eq!(check(&config, file, content), []);
+
+        // Chords need two different buttons
+        let content = r#"
+[[trigger.gestures]]
+type = "chord"
+buttons = ["left", "left"]
+"#;
+        let (config, _) = parse::<Config>(file, content, ConfigFormat::Toml).unwrap();
+        let issues = check(&config, file, content);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].message,
+            "`gestures.buttons` of a chord has `left` twice"
+        );
+        assert_eq!(issues[0].position, Some((4, 1)));
+        let content = "[[trigger.gestures]]\ntype = \"chord\"\nbuttons = [\"middle\"]\n";
+        let (config, _) = parse::<Config>(file, content, ConfigFormat::Toml).unwrap();
+        assert_eq!(
+            check(&config, file, content)[0].message,
+            "`gestures.buttons` of a chord has 1 button(s), expected at least 2"
+        );
+    }
+}
@@ -1,21 +1,23 @@
 //! Main UI application logic using egui
 
+use crate::control::PopupControl;
+use crate::placement::{Monitor, PopupAnchor};
 use crate::theme::{dark_theme, ThemeColors};
 use arboard::Clipboard;
-use chrono::Utc;
+use chrono::{Local, Utc};
 use eframe::egui::{self, CentralPanel, Context, Key, RichText, ScrollArea, Vec2};
 use launcher_core::{
-    config::{ItemType, LaunchItem},
+    config::{Config, ItemType, LaunchItem},
+    persist,
     platform::{get_data_source, PlatformDataSource},
-    ConfigManager, UsageTracker,
+    ConfigManager, Issue, Layout, Section, Severity, UsageRecord, UsageTracker,
 };
 use serde::{Deserialize, Serialize};
-use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-/// Default display limit for clipboard in UI (scrollable for more)
-const CLIPBOARD_DISPLAY_LIMIT: usize = 10;
+/// Config issues listed in the popup banner; the rest are only logged
+const MAX_BANNER_ISSUES: usize = 3;
 
 /// Extract the base command name from a path (e.g., "/usr/bin/python3" → "python3")
 fn base_cmd(path: &str) -> &str {
@@ -107,7 +109,7 @@ fn fuzzy_search_clipboard(
         })
         .collect();
 
-    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
     scored
         .into_iter()
         .take(limit)
@@ -115,6 +117,34 @@ fn fuzzy_search_clipboard(
         .collect()
 }
 
+/// Platform programs considered when ranking, so popup usage can lift
+/// programs outside the platform's own top entries
+const RANKING_POOL: usize = 50;
+
+/// Search results shown (and reachable with keys 1-9)
+const SEARCH_LIMIT: usize = 9;
+
+/// Frequent programs with those used over the focused app first
+fn context_first(context: Vec<LaunchItem>, frequent: Vec<LaunchItem>) -> Vec<LaunchItem> {
+    let mut items = context;
+    for item in frequent {
+        if !items.iter().any(|i| same_item(i, &item)) {
+            items.push(item);
+        }
+    }
+    items
+}
+
+/// Move snippets pasted over the focused app (most used first) to the top
+fn snippets_first(results: &mut [ClipboardEntry], snippets: &[String]) {
+    results.sort_by_key(|entry| {
+        snippets
+            .iter()
+            .position(|text| *text == entry.text)
+            .unwrap_or(snippets.len())
+    });
+}
+
 /// Truncate a string to `max_len` bytes (including "..."), respecting UTF-8 char boundaries.
 fn truncate_preview(s: &str, max_len: usize) -> String {
     if s.len() > max_len {
@@ -182,8 +212,7 @@ impl ClipboardEntry {
 fn eval_math(text: &str) -> Option<f64> {
     let expr = text
         .trim()
-        .replace('x', "*")
-        .replace('×', "*")
+        .replace(['x', '×'], "*")
         .replace('÷', "/")
         .replace(',', ".")
         .replace(' ', "");
@@ -213,37 +242,30 @@ fn clipboard_file_path() -> PathBuf {
 /// Load clipboard history from disk
 fn load_clipboard_history() -> Vec<ClipboardEntry> {
     let path = clipboard_file_path();
-    if !path.exists() {
-        return Vec::new();
-    }
-
-    match fs::read_to_string(&path) {
-        Ok(content) => {
-            let mut entries: Vec<ClipboardEntry> =
-                serde_json::from_str(&content).unwrap_or_default();
-            // Update previews (since they're skipped in serialization)
-            for entry in &mut entries {
-                entry.update_preview();
-            }
-            // Sort by count DESC, then last_used DESC
-            entries.sort_by(|a, b| {
-                b.count
-                    .cmp(&a.count)
-                    .then_with(|| b.last_used.cmp(&a.last_used))
-            });
-            entries
+    let mut entries: Vec<ClipboardEntry> = match persist::load_json(&path) {
+        Ok(entries) => entries.unwrap_or_default(),
+        Err(e) => {
+            log::error!("{:#}; starting with empty clipboard history", e);
+            Vec::new()
         }
-        Err(_) => Vec::new(),
+    };
+
+    // Update previews (since they're skipped in serialization)
+    for entry in &mut entries {
+        entry.update_preview();
     }
+    // Sort by count DESC, then last_used DESC
+    entries.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| b.last_used.cmp(&a.last_used))
+    });
+    entries
 }
 
 /// Save clipboard history to disk with smart eviction
 fn save_clipboard_history(history: &[ClipboardEntry], max_size: usize) {
     let path = clipboard_file_path();
-    if let Some(parent) = path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-
     let mut to_save: Vec<ClipboardEntry> = history.to_vec();
 
     // Smart eviction: if over limit, remove items with lowest (count, last_used)
@@ -263,15 +285,49 @@ fn save_clipboard_history(history: &[ClipboardEntry], max_size: usize) {
         });
     }
 
-    if let Ok(json) = serde_json::to_string_pretty(&to_save) {
-        let _ = fs::write(&path, json);
+    if let Err(e) = persist::save_json(&path, &to_save) {
+        log::error!("Failed to save clipboard history: {:#}", e);
+    }
+}
+
+/// Add a new entry to the history, or refresh `last_used` if it already exists
+fn upsert_clipboard_entry(history: &mut Vec<ClipboardEntry>, text: String) {
+    if let Some(existing) = history.iter_mut().find(|e| e.text == text) {
+        existing.last_used = Some(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        existing.update_preview();
+    } else {
+        history.insert(0, ClipboardEntry::new(text));
+    }
+}
+
+/// Persisted clipboard entries with how often each was pasted
+pub fn clipboard_paste_counts() -> Vec<(String, u32)> {
+    load_clipboard_history()
+        .into_iter()
+        .map(|entry| (entry.text, entry.count))
+        .collect()
+}
+
+/// Add text to the persisted clipboard history without opening the popup
+pub fn add_clipboard_entry(text: &str, max_size: usize) {
+    if text.is_empty() {
+        return;
     }
+    let mut history = load_clipboard_history();
+    upsert_clipboard_entry(&mut history, text.to_string());
+    save_clipboard_history(&history, max_size);
 }
 
 /// The launcher popup application
 pub struct LauncherApp {
     config_manager: Arc<ConfigManager>,
     usage_tracker: Arc<Mutex<UsageTracker>>,
+    control: PopupControl,
+    anchor: PopupAnchor,
+    /// Active popup profile (`None` for the global layout)
+    profile: Option<String>,
+    /// App id of the window focused when the popup was triggered
+    context: Option<String>,
     platform: Box<dyn PlatformDataSource + Send>,
     clipboard: Option<Clipboard>,
     clipboard_history: Vec<ClipboardEntry>,
@@ -280,11 +336,19 @@ pub struct LauncherApp {
     // UI state
     frequent_programs: Vec<LaunchItem>,
     recent_documents: Vec<LaunchItem>,
+    /// Snippets pasted over the focused app, most used first
+    context_snippets: Vec<String>,
     should_close: bool,
     show_add_dialog: bool,
     add_dialog_name: String,
     add_dialog_path: String,
     clipboard_search_query: String,
+    app_search_query: String,
+    /// Installed apps, loaded on the first search
+    installed_apps: Option<Vec<LaunchItem>>,
+    /// Ranked results and the query they are for (`None` when stale)
+    search_results: Vec<LaunchItem>,
+    search_results_query: Option<String>,
 
     // Pending actions (to avoid borrow issues)
     pending_launch: Option<LaunchItem>,
@@ -292,6 +356,9 @@ pub struct LauncherApp {
     pending_paste: Option<String>,
     pending_pin_clipboard: Option<String>,
     pending_unpin_clipboard: Option<String>,
+    pending_profile: Option<Option<String>>,
+    /// Why the last pin or shortcut couldn't be saved, until one is
+    save_error: Option<String>,
 
     // Frame counter for delayed focus check
     frame_count: u32,
@@ -300,6 +367,7 @@ pub struct LauncherApp {
     width: f32,
     last_height: f32,
     last_resize_frame: u32,
+    position: (f32, f32),
     has_been_focused: bool,
 }
 
@@ -307,69 +375,191 @@ impl LauncherApp {
     pub fn new(
         config_manager: Arc<ConfigManager>,
         usage_tracker: Arc<Mutex<UsageTracker>>,
+        control: PopupControl,
+        anchor: PopupAnchor,
         width: f32,
+        profile: Option<String>,
+        context: Option<String>,
     ) -> Self {
         let platform = Box::new(get_data_source());
         let clipboard = Clipboard::new().ok();
 
-        // Get config values then drop the lock
-        let (max_frequent_programs, max_frequent_documents) = {
-            let config = config_manager.get();
-            (config.max_frequent_programs, config.max_frequent_documents)
-        };
-
-        let frequent_programs = platform
-            .frequent_programs(max_frequent_programs)
-            .unwrap_or_default();
-        let recent_documents = platform
-            .recent_files(max_frequent_documents)
-            .unwrap_or_default();
+        // Load clipboard history from disk; from now on, external clients
+        // add to it through `control` rather than the file
+        let clipboard_history = control.open_clipboard(load_clipboard_history);
 
-        // Load clipboard history from disk
-        let clipboard_history = load_clipboard_history();
+        let position = anchor.geometry((width, 400.0)).position;
 
-        Self {
+        let mut app = Self {
             config_manager,
             usage_tracker,
+            control,
+            anchor,
+            profile,
+            context,
             platform,
             clipboard,
             clipboard_history,
             last_clipboard_content: String::new(),
-            frequent_programs,
-            recent_documents,
+            frequent_programs: Vec::new(),
+            recent_documents: Vec::new(),
+            context_snippets: Vec::new(),
             should_close: false,
             show_add_dialog: false,
             add_dialog_name: String::new(),
             add_dialog_path: String::new(),
             clipboard_search_query: String::new(),
+            app_search_query: String::new(),
+            installed_apps: None,
+            search_results: Vec::new(),
+            search_results_query: None,
             pending_launch: None,
             pending_pin: None,
             pending_paste: None,
             pending_pin_clipboard: None,
             pending_unpin_clipboard: None,
+            pending_profile: None,
+            save_error: None,
             frame_count: 0,
             width,
             last_height: 400.0,
             last_resize_frame: 0,
+            position,
             has_been_focused: false,
-        }
+        };
+        app.refresh();
+        app
     }
 
     /// Refresh data from platform sources
     pub fn refresh(&mut self) {
-        let (max_frequent_programs, max_frequent_documents) = {
-            let config = self.config_manager.get();
-            (config.max_frequent_programs, config.max_frequent_documents)
-        };
-
-        self.frequent_programs = self
+        let layout = self.config_manager.get().layout(self.profile.as_deref());
+        // The profile may have been removed from the config
+        self.profile = layout.profile.clone();
+
+        let frequent = self.ranked_programs(&layout);
+        let (context_programs, context_snippets) = self.context_usage(&layout);
+        self.frequent_programs = context_first(context_programs, frequent);
+        self.context_snippets = context_snippets;
+        self.search_results_query = None;
+        self.recent_documents = self
             .platform
-            .frequent_programs(max_frequent_programs)
+            .recent_files(layout.max_frequent_documents)
             .unwrap_or_default();
-        self.recent_documents = self
+    }
+
+    /// Frequent programs, ranked from popup launches and platform signals
+    fn ranked_programs(&self, layout: &Layout) -> Vec<LaunchItem> {
+        let platform = self
             .platform
-            .recent_files(max_frequent_documents)
+            .program_frequencies(RANKING_POOL)
             .unwrap_or_default();
+        let ranking = self.config_manager.get().ranking.clone();
+        match self.usage_tracker.lock() {
+            Ok(tracker) => tracker.rank_programs(platform, &ranking, layout.max_frequent_programs),
+            Err(_) => platform
+                .into_iter()
+                .take(layout.max_frequent_programs)
+                .map(|(item, _)| item)
+                .collect(),
+        }
+    }
+
+    /// Programs launched and snippets pasted over the focused app
+    fn context_usage(&self, layout: &Layout) -> (Vec<LaunchItem>, Vec<String>) {
+        let (Some(context), Ok(tracker)) = (&self.context, self.usage_tracker.lock()) else {
+            return Default::default();
+        };
+        let ranking = self.config_manager.get().ranking.clone();
+        let programs = tracker
+            .context_programs(context, layout.max_frequent_programs, &ranking)
+            .into_iter()
+            .map(UsageRecord::item)
+            .collect();
+        let snippets = tracker
+            .context_snippets(context, layout.max_clipboard_items, &ranking)
+            .into_iter()
+            .map(|record| record.path.clone())
+            .collect();
+        (programs, snippets)
+    }
+
+    /// Rank apps matching the search query, if it changed
+    fn update_search(&mut self, layout: &Layout) {
+        if self.search_results_query.as_ref() == Some(&self.app_search_query) {
+            return;
+        }
+        self.search_results_query = Some(self.app_search_query.clone());
+        self.search_results.clear();
+        let query = self.app_search_query.trim();
+        if query.is_empty() {
+            return;
+        }
+
+        let platform = &self.platform;
+        let apps = self
+            .installed_apps
+            .get_or_insert_with(|| platform.installed_apps().unwrap_or_default());
+        let mut matches: Vec<(LaunchItem, f64)> = Vec::new();
+        for item in layout
+            .pinned_programs
+            .iter()
+            .chain(&layout.shortcuts)
+            .chain(apps.iter())
+        {
+            let score = fuzzy_score(query, &item.name);
+            if score > 0 && !matches.iter().any(|(m, _)| same_item(m, item)) {
+                matches.push((item.clone(), score as f64));
+            }
+        }
+
+        let ranking = self.config_manager.get().ranking.clone();
+        if let Ok(tracker) = self.usage_tracker.lock() {
+            self.search_results = tracker.rank_matches(query, matches, &ranking, SEARCH_LIMIT);
+        }
+    }
+
+    /// Items reachable with keys 1-9, in display order
+    fn numbered_items(&self, layout: &Layout) -> Vec<LaunchItem> {
+        if !self.app_search_query.trim().is_empty() {
+            return self.search_results.clone();
+        }
+        self.item_sections(layout)
+            .into_iter()
+            .flat_map(|(_, items)| items)
+            .collect()
+    }
+
+    /// Launchable items of each item section in the layout, as displayed
+    fn item_sections(&self, layout: &Layout) -> Vec<(Section, Vec<LaunchItem>)> {
+        layout
+            .sections
+            .iter()
+            .filter_map(|&section| {
+                let items = match section {
+                    Section::PinnedPrograms => layout.pinned_programs.clone(),
+                    Section::FrequentPrograms => self
+                        .frequent_programs
+                        .iter()
+                        .filter(|p| !layout.pinned_programs.iter().any(|pp| same_item(pp, p)))
+                        .filter(|p| !layout.shortcuts.iter().any(|s| same_item(s, p)))
+                        .take(layout.max_frequent_programs)
+                        .cloned()
+                        .collect(),
+                    Section::PinnedDocuments => layout.pinned_documents.clone(),
+                    Section::RecentDocuments => self
+                        .recent_documents
+                        .iter()
+                        .filter(|d| !layout.pinned_documents.iter().any(|pd| same_item(pd, d)))
+                        .take(layout.max_frequent_documents)
+                        .cloned()
+                        .collect(),
+                    Section::Shortcuts => layout.shortcuts.clone(),
+                    Section::Clipboard => return None,
+                };
+                Some((section, items))
+            })
+            .collect()
     }
 
     /// Update clipboard history
@@ -385,19 +575,7 @@ impl LauncherApp {
                         return;
                     }
 
-                    // Check if entry already exists
-                    if let Some(existing) =
-                        self.clipboard_history.iter_mut().find(|e| e.text == text)
-                    {
-                        // Update last_used timestamp
-                        existing.last_used =
-                            Some(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string());
-                        existing.update_preview();
-                    } else {
-                        // Add new entry
-                        let entry = ClipboardEntry::new(text);
-                        self.clipboard_history.insert(0, entry);
-                    }
+                    upsert_clipboard_entry(&mut self.clipboard_history, text);
 
                     // Save to disk with smart eviction
                     let max_history = self.config_manager.get().max_clipboard_history;
@@ -414,6 +592,18 @@ impl LauncherApp {
         }
     }
 
+    /// Add clipboard text from external clients (`launcher add-clipboard`)
+    fn add_external_clipboard(&mut self, texts: Vec<String>) {
+        if texts.is_empty() {
+            return;
+        }
+        for text in texts.into_iter().filter(|text| !text.is_empty()) {
+            upsert_clipboard_entry(&mut self.clipboard_history, text);
+        }
+        let max_history = self.config_manager.get().max_clipboard_history;
+        save_clipboard_history(&self.clipboard_history, max_history);
+    }
+
     /// Launch an item and record usage
     fn launch_item(&mut self, item: &LaunchItem) {
         if let Err(e) = self.platform.launch(item) {
@@ -423,9 +613,17 @@ impl LauncherApp {
 
         // Record usage
         if let Ok(mut tracker) = self.usage_tracker.lock() {
+            // Only search results are shown while there is a query
+            let query = self.app_search_query.trim();
+            if !query.is_empty() {
+                tracker.record_selection(query, &item.path, &item.name);
+            }
             match item.item_type {
                 ItemType::Program | ItemType::Shortcut => {
-                    tracker.record_program(&item.path, &item.name);
+                    tracker.record_program(item);
+                    if let Some(context) = &self.context {
+                        tracker.record_context_program(context, item);
+                    }
                 }
                 ItemType::Document => {
                     tracker.record_document(&item.path, &item.name);
@@ -434,6 +632,7 @@ impl LauncherApp {
             let _ = tracker.save_if_dirty();
         }
 
+        self.control.notify_launched(item);
         self.should_close = true;
     }
 
@@ -450,6 +649,11 @@ impl LauncherApp {
         let max_history = self.config_manager.get().max_clipboard_history;
         save_clipboard_history(&self.clipboard_history, max_history);
 
+        if let (Some(context), Ok(mut tracker)) = (&self.context, self.usage_tracker.lock()) {
+            tracker.record_context_snippet(context, text);
+            let _ = tracker.save_if_dirty();
+        }
+
         // Set clipboard and close
         if let Some(ref mut clipboard) = self.clipboard {
             let _ = clipboard.set_text(text);
@@ -458,11 +662,20 @@ impl LauncherApp {
     }
 
     /// Pin an item to config
-    fn pin_item(&self, item: LaunchItem) {
-        let _ = self.config_manager.modify(|config| match item.item_type {
-            ItemType::Program | ItemType::Shortcut => config.pin_program(item),
-            ItemType::Document => config.pin_document(item),
-        });
+    fn pin_item(&mut self, item: LaunchItem) {
+        let profile = self.profile.clone();
+        self.modify_config(|config| config.pin_in_profile(profile.as_deref(), item));
+    }
+
+    /// Change and save the config, keeping the error to show in the popup
+    fn modify_config(&mut self, f: impl FnOnce(&mut Config)) {
+        match self.config_manager.modify(f) {
+            Ok(()) => self.save_error = None,
+            Err(e) => {
+                log::warn!("Failed to save config: {:#}", e);
+                self.save_error = Some(format!("{:#}", e));
+            }
+        }
     }
 
     /// Draw a section header
@@ -510,17 +723,294 @@ impl LauncherApp {
 
         // Handle pending clipboard pin
         if let Some(text) = self.pending_pin_clipboard.take() {
-            let _ = self.config_manager.modify(|cfg| {
+            self.modify_config(|cfg| {
                 cfg.pin_clipboard(text);
             });
         }
 
         // Handle pending clipboard unpin
         if let Some(text) = self.pending_unpin_clipboard.take() {
-            let _ = self.config_manager.modify(|cfg| {
+            self.modify_config(|cfg| {
                 cfg.unpin_clipboard(&text);
             });
         }
+
+        // Handle profile switch from the header
+        if let Some(profile) = self.pending_profile.take() {
+            self.profile = profile;
+            self.refresh();
+        }
+    }
+
+    /// Draw the problems of the config file, errors first
+    fn config_banner(ui: &mut egui::Ui, issues: &[Issue]) {
+        let errors = issues.iter().any(|i| i.severity == Severity::Error);
+        let (color, title) = if errors {
+            (
+                ThemeColors::ERROR,
+                "Config has errors, using the last working config",
+            )
+        } else {
+            (ThemeColors::WARNING, "Config warnings")
+        };
+        egui::Frame::NONE
+            .stroke(egui::Stroke::new(1.0, color))
+            .corner_radius(3.0)
+            .inner_margin(4.0)
+            .show(ui, |ui| {
+                ui.label(RichText::new(title).color(color).strong());
+                for issue in issues.iter().take(MAX_BANNER_ISSUES) {
+                    ui.label(
+                        RichText::new(issue.to_string())
+                            .color(ThemeColors::TEXT)
+                            .size(11.0),
+                    );
+                }
+                if issues.len() > MAX_BANNER_ISSUES {
+                    ui.label(
+                        RichText::new(format!(
+                            "and {} more (see the log)",
+                            issues.len() - MAX_BANNER_ISSUES
+                        ))
+                        .color(ThemeColors::DIM_TEXT)
+                        .size(11.0),
+                    );
+                }
+            });
+        ui.add_space(4.0);
+    }
+
+    /// Draw why the last change to the config wasn't saved
+    fn save_error_banner(ui: &mut egui::Ui, error: &str) {
+        egui::Frame::NONE
+            .stroke(egui::Stroke::new(1.0, ThemeColors::ERROR))
+            .corner_radius(3.0)
+            .inner_margin(4.0)
+            .show(ui, |ui| {
+                ui.label(
+                    RichText::new("Change not saved")
+                        .color(ThemeColors::ERROR)
+                        .strong(),
+                );
+                ui.label(RichText::new(error).color(ThemeColors::TEXT).size(11.0));
+            });
+        ui.add_space(4.0);
+    }
+
+    /// Draw the profile switcher
+    fn profile_header(&mut self, ui: &mut egui::Ui, profiles: &[String]) {
+        ui.horizontal_wrapped(|ui| {
+            let choices = std::iter::once(None).chain(profiles.iter().map(Some));
+            for choice in choices {
+                let selected = self.profile.as_ref() == choice;
+                let label = choice.map_or("Default", |name| name.as_str());
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    self.pending_profile = Some(choice.cloned());
+                }
+            }
+        });
+        Self::separator(ui);
+    }
+
+    /// Draw a section of launchable items with 1-9 shortcut numbers
+    fn item_section(
+        &mut self,
+        ui: &mut egui::Ui,
+        section: Section,
+        items: &[LaunchItem],
+        shortcut_num: &mut usize,
+    ) {
+        if items.is_empty() {
+            return;
+        }
+        let title = match section {
+            Section::PinnedPrograms => "Pinned Programs",
+            Section::FrequentPrograms => "Frequent Programs",
+            Section::PinnedDocuments => "Pinned Documents",
+            Section::RecentDocuments => "Recent Documents",
+            Section::Shortcuts => "Shortcuts",
+            Section::Clipboard => "Clipboard History",
+        };
+        // Suggestions get a pin button, pinned items and shortcuts an icon
+        let marker = match section {
+            Section::FrequentPrograms | Section::RecentDocuments => None,
+            Section::Shortcuts => Some(("\u{26A1}", ThemeColors::SHORTCUT_ICON)), // ⚡
+            _ => Some(("\u{1F4CC}", ThemeColors::PIN_ICON)),                      // 📌
+        };
+        self.item_list(ui, title, items, marker, shortcut_num);
+    }
+
+    /// Draw a titled list of launchable items; items without a marker icon
+    /// get a pin button
+    fn item_list(
+        &mut self,
+        ui: &mut egui::Ui,
+        title: &str,
+        items: &[LaunchItem],
+        marker: Option<(&str, egui::Color32)>,
+        shortcut_num: &mut usize,
+    ) {
+        let pinnable = marker.is_none();
+        Self::section_header(ui, title);
+        for item in items {
+            ui.horizontal(|ui| {
+                if *shortcut_num <= 9 {
+                    ui.label(
+                        RichText::new(format!("[{}]", shortcut_num))
+                            .color(ThemeColors::DIM_TEXT)
+                            .monospace(),
+                    );
+                }
+
+                let reserved = if pinnable { 60.0 } else { 40.0 };
+                let response = ui.add(
+                    egui::Button::new(&item.name)
+                        .fill(egui::Color32::TRANSPARENT)
+                        .min_size(Vec2::new(ui.available_width() - reserved, 24.0)),
+                );
+
+                if response.clicked() {
+                    self.pending_launch = Some(item.clone());
+                }
+
+                match marker {
+                    Some((icon, color)) => {
+                        ui.label(RichText::new(icon).color(color));
+                    }
+                    None => {
+                        if ui.small_button("pin").clicked() {
+                            self.pending_pin = Some(item.clone());
+                        }
+                    }
+                }
+            });
+            *shortcut_num += 1;
+        }
+        Self::separator(ui);
+    }
+
+    /// Draw the app search box; Enter launches the top result
+    fn app_search(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("\u{1F50D}").color(ThemeColors::DIM_TEXT)); // 🔍
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.app_search_query)
+                    .hint_text("Search apps...")
+                    .desired_width(ui.available_width() - 30.0),
+            );
+            if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                self.pending_launch = self.search_results.first().cloned();
+            }
+        });
+        Self::separator(ui);
+    }
+
+    /// Draw the clipboard history with fuzzy search and pinned entries
+    fn clipboard_section(&mut self, ui: &mut egui::Ui, pinned_clipboard: &[String], limit: usize) {
+        if self.clipboard_history.is_empty() {
+            return;
+        }
+        Self::section_header(ui, "Clipboard History");
+
+        // Search box
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("\u{1F50D}").color(ThemeColors::DIM_TEXT)); // 🔍
+            ui.add(
+                egui::TextEdit::singleline(&mut self.clipboard_search_query)
+                    .hint_text("Search clipboard...")
+                    .desired_width(ui.available_width() - 30.0),
+            );
+        });
+
+        ui.add_space(4.0);
+
+        // Fuzzy search results
+        let pinned_set: std::collections::HashSet<_> = pinned_clipboard.iter().collect();
+        let mut search_results =
+            fuzzy_search_clipboard(&self.clipboard_search_query, &self.clipboard_history, 50);
+        if self.clipboard_search_query.is_empty() {
+            snippets_first(&mut search_results, &self.context_snippets);
+        }
+
+        // Show non-pinned results (first `limit`)
+        let regular_results: Vec<_> = search_results
+            .iter()
+            .filter(|e| !pinned_set.contains(&e.text))
+            .take(limit)
+            .cloned()
+            .collect();
+
+        for entry in &regular_results {
+            ui.horizontal(|ui| {
+                let response = ui.add(
+                    egui::Button::new(&entry.preview)
+                        .fill(egui::Color32::TRANSPARENT)
+                        .min_size(Vec2::new(ui.available_width() - 60.0, 24.0)),
+                );
+
+                if response.clicked() {
+                    self.pending_paste = Some(entry.text.clone());
+                }
+
+                // Show full text on hover for long entries
+                if entry.text.len() > 40 {
+                    response.on_hover_text(&entry.text);
+                }
+
+                // Pin button
+                if ui.small_button("pin").clicked() {
+                    self.pending_pin_clipboard = Some(entry.text.clone());
+                }
+
+                ui.label(RichText::new("\u{1F4CB}").color(ThemeColors::CLIPBOARD_ICON));
+            });
+        }
+
+        // Show pinned clipboard section
+        if !pinned_clipboard.is_empty() {
+            ui.add_space(4.0);
+            ui.label(
+                RichText::new("Pinned")
+                    .color(ThemeColors::SECTION_HEADER)
+                    .size(11.0),
+            );
+
+            let query = &self.clipboard_search_query;
+            for text in pinned_clipboard {
+                // Filter by search query
+                if !query.is_empty() && fuzzy_score(query, text) == 0 {
+                    continue;
+                }
+
+                let preview = truncate_preview(text, 50);
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::Button::new(&preview)
+                            .fill(egui::Color32::TRANSPARENT)
+                            .min_size(Vec2::new(ui.available_width() - 60.0, 24.0)),
+                    );
+
+                    if response.clicked() {
+                        self.pending_paste = Some(text.clone());
+                    }
+
+                    // Show full text on hover for long entries
+                    if text.len() > 40 {
+                        response.on_hover_text(text);
+                    }
+
+                    // Unpin button
+                    if ui.small_button("x").clicked() {
+                        self.pending_unpin_clipboard = Some(text.clone());
+                    }
+
+                    ui.label(RichText::new("\u{1F4CC}").color(ThemeColors::PIN_ICON));
+                    // 📌
+                });
+            }
+        }
+        Self::separator(ui);
     }
 
     /// Draw the add shortcut dialog
@@ -556,7 +1046,7 @@ impl LauncherApp {
                             item_type: ItemType::Shortcut,
                         };
 
-                        let _ = self.config_manager.modify(|config| {
+                        self.modify_config(|config| {
                             config.add_shortcut(item);
                         });
 
@@ -569,6 +1059,15 @@ impl LauncherApp {
     }
 }
 
+/// Text added by external clients after the last frame is saved on close
+impl Drop for LauncherApp {
+    fn drop(&mut self) {
+        let control = self.control.clone();
+        control.set_context(None);
+        control.close_clipboard(|pending| self.add_external_clipboard(pending));
+    }
+}
+
 impl eframe::App for LauncherApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         // Check for config hot-reload
@@ -576,12 +1075,22 @@ impl eframe::App for LauncherApp {
             self.refresh();
         }
 
+        // Close if an external client asked us to hide
+        if self.control.take_hide_request() {
+            self.should_close = true;
+        }
+
         // Update clipboard history
         self.update_clipboard();
+        let external = self.control.take_clipboard();
+        self.add_external_clipboard(external);
 
         // Process any pending actions from previous frame
         self.process_pending_actions();
 
+        // Digits typed into a search box aren't shortcuts
+        let typing = ctx.wants_keyboard_input();
+
         // Handle keyboard shortcuts
         ctx.input(|i| {
             // Escape to close
@@ -589,15 +1098,12 @@ impl eframe::App for LauncherApp {
                 self.should_close = true;
             }
 
-            // Number keys 1-9 for shortcuts
-            let config = self.config_manager.get();
-            let mut all_items: Vec<LaunchItem> = Vec::new();
-            all_items.extend(config.pinned_programs.iter().cloned());
-            all_items.extend(self.frequent_programs.iter().cloned());
-            all_items.extend(config.pinned_documents.iter().cloned());
-            all_items.extend(self.recent_documents.iter().cloned());
-            all_items.extend(config.shortcuts.iter().cloned());
-            drop(config);
+            // Number keys 1-9 for shortcuts, in display order
+            if typing {
+                return;
+            }
+            let layout = self.config_manager.get().layout(self.profile.as_deref());
+            let all_items = self.numbered_items(&layout);
 
             for (idx, key) in [
                 Key::Num1,
@@ -634,24 +1140,22 @@ impl eframe::App for LauncherApp {
         }
 
         // Get config data we need (clone to avoid holding lock)
-        let (
-            pinned_programs,
-            pinned_documents,
-            pinned_clipboard,
-            shortcuts,
-            max_frequent_programs,
-            max_frequent_documents,
-        ) = {
+        let issues = self.config_manager.issues();
+        let save_error = self.save_error.clone();
+        let (layout, pinned_clipboard, profile_names) = {
             let config = self.config_manager.get();
             (
-                config.pinned_programs.clone(),
-                config.pinned_documents.clone(),
+                config.layout(self.profile.as_deref()),
                 config.pinned_clipboard.clone(),
-                config.shortcuts.clone(),
-                config.max_frequent_programs,
-                config.max_frequent_documents,
+                config
+                    .profiles
+                    .iter()
+                    .map(|p| p.name.clone())
+                    .collect::<Vec<_>>(),
             )
         };
+        let item_sections = self.item_sections(&layout);
+        self.update_search(&layout);
 
         // Main panel — ScrollArea with auto_shrink for hybrid adaptive height
         let mut resize_to: Option<f32> = None;
@@ -661,292 +1165,44 @@ impl eframe::App for LauncherApp {
                 .auto_shrink(true)
                 .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
                 .show(ui, |ui| {
-                    let mut shortcut_num = 1usize;
-
-                    // === Pinned Programs ===
-                    if !pinned_programs.is_empty() {
-                        Self::section_header(ui, "Pinned Programs");
-                        for item in &pinned_programs {
-                            ui.horizontal(|ui| {
-                                if shortcut_num <= 9 {
-                                    ui.label(
-                                        RichText::new(format!("[{}]", shortcut_num))
-                                            .color(ThemeColors::DIM_TEXT)
-                                            .monospace(),
-                                    );
-                                }
-
-                                let response = ui.add(
-                                    egui::Button::new(&item.name)
-                                        .fill(egui::Color32::TRANSPARENT)
-                                        .min_size(Vec2::new(ui.available_width() - 40.0, 24.0)),
-                                );
-
-                                if response.clicked() {
-                                    self.pending_launch = Some(item.clone());
-                                }
-
-                                ui.label(RichText::new("\u{1F4CC}").color(ThemeColors::PIN_ICON));
-                                // 📌
-                            });
-                            shortcut_num += 1;
-                        }
-                        Self::separator(ui);
-                    }
-
-                    // === Frequent Programs ===
-                    let frequent_programs: Vec<_> = self
-                        .frequent_programs
-                        .iter()
-                        .filter(|p| !pinned_programs.iter().any(|pp| same_item(pp, p)))
-                        .filter(|p| !shortcuts.iter().any(|s| same_item(s, p)))
-                        .take(max_frequent_programs)
-                        .cloned()
-                        .collect();
-
-                    if !frequent_programs.is_empty() {
-                        Self::section_header(ui, "Frequent Programs");
-                        for item in &frequent_programs {
-                            ui.horizontal(|ui| {
-                                if shortcut_num <= 9 {
-                                    ui.label(
-                                        RichText::new(format!("[{}]", shortcut_num))
-                                            .color(ThemeColors::DIM_TEXT)
-                                            .monospace(),
-                                    );
-                                }
-
-                                let response = ui.add(
-                                    egui::Button::new(&item.name)
-                                        .fill(egui::Color32::TRANSPARENT)
-                                        .min_size(Vec2::new(ui.available_width() - 60.0, 24.0)),
-                                );
-
-                                if response.clicked() {
-                                    self.pending_launch = Some(item.clone());
-                                }
-
-                                if ui.small_button("pin").clicked() {
-                                    self.pending_pin = Some(item.clone());
-                                }
-                            });
-                            shortcut_num += 1;
-                        }
-                        Self::separator(ui);
+                    // === Config problems ===
+                    if let Some(error) = &save_error {
+                        Self::save_error_banner(ui, error);
                     }
-
-                    // === Pinned Documents ===
-                    if !pinned_documents.is_empty() {
-                        Self::section_header(ui, "Pinned Documents");
-                        for item in &pinned_documents {
-                            ui.horizontal(|ui| {
-                                if shortcut_num <= 9 {
-                                    ui.label(
-                                        RichText::new(format!("[{}]", shortcut_num))
-                                            .color(ThemeColors::DIM_TEXT)
-                                            .monospace(),
-                                    );
-                                }
-
-                                let response = ui.add(
-                                    egui::Button::new(&item.name)
-                                        .fill(egui::Color32::TRANSPARENT)
-                                        .min_size(Vec2::new(ui.available_width() - 40.0, 24.0)),
-                                );
-
-                                if response.clicked() {
-                                    self.pending_launch = Some(item.clone());
-                                }
-
-                                ui.label(RichText::new("\u{1F4CC}").color(ThemeColors::PIN_ICON));
-                                // 📌
-                            });
-                            shortcut_num += 1;
-                        }
-                        Self::separator(ui);
+                    if !issues.is_empty() {
+                        Self::config_banner(ui, &issues);
                     }
 
-                    // === Recent Documents ===
-                    let recent_docs: Vec<_> = self
-                        .recent_documents
-                        .iter()
-                        .filter(|d| !pinned_documents.iter().any(|pd| same_item(pd, d)))
-                        .take(max_frequent_documents)
-                        .cloned()
-                        .collect();
-
-                    if !recent_docs.is_empty() {
-                        Self::section_header(ui, "Recent Documents");
-                        for item in &recent_docs {
-                            ui.horizontal(|ui| {
-                                if shortcut_num <= 9 {
-                                    ui.label(
-                                        RichText::new(format!("[{}]", shortcut_num))
-                                            .color(ThemeColors::DIM_TEXT)
-                                            .monospace(),
-                                    );
-                                }
-
-                                let response = ui.add(
-                                    egui::Button::new(&item.name)
-                                        .fill(egui::Color32::TRANSPARENT)
-                                        .min_size(Vec2::new(ui.available_width() - 60.0, 24.0)),
-                                );
-
-                                if response.clicked() {
-                                    self.pending_launch = Some(item.clone());
-                                }
-
-                                if ui.small_button("pin").clicked() {
-                                    self.pending_pin = Some(item.clone());
-                                }
-                            });
-                            shortcut_num += 1;
-                        }
-                        Self::separator(ui);
+                    // === Profile switcher ===
+                    if !profile_names.is_empty() {
+                        self.profile_header(ui, &profile_names);
                     }
 
-                    // === Shortcuts ===
-                    if !shortcuts.is_empty() {
-                        Self::section_header(ui, "Shortcuts");
-                        for item in &shortcuts {
-                            ui.horizontal(|ui| {
-                                if shortcut_num <= 9 {
-                                    ui.label(
-                                        RichText::new(format!("[{}]", shortcut_num))
-                                            .color(ThemeColors::DIM_TEXT)
-                                            .monospace(),
-                                    );
-                                }
-
-                                let response = ui.add(
-                                    egui::Button::new(&item.name)
-                                        .fill(egui::Color32::TRANSPARENT)
-                                        .min_size(Vec2::new(ui.available_width() - 40.0, 24.0)),
-                                );
-
-                                if response.clicked() {
-                                    self.pending_launch = Some(item.clone());
-                                }
+                    // === App search ===
+                    self.app_search(ui);
 
-                                ui.label(
-                                    RichText::new("\u{26A1}").color(ThemeColors::SHORTCUT_ICON),
-                                );
-                                // ⚡
-                            });
-                            shortcut_num += 1;
+                    let mut shortcut_num = 1usize;
+                    if !self.app_search_query.trim().is_empty() {
+                        let results = self.search_results.clone();
+                        if results.is_empty() {
+                            ui.label(RichText::new("No matches").color(ThemeColors::DIM_TEXT));
+                        } else {
+                            self.item_list(ui, "Search Results", &results, None, &mut shortcut_num);
                         }
-                        Self::separator(ui);
-                    }
-
-                    // === Clipboard History with Fuzzy Search ===
-                    if !self.clipboard_history.is_empty() {
-                        Self::section_header(ui, "Clipboard History");
-
-                        // Search box
-                        ui.horizontal(|ui| {
-                            ui.label(RichText::new("\u{1F50D}").color(ThemeColors::DIM_TEXT)); // 🔍
-                            ui.add(
-                                egui::TextEdit::singleline(&mut self.clipboard_search_query)
-                                    .hint_text("Search clipboard...")
-                                    .desired_width(ui.available_width() - 30.0),
-                            );
-                        });
-
-                        ui.add_space(4.0);
-
-                        // Fuzzy search results
-                        let pinned_set: std::collections::HashSet<_> =
-                            pinned_clipboard.iter().collect();
-                        let search_results = fuzzy_search_clipboard(
-                            &self.clipboard_search_query,
-                            &self.clipboard_history,
-                            50,
-                        );
-
-                        // Show non-pinned results (first 10)
-                        let regular_results: Vec<_> = search_results
-                            .iter()
-                            .filter(|e| !pinned_set.contains(&e.text))
-                            .take(CLIPBOARD_DISPLAY_LIMIT)
-                            .cloned()
-                            .collect();
-
-                        for entry in &regular_results {
-                            ui.horizontal(|ui| {
-                                let response = ui.add(
-                                    egui::Button::new(&entry.preview)
-                                        .fill(egui::Color32::TRANSPARENT)
-                                        .min_size(Vec2::new(ui.available_width() - 60.0, 24.0)),
-                                );
-
-                                if response.clicked() {
-                                    self.pending_paste = Some(entry.text.clone());
-                                }
-
-                                // Show full text on hover for long entries
-                                if entry.text.len() > 40 {
-                                    response.on_hover_text(&entry.text);
-                                }
-
-                                // Pin button
-                                if ui.small_button("pin").clicked() {
-                                    self.pending_pin_clipboard = Some(entry.text.clone());
-                                }
-
-                                ui.label(
-                                    RichText::new("\u{1F4CB}").color(ThemeColors::CLIPBOARD_ICON),
+                    } else {
+                        for section in &layout.sections {
+                            if *section == Section::Clipboard {
+                                self.clipboard_section(
+                                    ui,
+                                    &pinned_clipboard,
+                                    layout.max_clipboard_items,
                                 );
-                            });
-                        }
-
-                        // Show pinned clipboard section
-                        if !pinned_clipboard.is_empty() {
-                            ui.add_space(4.0);
-                            ui.label(
-                                RichText::new("Pinned")
-                                    .color(ThemeColors::SECTION_HEADER)
-                                    .size(11.0),
-                            );
-
-                            let query = &self.clipboard_search_query;
-                            for text in &pinned_clipboard {
-                                // Filter by search query
-                                if !query.is_empty() && fuzzy_score(query, text) == 0 {
-                                    continue;
-                                }
-
-                                let preview = truncate_preview(text, 50);
-
-                                ui.horizontal(|ui| {
-                                    let response = ui.add(
-                                        egui::Button::new(&preview)
-                                            .fill(egui::Color32::TRANSPARENT)
-                                            .min_size(Vec2::new(ui.available_width() - 60.0, 24.0)),
-                                    );
-
-                                    if response.clicked() {
-                                        self.pending_paste = Some(text.clone());
-                                    }
-
-                                    // Show full text on hover for long entries
-                                    if text.len() > 40 {
-                                        response.on_hover_text(text);
-                                    }
-
-                                    // Unpin button
-                                    if ui.small_button("x").clicked() {
-                                        self.pending_unpin_clipboard = Some(text.clone());
-                                    }
-
-                                    ui.label(
-                                        RichText::new("\u{1F4CC}").color(ThemeColors::PIN_ICON),
-                                    );
-                                    // 📌
-                                });
+                            } else if let Some((_, items)) =
+                                item_sections.iter().find(|(s, _)| s == section)
+                            {
+                                self.item_section(ui, *section, items, &mut shortcut_num);
                             }
                         }
-                        Self::separator(ui);
                     }
 
                     // === Add Shortcut Button ===
@@ -962,10 +1218,16 @@ impl eframe::App for LauncherApp {
             // Adaptive height from scroll content_size (unbounded content height)
             let content_h = scroll_output.content_size.y;
             let chrome = 16.0;
-            let monitor_h = ctx
-                .input(|i| i.viewport().monitor_size.map(|s| s.y))
-                .unwrap_or(1080.0);
-            let max_h = (monitor_h - 50.0).max(60.0);
+            let max_h = match self.anchor.geometry((self.width, 0.0)).max_height {
+                Some(h) => h,
+                None => {
+                    let monitor_h = ctx
+                        .input(|i| i.viewport().monitor_size.map(|s| s.y))
+                        .unwrap_or(1080.0);
+                    monitor_h - 50.0
+                }
+            }
+            .max(60.0);
             let desired = (content_h + chrome).clamp(60.0, max_h);
             if content_h > 10.0
                 && (desired - self.last_height).abs() > 2.0
@@ -980,6 +1242,15 @@ impl eframe::App for LauncherApp {
         // Apply resize OUTSIDE the panel closure to prevent re-render loop
         if let Some(h) = resize_to {
             ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(self.width, h)));
+
+            // Growing may push the popup past a monitor edge: re-place it
+            let position = self.anchor.geometry((self.width, h)).position;
+            if position != self.position {
+                self.position = position;
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(
+                    position.0, position.1,
+                )));
+            }
         }
 
         // Show add dialog if requested
@@ -999,20 +1270,42 @@ impl eframe::App for LauncherApp {
 }
 
 /// Create and run the launcher popup window
+///
+/// `position` is the cursor position and `monitors` the current monitor
+/// layout (may be empty), both in the coordinate space of the cursor source.
+/// `profile` is the popup profile requested by the trigger; without one the
+/// profile scheduled for the current time (or the default) opens.
+/// `context` is the app id of the focused window, used to surface the
+/// programs and snippets used over it.
 pub fn run_popup(
     position: (f64, f64),
+    monitors: Vec<Monitor>,
+    profile: Option<String>,
+    context: Option<String>,
     config_manager: Arc<ConfigManager>,
     usage_tracker: Arc<Mutex<UsageTracker>>,
+    control: PopupControl,
 ) -> Result<(), eframe::Error> {
-    let width = {
+    let (width, anchor, profile) = {
         let config = config_manager.get();
-        config.ui.width
+        let profile = config.select_profile(profile.as_deref(), Local::now().naive_local());
+        let anchor = PopupAnchor {
+            placement: config.ui.placement,
+            cursor: position,
+            margin: config.ui.margin,
+            monitors,
+        };
+        (config.ui.width, anchor, profile)
     };
+    let geometry = anchor.geometry((width, 400.0));
+
+    // Discard hide requests that arrived while no popup was open
+    control.take_hide_request();
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([width, 400.0])
-            .with_position([position.0 as f32, position.1 as f32])
+            .with_position([geometry.position.0, geometry.position.1])
             .with_decorations(false)
             .with_transparent(true)
             .with_always_on_top(),
@@ -1022,11 +1315,16 @@ pub fn run_popup(
     eframe::run_native(
         "Launcher",
         options,
-        Box::new(move |_cc| {
+        Box::new(move |cc| {
+            control.set_context(Some(cc.egui_ctx.clone()));
             Ok(Box::new(LauncherApp::new(
                 config_manager.clone(),
                 usage_tracker.clone(),
+                control.clone(),
+                anchor.clone(),
                 width,
+                profile.clone(),
+                context.clone(),
             )))
         }),
     )
@@ -0,0 +1,142 @@
+//! Shared handle for controlling the popup from outside the UI thread
+
+use eframe::egui;
+use launcher_core::LaunchItem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Cloneable handle used by external clients (D-Bus, CLI) to talk to the popup
+///
+/// The popup runs on the main thread and blocks it while open, so requests
+/// are passed through shared state that the popup polls every frame.
+#[derive(Clone, Default)]
+pub struct PopupControl {
+    hide_requested: Arc<AtomicBool>,
+    quit_requested: Arc<AtomicBool>,
+    /// Context of the open popup, to wake it for requests
+    context: Arc<Mutex<Option<egui::Context>>>,
+    launch_subscribers: Arc<Mutex<Vec<Sender<LaunchItem>>>>,
+    clipboard: Arc<Mutex<ClipboardInbox>>,
+}
+
+/// Clipboard text from external clients for the open popup
+#[derive(Default)]
+struct ClipboardInbox {
+    popup_open: bool,
+    pending: Vec<String>,
+}
+
+impl PopupControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the open popup (if any) to close
+    pub fn request_hide(&self) {
+        self.hide_requested.store(true, Ordering::SeqCst);
+        self.wake();
+    }
+
+    /// Close the popup and ask the main loop to exit
+    pub fn request_quit(&self) {
+        self.quit_requested.store(true, Ordering::SeqCst);
+        self.request_hide();
+    }
+
+    /// Whether the launcher should exit
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested.load(Ordering::SeqCst)
+    }
+
+    /// Set (or clear) the context of the open popup
+    pub fn set_context(&self, context: Option<egui::Context>) {
+        *self.context.lock().unwrap_or_else(|e| e.into_inner()) = context;
+    }
+
+    /// Repaint the open popup so it handles requests without waiting for input
+    fn wake(&self) {
+        if let Some(context) = &*self.context.lock().unwrap_or_else(|e| e.into_inner()) {
+            context.request_repaint();
+        }
+    }
+
+    /// Check and clear a pending hide request (non-blocking)
+    pub fn take_hide_request(&self) -> bool {
+        self.hide_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Hand clipboard text to the open popup, which owns the history file
+    /// while open; without a popup, `save` adds it to the file directly
+    ///
+    /// The popup can't open or close while `save` runs.
+    pub fn add_clipboard(&self, text: String, save: impl FnOnce(&str)) {
+        let mut inbox = self.clipboard.lock().unwrap_or_else(|e| e.into_inner());
+        if inbox.popup_open {
+            inbox.pending.push(text);
+            drop(inbox);
+            self.wake();
+        } else {
+            save(&text);
+        }
+    }
+
+    /// Mark the popup open; `load` runs before external clipboard text
+    /// goes to the popup instead of the file
+    pub fn open_clipboard<T>(&self, load: impl FnOnce() -> T) -> T {
+        let mut inbox = self.clipboard.lock().unwrap_or_else(|e| e.into_inner());
+        inbox.popup_open = true;
+        load()
+    }
+
+    /// Take the clipboard text that arrived for the open popup
+    pub fn take_clipboard(&self) -> Vec<String> {
+        let mut inbox = self.clipboard.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut inbox.pending)
+    }
+
+    /// Mark the popup closed, with `save` getting the text still pending
+    pub fn close_clipboard(&self, save: impl FnOnce(Vec<String>)) {
+        let mut inbox = self.clipboard.lock().unwrap_or_else(|e| e.into_inner());
+        inbox.popup_open = false;
+        save(std::mem::take(&mut inbox.pending));
+    }
+
+    /// Receive every item launched through the popup or an external client
+    pub fn subscribe_launches(&self) -> Receiver<LaunchItem> {
+        let (tx, rx) = channel();
+        if let Ok(mut subscribers) = self.launch_subscribers.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+
+    /// Notify subscribers that an item was launched
+    pub fn notify_launched(&self, item: &LaunchItem) {
+        if let Ok(mut subscribers) = self.launch_subscribers.lock() {
+            // Drop subscribers whose receiver has gone away
+            subscribers.retain(|tx| tx.send(item.clone()).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipboard_goes_to_open_popup() {
+        let control = PopupControl::new();
+        let mut saved = Vec::new();
+        control.add_clipboard("before".to_string(), |text| saved.push(text.to_string()));
+
+        control.open_clipboard(|| ());
+        control.add_clipboard("while open".to_string(), |_| panic!("popup is open"));
+        assert_eq!(control.take_clipboard(), ["while open"]);
+        control.add_clipboard("at close".to_string(), |_| panic!("popup is open"));
+        control.close_clipboard(|pending| saved.extend(pending));
+
+        control.add_clipboard("after".to_string(), |text| saved.push(text.to_string()));
+        assert_eq!(saved, ["before", "at close", "after"]);
+    }
+}
@@ -1,6 +1,10 @@
 //! UI components for the program launcher
 
 pub mod app;
+pub mod control;
+pub mod placement;
 pub mod theme;
 
-pub use app::{run_popup, LauncherApp};
+pub use app::{add_clipboard_entry, clipboard_paste_counts, run_popup, LauncherApp};
+pub use control::PopupControl;
+pub use placement::Monitor;
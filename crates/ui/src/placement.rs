@@ -0,0 +1,209 @@
+//! Popup placement across monitors
+//!
+//! Monitor geometry and the cursor are in physical pixels (as reported by
+//! X11/compositors); popup size and margin are in logical points and scaled
+//! by the monitor the popup lands on.
+
+use launcher_core::{Corner, Placement};
+
+/// A monitor in the global physical pixel space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Monitor {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Physical pixels per logical point
+    pub scale: f64,
+}
+
+impl Monitor {
+    fn contains(&self, (px, py): (f64, f64)) -> bool {
+        px >= self.x && px < self.x + self.width && py >= self.y && py < self.y + self.height
+    }
+
+    /// Squared distance from a point to the monitor rectangle
+    fn distance2(&self, (px, py): (f64, f64)) -> f64 {
+        let dx = (self.x - px).max(px - (self.x + self.width)).max(0.0);
+        let dy = (self.y - py).max(py - (self.y + self.height)).max(0.0);
+        dx * dx + dy * dy
+    }
+}
+
+/// Where to put the popup and how tall it may grow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PopupGeometry {
+    /// Top-left corner in logical points: the monitor's origin plus the
+    /// scaled offset within it
+    pub position: (f32, f32),
+    /// Usable monitor height in logical points, if known
+    pub max_height: Option<f32>,
+}
+
+/// What the popup is placed relative to, kept so it can be re-placed as it grows
+#[derive(Debug, Clone)]
+pub struct PopupAnchor {
+    pub placement: Placement,
+    pub cursor: (f64, f64),
+    pub margin: f32,
+    pub monitors: Vec<Monitor>,
+}
+
+impl PopupAnchor {
+    pub fn geometry(&self, size: (f32, f32)) -> PopupGeometry {
+        place(
+            self.placement,
+            self.cursor,
+            size,
+            self.margin,
+            &self.monitors,
+        )
+    }
+}
+
+/// Find the monitor containing a point, or the nearest one
+pub fn monitor_at(monitors: &[Monitor], point: (f64, f64)) -> Option<&Monitor> {
+    monitors.iter().find(|m| m.contains(point)).or_else(|| {
+        monitors
+            .iter()
+            .min_by(|a, b| a.distance2(point).total_cmp(&b.distance2(point)))
+    })
+}
+
+/// Compute the popup position for a placement mode
+///
+/// `size` and `margin` are logical. Without monitor information the popup is
+/// placed at the cursor unchanged.
+pub fn place(
+    placement: Placement,
+    cursor: (f64, f64),
+    size: (f32, f32),
+    margin: f32,
+    monitors: &[Monitor],
+) -> PopupGeometry {
+    let Some(monitor) = monitor_at(monitors, cursor) else {
+        return PopupGeometry {
+            position: (cursor.0 as f32, cursor.1 as f32),
+            max_height: None,
+        };
+    };
+
+    let scale = monitor.scale.max(0.1);
+    let margin = margin as f64 * scale;
+    let (left, top) = (monitor.x + margin, monitor.y + margin);
+    let (right, bottom) = (
+        monitor.x + monitor.width - margin,
+        monitor.y + monitor.height - margin,
+    );
+    let width = (size.0 as f64 * scale).min(right - left);
+    let height = (size.1 as f64 * scale).min(bottom - top);
+
+    let (x, y) = match placement {
+        Placement::AtCursor => (
+            flip(cursor.0, width, left, right),
+            flip(cursor.1, height, top, bottom),
+        ),
+        Placement::Centered => ((left + right - width) / 2.0, (top + bottom - height) / 2.0),
+        Placement::Corner { corner } => match corner {
+            Corner::TopLeft => (left, top),
+            Corner::TopRight => (right - width, top),
+            Corner::BottomLeft => (left, bottom - height),
+            Corner::BottomRight => (right - width, bottom - height),
+        },
+    };
+
+    // Monitor origins stay where they are; only the offset within the
+    // monitor is scaled, or popups on a monitor right of a scaled one would
+    // land on the wrong monitor
+    PopupGeometry {
+        position: (
+            (monitor.x + (x - monitor.x) / scale) as f32,
+            (monitor.y + (y - monitor.y) / scale) as f32,
+        ),
+        max_height: Some(((bottom - top) / scale) as f32),
+    }
+}
+
+/// Open towards higher coordinates if it fits, else flip to end at `at`
+fn flip(at: f64, extent: f64, low: f64, high: f64) -> f64 {
+    let start = if at + extent <= high { at } else { at - extent };
+    start.clamp(low, (high - extent).max(low))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitors() -> Vec<Monitor> {
+        vec![
+            Monitor {
+                x: 0.0,
+                y: 0.0,
+                width: 1920.0,
+                height: 1080.0,
+                scale: 1.0,
+            },
+            Monitor {
+                x: 1920.0,
+                y: 0.0,
+                width: 2560.0,
+                height: 1440.0,
+                scale: 2.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_at_cursor_flips_near_edges() {
+        let m = monitors();
+
+        let g = place(Placement::AtCursor, (100.0, 100.0), (300.0, 400.0), 4.0, &m);
+        assert_eq!(g.position, (100.0, 100.0));
+        assert_eq!(g.max_height, Some(1072.0));
+
+        // Near the bottom-right of the first monitor: open left and up
+        let g = place(
+            Placement::AtCursor,
+            (1900.0, 1000.0),
+            (300.0, 400.0),
+            4.0,
+            &m,
+        );
+        assert_eq!(g.position, (1600.0, 600.0));
+    }
+
+    #[test]
+    fn test_scaled_monitor() {
+        let m = monitors();
+
+        // Second monitor is 2x: 300x400 logical is 600x800 physical
+        let g = place(
+            Placement::AtCursor,
+            (4400.0, 100.0),
+            (300.0, 400.0),
+            4.0,
+            &m,
+        );
+        // Flipped left to end at the cursor: 3800 physical, 940 into the monitor
+        assert_eq!(g.position, (2860.0, 50.0));
+
+        let g = place(Placement::Centered, (2000.0, 10.0), (300.0, 400.0), 4.0, &m);
+        assert_eq!(g.position, (2410.0, 160.0));
+
+        let corner = Placement::Corner {
+            corner: Corner::BottomRight,
+        };
+        let g = place(corner, (2000.0, 10.0), (300.0, 400.0), 4.0, &m);
+        assert_eq!(g.position, (2896.0, 316.0));
+    }
+
+    #[test]
+    fn test_cursor_outside_monitors() {
+        let m = monitors();
+        assert_eq!(monitor_at(&m, (5000.0, 100.0)), Some(&m[1]));
+
+        let g = place(Placement::AtCursor, (10.0, 10.0), (300.0, 400.0), 4.0, &[]);
+        assert_eq!(g.position, (10.0, 10.0));
+        assert_eq!(g.max_height, None);
+    }
+}
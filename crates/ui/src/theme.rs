@@ -4,10 +4,11 @@ use egui::{Color32, CornerRadius, FontFamily, FontId, Stroke, Style, TextStyle,
 
 /// Create the dark theme for the launcher
 pub fn dark_theme() -> Style {
-    let mut style = Style::default();
-
     // Dark mode visuals
-    style.visuals = Visuals::dark();
+    let mut style = Style {
+        visuals: Visuals::dark(),
+        ..Default::default()
+    };
 
     // Custom colors
     let bg_color = Color32::from_rgb(30, 30, 35);
@@ -99,4 +100,6 @@ impl ThemeColors {
     pub const SHORTCUT_ICON: Color32 = Color32::from_rgb(255, 150, 50); // Orange
     pub const CLIPBOARD_ICON: Color32 = Color32::from_rgb(100, 200, 150); // Teal
     pub const SECTION_HEADER: Color32 = Color32::from_rgb(120, 120, 140);
+    pub const ERROR: Color32 = Color32::from_rgb(235, 90, 90); // Red
+    pub const WARNING: Color32 = Color32::from_rgb(230, 180, 60); // Amber
 }